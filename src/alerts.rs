@@ -1,9 +1,13 @@
-use crate::communications::CommunicationRegistry;
-use crate::config::AppConfig;
+use crate::audit::AuditLog;
+use crate::communications::{CommunicationRegistry, SendOutcome};
+use crate::config::{AppConfig, TimestampFormat};
+use crate::transforms::TransformChain;
 use anyhow::Context;
-use log::{debug, warn};
+use log::{debug, error, info, warn};
 
-#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+const SHUTDOWN_ALERT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize)]
 pub(crate) enum AlertLevel {
     Info,
     Warning,
@@ -34,12 +38,60 @@ impl TryFrom<u8> for AlertLevel {
     }
 }
 
+#[derive(Default, Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) struct AlertMetadata {
+    /// Providers that must not send this alert.
+    #[serde(default)]
+    pub skip_providers: Option<std::collections::HashSet<String>>,
+
+    /// If set, only these providers may send this alert.
+    #[serde(default)]
+    pub only_providers: Option<std::collections::HashSet<String>>,
+
+    /// Bypass alarm confirmation and cooldown, and always broadcast to every provider.
+    #[serde(default)]
+    pub unsuppressible: bool,
+
+    /// The alert reports that a previously alerted problem from the same source has cleared.
+    #[serde(default)]
+    pub recovery: bool,
+
+    /// The monitored target within the source that the alert is about, such as a service name.
+    #[serde(default)]
+    pub subject: Option<String>,
+
+    /// A problem that's followed by a recovery alert from the same source and subject once it clears.
+    #[serde(default)]
+    pub recoverable: bool,
+
+    /// A scheduled drill, which is itself alerted on if it isn't delivered.
+    #[serde(default)]
+    pub test: bool,
+}
+impl AlertMetadata {
+    /// Check if the named provider is allowed to send the alert.
+    pub fn allows_provider(&self, name: &str) -> bool {
+        if let Some(skip_providers) = &self.skip_providers {
+            if skip_providers.contains(name) {
+                return false;
+            }
+        }
+        match &self.only_providers {
+            Some(only_providers) => only_providers.contains(name),
+            None => true,
+        }
+    }
+}
+
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub(crate) struct AlertInfo {
     pub source: String,
     pub message: String,
     pub level: AlertLevel,
     pub timestamp: Option<u64>,
+
+    #[serde(default)]
+    pub metadata: AlertMetadata,
 }
 impl AlertInfo {
     pub fn new(source: String, message: String, level: AlertLevel) -> anyhow::Result<Self> {
@@ -49,13 +101,54 @@ impl AlertInfo {
             message,
             level,
             timestamp: Some(timestamp.as_secs()),
+            metadata: AlertMetadata::default(),
         })
     }
 
+    /// Set the timestamp to now if it was never set, so every delivered alert carries one.
+    pub fn backfill_timestamp(&mut self) {
+        if self.timestamp.is_none() {
+            self.timestamp = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .ok()
+                .map(|timestamp| timestamp.as_secs());
+        }
+    }
+
     #[inline]
     pub fn is_alarm(&self) -> bool {
         self.level == AlertLevel::Alarm
     }
+
+    /// Render the timestamp in a human-readable form, if there is one to render.
+    pub fn format_timestamp(&self, format: &TimestampFormat) -> Option<String> {
+        let timestamp = self.timestamp?;
+        match format {
+            TimestampFormat::None => None,
+            TimestampFormat::Absolute => {
+                time::OffsetDateTime::from_unix_timestamp(timestamp as i64)
+                    .ok()?
+                    .format(time::macros::format_description!(
+                        "[year]-[month]-[day] [hour]:[minute] UTC"
+                    ))
+                    .ok()
+            }
+            TimestampFormat::Relative => {
+                let now = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .ok()?
+                    .as_secs();
+
+                let age = now.saturating_sub(timestamp);
+                Some(match age {
+                    0..60 => format!("{age}s ago"),
+                    60..3600 => format!("{}m ago", age / 60),
+                    3600..86400 => format!("{}h ago", age / 3600),
+                    _ => format!("{}d ago", age / 86400),
+                })
+            }
+        }
+    }
 }
 impl std::fmt::Display for AlertInfo {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -63,12 +156,147 @@ impl std::fmt::Display for AlertInfo {
     }
 }
 
+struct SourceWindow {
+    started: tokio::time::Instant,
+    count: u32,
+    dropped: u32,
+}
+
+#[derive(Debug, PartialEq)]
+enum RateDecision {
+    /// Queue the alert, also summarizing any alerts dropped in the previous window.
+    Allowed {
+        dropped: u32,
+    },
+    Dropped {
+        first: bool,
+    },
+}
+
+/// Caps how many non-alarm alerts each source can queue per window, so one
+/// misbehaving source can't fill the channel and starve everything else.
+struct SourceRateLimit {
+    max: u32,
+    window: std::time::Duration,
+    sources: std::sync::Mutex<std::collections::HashMap<String, SourceWindow>>,
+}
+impl SourceRateLimit {
+    fn new(max: u32, window: std::time::Duration) -> Self {
+        Self {
+            max,
+            window,
+            sources: std::sync::Mutex::new(std::collections::HashMap::new()),
+        }
+    }
+
+    fn check(&self, source: &str) -> RateDecision {
+        let now = tokio::time::Instant::now();
+        let mut sources = self.sources.lock().unwrap_or_else(|e| e.into_inner());
+        let window = sources.entry(source.to_string()).or_insert(SourceWindow {
+            started: now,
+            count: 0,
+            dropped: 0,
+        });
+
+        let mut dropped = 0;
+        if now.duration_since(window.started) >= self.window {
+            dropped = window.dropped;
+            *window = SourceWindow {
+                started: now,
+                count: 0,
+                dropped: 0,
+            };
+        }
+
+        if window.count < self.max {
+            window.count += 1;
+            RateDecision::Allowed { dropped }
+        } else {
+            window.dropped += 1;
+            RateDecision::Dropped {
+                first: window.dropped == 1,
+            }
+        }
+    }
+
+    /// Evict every expired window, returning the sources that had alerts dropped in theirs,
+    /// so their summary isn't lost when the source never sends again.
+    fn sweep(&self) -> Vec<(String, u32)> {
+        let now = tokio::time::Instant::now();
+        let mut sources = self.sources.lock().unwrap_or_else(|e| e.into_inner());
+        let mut dropped = Vec::new();
+        sources.retain(|source, window| {
+            if now.duration_since(window.started) < self.window {
+                return true;
+            }
+            if window.dropped > 0 {
+                dropped.push((source.clone(), window.dropped));
+            }
+            false
+        });
+        dropped
+    }
+}
+
+fn dropped_summary(source: String, dropped: u32) -> anyhow::Result<AlertInfo> {
+    AlertInfo::new(
+        source,
+        format!("Dropped {dropped} alert(s) over the rate limit!"),
+        AlertLevel::Warning,
+    )
+}
+
 #[derive(Clone)]
 pub(crate) struct AlertSender {
     sender: tokio::sync::mpsc::Sender<AlertInfo>,
+    rate_limit: Option<std::sync::Arc<SourceRateLimit>>,
 }
 impl AlertSender {
-    pub async fn send(&self, alert: AlertInfo) -> anyhow::Result<()> {
+    /// Number of alerts that can currently be queued without waiting.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.sender.capacity()
+    }
+
+    /// Number of alerts currently waiting in the channel.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.sender.max_capacity() - self.capacity()
+    }
+
+    /// Fraction of the channel that is currently filled, from 0 to 1.
+    pub fn fill_level(&self) -> f32 {
+        self.len() as f32 / self.sender.max_capacity() as f32
+    }
+
+    pub async fn send(&self, mut alert: AlertInfo) -> anyhow::Result<()> {
+        if let Some(rate_limit) = self.rate_limit.as_ref().filter(|_| !alert.is_alarm()) {
+            match rate_limit.check(&alert.source) {
+                RateDecision::Allowed { dropped: 0 } => {}
+                RateDecision::Allowed { dropped } => {
+                    // Summarized once the source is next allowed, rather than per drop.
+                    self.queue(dropped_summary(alert.source.clone(), dropped)?)
+                        .await?;
+                }
+                RateDecision::Dropped { first } => {
+                    if first {
+                        warn!(
+                            "Source '{}' is over the rate limit, dropping its alerts!",
+                            alert.source
+                        );
+                    }
+                    debug!("Dropped rate limited alert: {alert}");
+                    return Ok(());
+                }
+            }
+        }
+
+        // Stamp at enqueue time rather than dequeue, so a backlog doesn't skew it.
+        alert.backfill_timestamp();
+        self.queue(alert).await
+    }
+
+    async fn queue(&self, alert: AlertInfo) -> anyhow::Result<()> {
         self.sender
             .send(alert)
             .await
@@ -76,58 +304,349 @@ impl AlertSender {
     }
 }
 
+/// Problem alerts held after startup, since monitors report transient failures while
+/// the network and services are still coming up.
+struct BootSettling {
+    until: tokio::time::Instant,
+    held: Vec<AlertInfo>,
+}
+
 pub(crate) struct AlertManager {
     alarm_cooldown: tokio::time::Duration,
     alarm_last: std::sync::Arc<tokio::sync::RwLock<Option<tokio::time::Instant>>>,
+    alarm_confirmation_window: Option<tokio::time::Duration>,
+    alarm_confirmation_sources: Option<std::collections::HashSet<String>>,
+    alarm_pending: tokio::sync::Mutex<Option<tokio::time::Instant>>,
     communications: std::sync::Arc<CommunicationRegistry>,
+    audit: Option<std::sync::Arc<AuditLog>>,
+    transforms: TransformChain,
     semaphore: std::sync::Arc<tokio::sync::Semaphore>,
+    settling: Option<BootSettling>,
+    rate_limit: Option<std::sync::Arc<SourceRateLimit>>,
     receiver: tokio::sync::mpsc::Receiver<AlertInfo>,
+
+    // Broadcasts still sending, awaited on shutdown so they outlive the runtime.
+    broadcasts: std::sync::Mutex<tokio::task::JoinSet<()>>,
 }
 impl AlertManager {
-    pub fn new(config: &AppConfig) -> anyhow::Result<(Self, AlertSender)> {
+    pub async fn new(config: &AppConfig) -> anyhow::Result<(Self, AlertSender)> {
         let registry = CommunicationRegistry::new(&config.communications)
+            .await
             .context("Failed to initialize communication registry!")?;
+        Ok(Self::with_registry(config, registry))
+    }
 
+    fn with_registry(config: &AppConfig, registry: CommunicationRegistry) -> (Self, AlertSender) {
         let (sender, receiver) = tokio::sync::mpsc::channel::<AlertInfo>(100);
-        Ok((
+        let rate_limit = config.alerts.source_rate_limit.map(|max| {
+            std::sync::Arc::new(SourceRateLimit::new(
+                max,
+                std::time::Duration::from_secs(config.alerts.source_rate_window),
+            ))
+        });
+        (
             Self {
                 alarm_cooldown: tokio::time::Duration::from_secs(config.alerts.alarm_cooldown),
                 alarm_last: std::sync::Arc::new(tokio::sync::RwLock::new(None)),
+                alarm_confirmation_window: config
+                    .alerts
+                    .alarm_confirmation_window
+                    .map(tokio::time::Duration::from_secs),
+                alarm_confirmation_sources: config.alerts.alarm_confirmation_sources.clone(),
+                alarm_pending: tokio::sync::Mutex::new(None),
 
                 communications: std::sync::Arc::new(registry),
+                audit: AuditLog::from_config(&config.alerts).map(std::sync::Arc::new),
+                transforms: TransformChain::from_config(&config.alerts),
                 semaphore: std::sync::Arc::new(tokio::sync::Semaphore::new(
                     config.alerts.send_concurrency_limit,
                 )),
+                settling: config.alerts.boot_settle.map(|boot_settle| BootSettling {
+                    until: tokio::time::Instant::now()
+                        + tokio::time::Duration::from_secs(boot_settle),
+                    held: vec![],
+                }),
+                rate_limit: rate_limit.clone(),
                 receiver,
+                broadcasts: std::sync::Mutex::new(tokio::task::JoinSet::new()),
             },
-            AlertSender { sender },
-        ))
+            AlertSender { sender, rate_limit },
+        )
     }
 
-    pub async fn run(mut self) -> anyhow::Result<()> {
+    /// Process alerts until the shutdown reason is received, which is then broadcast
+    /// as a final alert so remote operators know why the system went quiet. Alerts
+    /// already queued or sending are delivered first, such as from monitors stopping.
+    pub async fn run(
+        mut self,
+        mut shutdown: tokio::sync::oneshot::Receiver<(String, AlertLevel)>,
+    ) -> anyhow::Result<()> {
         debug!("AlertManager starting to process channel alerts...");
         self.execute(AlertInfo {
             source: "startup".to_string(),
             message: "Sentinel is online".to_string(),
             level: AlertLevel::Info,
             timestamp: None,
+            metadata: AlertMetadata::default(),
         })
         .await;
 
-        while let Some(alert) = self.receiver.recv().await {
-            self.execute(alert).await;
+        let mut rate_sweep = self
+            .rate_limit
+            .as_ref()
+            .map(|rate_limit| tokio::time::interval(rate_limit.window));
+        loop {
+            let swept = async {
+                match rate_sweep.as_mut() {
+                    Some(rate_sweep) => {
+                        rate_sweep.tick().await;
+                    }
+                    None => std::future::pending().await,
+                }
+            };
+            let settle_until = self.settling.as_ref().map(|settling| settling.until);
+            let settled = async move {
+                match settle_until {
+                    Some(until) => tokio::time::sleep_until(until).await,
+                    None => std::future::pending().await,
+                }
+            };
+
+            // Queued alerts go first, so none are left behind once shutdown is received.
+            tokio::select! {
+                biased;
+
+                alert = self.receiver.recv() => match alert {
+                    Some(alert) => {
+                        if let Some(alert) = self.hold_while_settling(alert) {
+                            self.execute(alert).await;
+                        }
+                    }
+                    None => break,
+                },
+                _ = settled => self.finish_settling().await,
+                _ = swept => self.flush_rate_limited().await,
+                reason = &mut shutdown => {
+                    self.finish_settling().await;
+                    self.drain().await;
+                    match reason {
+                        Ok((reason, level)) => {
+                            self.broadcast_shutdown(AlertInfo {
+                                source: "sentinel".to_string(),
+                                message: format!("Shutting down: {reason}"),
+                                level,
+                                timestamp: None,
+                                metadata: AlertMetadata::default(),
+                            })
+                            .await;
+                            return Ok(());
+                        }
+                        // The shutdown flow died without giving a reason.
+                        Err(_) => break,
+                    }
+                }
+            }
         }
 
+        // Reaching here means the shutdown flow or every sender was dropped
+        // unexpectedly. Broadcast directly since nothing can be queued anymore.
+        self.drain().await;
+        error!("Alert pipeline stopped unexpectedly, sending last-gasp notification!");
+        self.broadcast_shutdown(AlertInfo {
+            source: "alert-manager".to_string(),
+            message: "Alert pipeline died, no more alerts can be sent!".to_string(),
+            level: AlertLevel::Critical,
+            timestamp: None,
+            metadata: AlertMetadata::default(),
+        })
+        .await;
+
         Err(anyhow::anyhow!(
-            "Alert channel closed, no more alerts can be processed!"
+            "Alert pipeline stopped, no more alerts can be processed!"
         ))
     }
 
-    async fn execute(&self, alert: AlertInfo) {
+    /// Stop accepting alerts, execute those still queued and wait for every broadcast
+    /// to finish, giving up after the shutdown timeout.
+    async fn drain(&mut self) {
+        let drained = async {
+            self.receiver.close();
+            while let Some(alert) = self.receiver.recv().await {
+                self.execute(alert).await;
+            }
+
+            let mut broadcasts =
+                std::mem::take(&mut *self.broadcasts.lock().unwrap_or_else(|e| e.into_inner()));
+            debug!("Waiting for {} broadcast(s) to finish...", broadcasts.len());
+            while broadcasts.join_next().await.is_some() {}
+        };
+        if tokio::time::timeout(SHUTDOWN_ALERT_TIMEOUT, drained)
+            .await
+            .is_err()
+        {
+            warn!("Timed out delivering queued alerts before shutdown!");
+        }
+    }
+
+    /// Broadcast directly rather than through execute, since spawned sends
+    /// wouldn't outlive the runtime shutting down.
+    async fn broadcast_shutdown(&self, mut alert: AlertInfo) {
+        alert.backfill_timestamp();
+
+        if tokio::time::timeout(
+            SHUTDOWN_ALERT_TIMEOUT,
+            self.communications.broadcast(&alert),
+        )
+        .await
+        .is_err()
+        {
+            warn!("Timed out sending shutdown alert!");
+        }
+    }
+
+    /// Check if an alarm is confirmed by a previous alarm within the confirmation window.
+    /// The first alarm arms a pending state and is downgraded to Critical.
+    async fn confirm_alarm(&self, alert: &mut AlertInfo) {
+        let Some(window) = self.alarm_confirmation_window else {
+            return;
+        };
+        if let Some(sources) = &self.alarm_confirmation_sources {
+            if !sources.contains(&alert.source) {
+                return;
+            }
+        }
+
+        let mut pending_guard = self.alarm_pending.lock().await;
+        let now = tokio::time::Instant::now();
+        match *pending_guard {
+            Some(pending) if now.duration_since(pending) <= window => {
+                debug!("Alarm confirmed by a second trigger: {alert}");
+                *pending_guard = None;
+            }
+            _ => {
+                warn!("Alarm pending confirmation, sending as Critical: {alert}");
+                *pending_guard = Some(now);
+                alert.level = AlertLevel::Critical;
+            }
+        }
+    }
+
+    /// Hold recoverable problem alerts while settling, returning the alert if it should be
+    /// executed now. Anything that won't recover by itself, such as webhook events, isn't held.
+    /// A recovery discards the held alerts from its source and subject, along with itself.
+    fn hold_while_settling(&mut self, alert: AlertInfo) -> Option<AlertInfo> {
+        let Some(settling) = &mut self.settling else {
+            return Some(alert);
+        };
+
+        if alert.metadata.recovery {
+            let held = settling.held.len();
+            settling.held.retain(|held| {
+                held.source != alert.source || held.metadata.subject != alert.metadata.subject
+            });
+            if settling.held.len() == held {
+                return Some(alert);
+            }
+
+            debug!("Discarding held alerts that recovered while settling: {alert}");
+            return None;
+        }
+        if !alert.metadata.recoverable || alert.is_alarm() || alert.level == AlertLevel::Info {
+            return Some(alert);
+        }
+
+        debug!("Holding alert while settling after boot: {alert}");
+        settling.held.push(alert);
+        None
+    }
+
+    /// End the settling window, executing every alert that's still held.
+    async fn finish_settling(&mut self) {
+        let Some(settling) = self.settling.take() else {
+            return;
+        };
+        if settling.held.is_empty() {
+            debug!("Finished settling after boot.");
+            return;
+        }
+
+        info!(
+            "Finished settling after boot, sending {} held alert(s).",
+            settling.held.len()
+        );
+        for alert in settling.held {
+            self.execute(alert).await;
+        }
+    }
+
+    /// Summarize the alerts dropped in expired rate windows, for sources that went quiet.
+    async fn flush_rate_limited(&mut self) {
+        let Some(rate_limit) = self.rate_limit.clone() else {
+            return;
+        };
+        for (source, dropped) in rate_limit.sweep() {
+            match dropped_summary(source, dropped) {
+                Ok(summary) => {
+                    if let Some(summary) = self.hold_while_settling(summary) {
+                        self.execute(summary).await;
+                    }
+                }
+                Err(e) => error!("Failed to create rate limit summary alert: {e:#?}"),
+            }
+        }
+    }
+
+    /// Alert if a test alarm wasn't fully delivered, since finding that is the point of the drill.
+    /// The source must be from before transforms, as the failure alert is executed again.
+    async fn report_test_outcome(source: String, outcomes: &[(&'static str, SendOutcome)]) {
+        let failed: Vec<_> = outcomes
+            .iter()
+            .filter(|(_, outcome)| !outcome.is_delivered())
+            .map(|(name, _)| *name)
+            .collect();
+
+        let message = if outcomes.is_empty() {
+            "Test alarm wasn't sent by any provider!".to_string()
+        } else if failed.is_empty() {
+            info!("Test alarm delivered by {} provider(s).", outcomes.len());
+            return;
+        } else {
+            format!("Test alarm failed to deliver via {}!", failed.join(", "))
+        };
+
+        warn!("{message}");
+        let result = match AlertInfo::new(source, message, AlertLevel::Critical) {
+            Ok(failure) => send_alert(failure).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            error!("Failed to send test alarm failure alert: {e:#?}");
+        }
+    }
+
+    async fn execute(&self, mut alert: AlertInfo) {
+        alert.backfill_timestamp();
+        if alert.is_alarm() && !alert.metadata.unsuppressible {
+            self.confirm_alarm(&mut alert).await;
+        }
+
+        let count_index = match alert.level {
+            AlertLevel::Info => None,
+            AlertLevel::Warning => Some(0),
+            AlertLevel::Critical => Some(1),
+            AlertLevel::Alarm => Some(2),
+        };
+        if let Some(index) = count_index.filter(|_| !alert.metadata.test) {
+            ALERT_COUNTS[index].fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        }
+
+        let test_source = alert.metadata.test.then(|| alert.source.clone());
+        self.transforms.apply(&mut alert);
+
         // Enforce a cooldown on alarms, since the CCTV system could report multiple
         // alarms within rapid succession if motion is detected on multiple cameras.
         let is_alarm = alert.is_alarm();
-        if is_alarm {
+        if is_alarm && !alert.metadata.unsuppressible {
             let mut alarm_last_guard = self.alarm_last.write().await;
             let now = tokio::time::Instant::now();
 
@@ -150,19 +669,56 @@ impl AlertManager {
 
         // Hold semaphore permit in the communication task.
         let communications = self.communications.clone();
-        tokio::spawn(async move {
+        let audit = self.audit.clone();
+        let mut broadcasts = self.broadcasts.lock().unwrap_or_else(|e| e.into_inner());
+        while broadcasts.try_join_next().is_some() {}
+        let task = async move {
             let _permit = permit;
 
             debug!("Executing alert: {alert:?}");
-            communications.broadcast(&alert).await;
-        });
+            let outcomes = communications.broadcast(&alert).await;
+            if let Some(source) = test_source {
+                Self::report_test_outcome(source, &outcomes).await;
+            }
+            if let Some(audit) = audit {
+                if let Err(e) = audit.append(&alert, &outcomes).await {
+                    error!("Failed to write alert to audit log: {e:#?}");
+                }
+            }
+        };
+
+        #[cfg(test)]
+        let task = tests::propagate_sender(task);
+        broadcasts.spawn(task);
     }
 }
 
+// Warning, Critical and Alarm alerts processed since the counts were last taken.
+static ALERT_COUNTS: [std::sync::atomic::AtomicU64; 3] = [
+    std::sync::atomic::AtomicU64::new(0),
+    std::sync::atomic::AtomicU64::new(0),
+    std::sync::atomic::AtomicU64::new(0),
+];
+
+/// Get the Warning, Critical and Alarm alert counts, resetting them to zero.
+pub fn take_alert_counts() -> [u64; 3] {
+    ALERT_COUNTS
+        .each_ref()
+        .map(|count| count.swap(0, std::sync::atomic::Ordering::Relaxed))
+}
+
 static ALERT_SENDER: tokio::sync::OnceCell<AlertSender> = tokio::sync::OnceCell::const_new();
+static DISPLAY_NAMES: std::sync::OnceLock<std::collections::BTreeMap<String, String>> =
+    std::sync::OnceLock::new();
+
+/// Get the configured display name for an internal monitor or provider name.
+pub fn display_name(name: &str) -> Option<&'static str> {
+    DISPLAY_NAMES.get()?.get(name).map(String::as_str)
+}
 
 pub async fn initialize_alert_manager(config: &AppConfig) -> anyhow::Result<AlertManager> {
-    let (manager, sender) = AlertManager::new(config)?;
+    let _ = DISPLAY_NAMES.set(config.alerts.display_names.clone());
+    let (manager, sender) = AlertManager::new(config).await?;
     ALERT_SENDER
         .set(sender)
         .map_err(|_| anyhow::anyhow!("AlertSender already initialized!"))?;
@@ -170,10 +726,834 @@ pub async fn initialize_alert_manager(config: &AppConfig) -> anyhow::Result<Aler
     Ok(manager)
 }
 
+/// Get the AlertSender, which tests can replace for the current task to capture alerts.
+fn alert_sender() -> Option<AlertSender> {
+    #[cfg(test)]
+    if let Ok(sender) = tests::SENDER.try_with(AlertSender::clone) {
+        return Some(sender);
+    }
+    ALERT_SENDER.get().cloned()
+}
+
+/// Get the number of queued alerts that are waiting to be executed.
+pub fn alert_channel_len() -> Option<usize> {
+    alert_sender().as_ref().map(AlertSender::len)
+}
+
+/// Get the alert channel fill level, or None if the AlertSender is not initialized.
+pub fn alert_channel_fill_level() -> Option<f32> {
+    alert_sender().as_ref().map(AlertSender::fill_level)
+}
+
 pub async fn send_alert(alert: AlertInfo) -> anyhow::Result<()> {
-    ALERT_SENDER
-        .get()
+    alert_sender()
         .ok_or_else(|| anyhow::anyhow!("AlertSender is not initialized!"))?
         .send(alert)
         .await
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::communications::tests::{registry, StubProvider};
+
+    tokio::task_local! {
+        pub(crate) static SENDER: AlertSender;
+    }
+
+    /// Alerts sent by the task, queued in a channel instead of reaching an AlertManager.
+    pub(crate) struct CapturedAlerts {
+        sender: AlertSender,
+        receiver: std::sync::Mutex<tokio::sync::mpsc::Receiver<AlertInfo>>,
+    }
+    impl CapturedAlerts {
+        pub fn new(capacity: usize) -> Self {
+            let (sender, receiver) = tokio::sync::mpsc::channel(capacity);
+            Self {
+                sender: AlertSender {
+                    sender,
+                    rate_limit: None,
+                },
+                receiver: std::sync::Mutex::new(receiver),
+            }
+        }
+
+        /// Run the future with its alerts captured here.
+        pub async fn scope<F: std::future::Future>(&self, future: F) -> F::Output {
+            SENDER.scope(self.sender.clone(), future).await
+        }
+
+        /// Take every alert queued so far.
+        pub fn take(&self) -> Vec<AlertInfo> {
+            let mut receiver = self.receiver.lock().unwrap();
+            let mut alerts = Vec::new();
+            while let Ok(alert) = receiver.try_recv() {
+                alerts.push(alert);
+            }
+            alerts
+        }
+    }
+
+    /// Keep the task's captured alerts for a spawned future, since task locals aren't inherited.
+    pub(crate) fn propagate_sender<F>(future: F) -> futures::future::BoxFuture<'static, ()>
+    where
+        F: std::future::Future<Output = ()> + Send + 'static,
+    {
+        match SENDER.try_with(AlertSender::clone) {
+            Ok(sender) => Box::pin(SENDER.scope(sender, future)),
+            Err(_) => Box::pin(future),
+        }
+    }
+
+    /// Alias the names used by the display name tests. Display names can only be set
+    /// once per process, so every other name keeps its internal name.
+    pub(crate) fn set_display_names() {
+        DISPLAY_NAMES.get_or_init(|| {
+            [("aliased", "Friendly Monitor"), ("aliased_sms", "SMS")]
+                .into_iter()
+                .map(|(name, display)| (name.to_string(), display.to_string()))
+                .collect()
+        });
+    }
+
+    fn manager(
+        config: &str,
+        providers: Vec<(
+            &'static str,
+            Box<dyn crate::communications::CommunicationProvider>,
+        )>,
+    ) -> (AlertManager, AlertSender) {
+        let config: AppConfig = toml::from_str(config).unwrap();
+        AlertManager::with_registry(&config, registry(providers))
+    }
+
+    fn alert(source: &str, level: AlertLevel) -> AlertInfo {
+        AlertInfo::new(source.to_string(), "Test alert".to_string(), level).unwrap()
+    }
+
+    fn alert_at(timestamp: Option<u64>) -> AlertInfo {
+        AlertInfo {
+            timestamp,
+            ..alert("clock", AlertLevel::Info)
+        }
+    }
+
+    #[test]
+    fn formats_absolute_timestamps() {
+        let alert = alert_at(Some(1_700_000_000));
+        assert_eq!(
+            alert
+                .format_timestamp(&TimestampFormat::Absolute)
+                .as_deref(),
+            Some("2023-11-14 22:13 UTC")
+        );
+    }
+
+    #[test]
+    fn formats_relative_timestamps() {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        for (age, expected) in [
+            (5, "5s ago"),
+            (150, "2m ago"),
+            (7300, "2h ago"),
+            (3 * 86400, "3d ago"),
+        ] {
+            let alert = alert_at(Some(now - age));
+            assert_eq!(
+                alert
+                    .format_timestamp(&TimestampFormat::Relative)
+                    .as_deref(),
+                Some(expected)
+            );
+        }
+    }
+
+    #[test]
+    fn formats_no_timestamp() {
+        let alert = alert_at(Some(1_700_000_000));
+        assert_eq!(alert.format_timestamp(&TimestampFormat::None), None);
+        assert_eq!(
+            alert_at(None).format_timestamp(&TimestampFormat::Absolute),
+            None
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn lost_shutdown_flow_sends_last_gasp() {
+        let provider = StubProvider::new(&[AlertLevel::Info]);
+        let state = provider.state();
+        let (manager, _sender) = manager("", vec![provider.boxed("stub")]);
+
+        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel();
+        let handle = tokio::spawn(manager.run(shutdown_rx));
+        drop(shutdown_tx);
+
+        assert!(handle.await.unwrap().is_err());
+        assert!(state.messages().contains(&(
+            "Alert pipeline died, no more alerts can be sent!".to_string(),
+            AlertLevel::Critical
+        )));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn closed_alert_channel_sends_last_gasp() {
+        let provider = StubProvider::new(&[AlertLevel::Info]);
+        let state = provider.state();
+        let (manager, sender) = manager("", vec![provider.boxed("stub")]);
+
+        let (_shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel();
+        let handle = tokio::spawn(manager.run(shutdown_rx));
+        drop(sender);
+
+        assert!(handle.await.unwrap().is_err());
+        assert!(state.messages().contains(&(
+            "Alert pipeline died, no more alerts can be sent!".to_string(),
+            AlertLevel::Critical
+        )));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn shutdown_reason_is_broadcast_instead_of_last_gasp() {
+        let provider = StubProvider::new(&[AlertLevel::Info]);
+        let state = provider.state();
+        let (manager, _sender) = manager("", vec![provider.boxed("stub")]);
+
+        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel();
+        let handle = tokio::spawn(manager.run(shutdown_rx));
+        shutdown_tx
+            .send(("received shutdown signal".to_string(), AlertLevel::Warning))
+            .unwrap();
+
+        assert!(handle.await.unwrap().is_ok());
+        assert!(state.messages().contains(&(
+            "Shutting down: received shutdown signal".to_string(),
+            AlertLevel::Warning
+        )));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn burst_never_exceeds_send_concurrency_limit() {
+        let provider =
+            StubProvider::new(&[AlertLevel::Warning]).delayed(std::time::Duration::from_secs(1));
+        let state = provider.state();
+        let (manager, _sender) = manager(
+            "[alerts]\nsend_concurrency_limit = 2",
+            vec![provider.boxed("stub")],
+        );
+
+        for _ in 0..6 {
+            manager.execute(alert("burst", AlertLevel::Warning)).await;
+        }
+        tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+
+        assert_eq!(state.sends(), 6);
+        assert_eq!(state.peak(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn single_trigger_is_downgraded_until_confirmed() {
+        let (manager, _sender) = manager("[alerts]\nalarm_confirmation_window = 60", vec![]);
+
+        let mut first = alert("cctv", AlertLevel::Alarm);
+        manager.confirm_alarm(&mut first).await;
+        assert_eq!(first.level, AlertLevel::Critical);
+
+        tokio::time::sleep(std::time::Duration::from_secs(30)).await;
+        let mut second = alert("cctv", AlertLevel::Alarm);
+        manager.confirm_alarm(&mut second).await;
+        assert_eq!(second.level, AlertLevel::Alarm);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn trigger_after_the_window_starts_over() {
+        let (manager, _sender) = manager("[alerts]\nalarm_confirmation_window = 60", vec![]);
+
+        let mut first = alert("cctv", AlertLevel::Alarm);
+        manager.confirm_alarm(&mut first).await;
+        tokio::time::sleep(std::time::Duration::from_secs(61)).await;
+
+        let mut second = alert("cctv", AlertLevel::Alarm);
+        manager.confirm_alarm(&mut second).await;
+        assert_eq!(second.level, AlertLevel::Critical);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn only_listed_sources_need_confirmation() {
+        let (manager, _sender) = manager(
+            "[alerts]\nalarm_confirmation_window = 60\nalarm_confirmation_sources = [\"cctv\"]",
+            vec![],
+        );
+
+        let mut other = alert("door", AlertLevel::Alarm);
+        manager.confirm_alarm(&mut other).await;
+        assert_eq!(other.level, AlertLevel::Alarm);
+
+        let mut listed = alert("cctv", AlertLevel::Alarm);
+        manager.confirm_alarm(&mut listed).await;
+        assert_eq!(listed.level, AlertLevel::Critical);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn confirmed_alarm_is_broadcast_as_alarm() {
+        let provider = StubProvider::new(&[AlertLevel::Warning]);
+        let state = provider.state();
+        let (manager, _sender) = manager(
+            "[alerts]\nalarm_confirmation_window = 60",
+            vec![provider.boxed("stub")],
+        );
+
+        manager.execute(alert("cctv", AlertLevel::Alarm)).await;
+        manager.execute(alert("cctv", AlertLevel::Alarm)).await;
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+        let mut levels: Vec<_> = state
+            .messages()
+            .into_iter()
+            .map(|(_, level)| level)
+            .collect();
+        levels.sort();
+        assert_eq!(levels, [AlertLevel::Critical, AlertLevel::Alarm]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn alarm_cooldown_runs_a_full_cycle() {
+        let provider = StubProvider::new(&[AlertLevel::Warning]);
+        let state = provider.state();
+        let (manager, _sender) = manager(
+            "[alerts]\nalarm_cooldown = 300",
+            vec![provider.boxed("stub")],
+        );
+        let wall_clock = std::time::Instant::now();
+
+        let mut sends = Vec::new();
+        for wait in [0, 299, 2, 150, 151] {
+            tokio::time::sleep(std::time::Duration::from_secs(wait)).await;
+            manager.execute(alert("cctv", AlertLevel::Alarm)).await;
+            tokio::task::yield_now().await;
+            sends.push(state.sends());
+        }
+
+        // Suppressed until 300s after the last sent alarm, which restarts the cooldown.
+        assert_eq!(sends, [1, 1, 2, 2, 3]);
+        assert!(wall_clock.elapsed() < std::time::Duration::from_secs(1));
+    }
+
+    fn now() -> u64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    #[tokio::test]
+    async fn enqueued_alerts_without_a_timestamp_get_one() {
+        let alerts = CapturedAlerts::new(2);
+        let stamped = alert_at(Some(1_700_000_000));
+        alerts
+            .scope(async {
+                send_alert(alert_at(None)).await.unwrap();
+                send_alert(stamped).await.unwrap();
+            })
+            .await;
+
+        let alerts = alerts.take();
+        assert!(alerts[0].timestamp.unwrap().abs_diff(now()) <= 1);
+        assert_eq!(alerts[1].timestamp, Some(1_700_000_000));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn executed_alerts_are_broadcast_with_a_timestamp() {
+        let provider = StubProvider::new(&[AlertLevel::Info]);
+        let state = provider.state();
+        let (manager, _sender) = manager("", vec![provider.boxed("stub")]);
+
+        manager.execute(crate::webhooks::panic_alert()).await;
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+        let timestamps = state.timestamps();
+        assert_eq!(timestamps.len(), 1);
+        assert!(timestamps[0].unwrap().abs_diff(now()) <= 1);
+    }
+
+    #[tokio::test]
+    async fn executed_alerts_are_audited() {
+        let path = crate::audit::tests::audit_path("executed");
+        let provider = StubProvider::new(&[AlertLevel::Warning]);
+        let (manager, _sender) = manager(
+            &format!("[alerts]\naudit_log_path = {:?}", path),
+            vec![provider.boxed("stub")],
+        );
+
+        manager.execute(alert("cctv", AlertLevel::Alarm)).await;
+        let mut contents = String::new();
+        for _ in 0..100 {
+            contents = std::fs::read_to_string(&path).unwrap_or_default();
+            if !contents.is_empty() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+
+        let line: serde_json::Value = serde_json::from_str(contents.trim_end()).unwrap();
+        assert_eq!(line["alert"]["source"], "cctv");
+        assert_eq!(line["deliveries"]["stub"]["delivered"], true);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn panic_fires_during_cooldown() {
+        let provider = StubProvider::new(&[AlertLevel::Warning]);
+        let state = provider.state();
+        let (manager, _sender) = manager(
+            "[alerts]\nalarm_cooldown = 300",
+            vec![provider.boxed("stub")],
+        );
+
+        manager.execute(alert("cctv", AlertLevel::Alarm)).await;
+        manager.execute(alert("cctv", AlertLevel::Alarm)).await;
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        assert_eq!(state.sends(), 1, "Second alarm should be in cooldown");
+
+        manager.execute(crate::webhooks::panic_alert()).await;
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        assert_eq!(state.sends(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn panic_skips_alarm_confirmation() {
+        let provider = StubProvider::new(&[AlertLevel::Warning]);
+        let state = provider.state();
+        let (manager, _sender) = manager(
+            "[alerts]\nalarm_confirmation_window = 60",
+            vec![provider.boxed("stub")],
+        );
+
+        manager.execute(crate::webhooks::panic_alert()).await;
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+        assert_eq!(
+            state.messages(),
+            [("Panic button pressed!".to_string(), AlertLevel::Alarm)]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn panic_does_not_start_a_cooldown() {
+        let provider = StubProvider::new(&[AlertLevel::Warning]);
+        let state = provider.state();
+        let (manager, _sender) = manager(
+            "[alerts]\nalarm_cooldown = 300",
+            vec![provider.boxed("stub")],
+        );
+
+        manager.execute(crate::webhooks::panic_alert()).await;
+        manager.execute(alert("cctv", AlertLevel::Alarm)).await;
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        assert_eq!(state.sends(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rate_limit_throttles_one_source_while_others_flow() {
+        let rate_limit = SourceRateLimit::new(2, std::time::Duration::from_secs(60));
+        assert_eq!(
+            rate_limit.check("noisy"),
+            RateDecision::Allowed { dropped: 0 }
+        );
+        assert_eq!(
+            rate_limit.check("noisy"),
+            RateDecision::Allowed { dropped: 0 }
+        );
+        assert_eq!(
+            rate_limit.check("noisy"),
+            RateDecision::Dropped { first: true }
+        );
+        assert_eq!(
+            rate_limit.check("noisy"),
+            RateDecision::Dropped { first: false }
+        );
+
+        assert_eq!(
+            rate_limit.check("quiet"),
+            RateDecision::Allowed { dropped: 0 }
+        );
+        assert_eq!(
+            rate_limit.check("quiet"),
+            RateDecision::Allowed { dropped: 0 }
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rate_limit_summarizes_drops_in_the_next_window() {
+        let rate_limit = SourceRateLimit::new(1, std::time::Duration::from_secs(60));
+        rate_limit.check("noisy");
+        rate_limit.check("noisy");
+        rate_limit.check("noisy");
+
+        tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+        assert_eq!(
+            rate_limit.check("noisy"),
+            RateDecision::Allowed { dropped: 2 }
+        );
+        assert_eq!(
+            rate_limit.check("noisy"),
+            RateDecision::Dropped { first: true }
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rate_limit_sweep_evicts_expired_windows() {
+        let rate_limit = SourceRateLimit::new(1, std::time::Duration::from_secs(60));
+        rate_limit.check("noisy");
+        rate_limit.check("noisy");
+        rate_limit.check("quiet");
+        assert!(rate_limit.sweep().is_empty(), "Windows haven't expired yet");
+
+        tokio::time::sleep(std::time::Duration::from_secs(30)).await;
+        rate_limit.check("late");
+        tokio::time::sleep(std::time::Duration::from_secs(30)).await;
+
+        assert_eq!(rate_limit.sweep(), [("noisy".to_string(), 1)]);
+        let sources = rate_limit.sources.lock().unwrap();
+        assert_eq!(sources.keys().collect::<Vec<_>>(), ["late"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn sender_exempts_alarms_from_the_rate_limit() {
+        let (mut manager, sender) = manager("[alerts]\nsource_rate_limit = 1", vec![]);
+
+        sender
+            .send(alert("noisy", AlertLevel::Warning))
+            .await
+            .unwrap();
+        sender
+            .send(alert("noisy", AlertLevel::Warning))
+            .await
+            .unwrap();
+        sender
+            .send(alert("noisy", AlertLevel::Alarm))
+            .await
+            .unwrap();
+        sender
+            .send(alert("quiet", AlertLevel::Warning))
+            .await
+            .unwrap();
+
+        let mut received = Vec::new();
+        while let Ok(alert) = manager.receiver.try_recv() {
+            received.push((alert.source, alert.level));
+        }
+        assert_eq!(
+            received,
+            [
+                ("noisy".to_string(), AlertLevel::Warning),
+                ("noisy".to_string(), AlertLevel::Alarm),
+                ("quiet".to_string(), AlertLevel::Warning),
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn drops_are_summarized_when_the_source_goes_quiet() {
+        let provider = StubProvider::new(&[AlertLevel::Info]);
+        let state = provider.state();
+        let (manager, sender) = manager(
+            "[alerts]\nsource_rate_limit = 1\nsource_rate_window = 60",
+            vec![provider.boxed("stub")],
+        );
+        let (shutdown, receiver) = tokio::sync::oneshot::channel();
+        let handle = tokio::spawn(manager.run(receiver));
+
+        for _ in 0..3 {
+            sender
+                .send(alert("noisy", AlertLevel::Warning))
+                .await
+                .unwrap();
+        }
+        tokio::time::sleep(std::time::Duration::from_secs(150)).await;
+
+        let messages: Vec<_> = state
+            .messages()
+            .into_iter()
+            .map(|(message, _)| message)
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "Sentinel is online",
+                "Test alert",
+                "Dropped 2 alert(s) over the rate limit!"
+            ]
+        );
+
+        let _ = shutdown.send(("test finished".to_string(), AlertLevel::Info));
+        handle.await.unwrap().unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn zero_rate_windows_are_clamped_rather_than_panicking() {
+        let provider = StubProvider::new(&[AlertLevel::Info]);
+        let state = provider.state();
+        let mut config: AppConfig =
+            toml::from_str("[alerts]\nsource_rate_limit = 1\nsource_rate_window = 0").unwrap();
+        config.clamp_limits();
+        let (manager, sender) =
+            AlertManager::with_registry(&config, registry(vec![provider.boxed("stub")]));
+        let (shutdown, receiver) = tokio::sync::oneshot::channel();
+        let handle = tokio::spawn(manager.run(receiver));
+
+        for _ in 0..2 {
+            sender
+                .send(alert("noisy", AlertLevel::Warning))
+                .await
+                .unwrap();
+        }
+        tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+
+        // Still rate limited, in one second windows.
+        assert_eq!(state.sends(), 3);
+        assert_eq!(
+            state.messages()[2].0,
+            "Dropped 1 alert(s) over the rate limit!"
+        );
+        let _ = shutdown.send(("test finished".to_string(), AlertLevel::Info));
+        handle.await.unwrap().unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn shutdown_delivers_queued_and_sending_alerts_first() {
+        let provider =
+            StubProvider::new(&[AlertLevel::Info]).delayed(std::time::Duration::from_secs(2));
+        let state = provider.state();
+        let (manager, sender) = manager("", vec![provider.boxed("stub")]);
+        let (shutdown, receiver) = tokio::sync::oneshot::channel();
+        for source in ["first", "second"] {
+            sender
+                .send(alert(source, AlertLevel::Warning))
+                .await
+                .unwrap();
+        }
+        let _ = shutdown.send(("test finished".to_string(), AlertLevel::Info));
+
+        let started = tokio::time::Instant::now();
+        manager.run(receiver).await.unwrap();
+
+        let messages: Vec<_> = state
+            .messages()
+            .into_iter()
+            .map(|(message, _)| message)
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "Sentinel is online",
+                "Test alert",
+                "Test alert",
+                "Shutting down: test finished"
+            ]
+        );
+        // The queued alerts finished sending before the shutdown alert was sent and finished.
+        assert_eq!(started.elapsed(), std::time::Duration::from_secs(4));
+    }
+
+    fn test_alarm() -> AlertInfo {
+        let mut alert = alert("self_test monitor", AlertLevel::Alarm);
+        alert.metadata.test = true;
+        alert
+    }
+
+    #[tokio::test]
+    async fn undelivered_test_alarms_are_reported() {
+        let alerts = CapturedAlerts::new(10);
+        let outcomes = [
+            (
+                "pushover",
+                SendOutcome {
+                    unsent: vec![],
+                    rejected: vec![],
+                },
+            ),
+            (
+                "sms",
+                SendOutcome {
+                    unsent: vec![0],
+                    rejected: vec![],
+                },
+            ),
+        ];
+        alerts
+            .scope(AlertManager::report_test_outcome(
+                test_alarm().source,
+                &outcomes,
+            ))
+            .await;
+
+        let reported = alerts.take();
+        assert_eq!(reported.len(), 1);
+        assert_eq!(reported[0].message, "Test alarm failed to deliver via sms!");
+        assert_eq!(reported[0].level, AlertLevel::Critical);
+        assert_eq!(reported[0].source, "self_test monitor");
+    }
+
+    #[tokio::test]
+    async fn test_alarms_sent_by_no_provider_are_reported() {
+        let alerts = CapturedAlerts::new(10);
+        alerts
+            .scope(AlertManager::report_test_outcome(test_alarm().source, &[]))
+            .await;
+        assert_eq!(
+            alerts.take()[0].message,
+            "Test alarm wasn't sent by any provider!"
+        );
+    }
+
+    #[tokio::test]
+    async fn delivered_test_alarms_are_not_reported() {
+        let alerts = CapturedAlerts::new(10);
+        let outcomes = [(
+            "sms",
+            SendOutcome {
+                unsent: vec![],
+                rejected: vec![],
+            },
+        )];
+        alerts
+            .scope(AlertManager::report_test_outcome(
+                test_alarm().source,
+                &outcomes,
+            ))
+            .await;
+        assert!(alerts.take().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_alarm_failures_are_only_transformed_once() {
+        let provider = StubProvider::new(&[AlertLevel::Info]).unsupported();
+        let (manager, _sender) = manager(
+            "[alerts]\nsite = \"Warehouse\"\nmessage_template = \"{message} ({source})\"",
+            vec![provider.boxed("stub")],
+        );
+
+        let alerts = CapturedAlerts::new(10);
+        alerts
+            .scope(async {
+                manager.execute(test_alarm()).await;
+                tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+            })
+            .await;
+
+        // Reported from before transforms, which apply again once it's executed.
+        let reported = alerts.take();
+        assert_eq!(reported.len(), 1);
+        assert_eq!(reported[0].source, "self_test monitor");
+        assert_eq!(
+            reported[0].message,
+            "Test alarm wasn't sent by any provider!"
+        );
+    }
+
+    fn subject_alert(subject: &str, level: AlertLevel, recovery: bool) -> AlertInfo {
+        let mut alert = alert("ping monitor", level);
+        alert.metadata.subject = Some(subject.to_string());
+        alert.metadata.recovery = recovery;
+        alert.metadata.recoverable = !recovery;
+        alert
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn settling_holds_problems_then_releases_them() {
+        let provider = StubProvider::new(&[AlertLevel::Info]);
+        let state = provider.state();
+        let (mut manager, _sender) =
+            manager("[alerts]\nboot_settle = 60", vec![provider.boxed("stub")]);
+
+        let held = subject_alert("router", AlertLevel::Warning, false);
+        assert!(manager.hold_while_settling(held).is_none());
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        assert_eq!(state.sends(), 0);
+
+        manager.finish_settling().await;
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        assert_eq!(state.sends(), 1);
+        assert!(manager.settling.is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn settling_discards_problems_that_recover() {
+        let provider = StubProvider::new(&[AlertLevel::Info]);
+        let state = provider.state();
+        let (mut manager, _sender) =
+            manager("[alerts]\nboot_settle = 60", vec![provider.boxed("stub")]);
+
+        let problem = subject_alert("router", AlertLevel::Critical, false);
+        let recovery = subject_alert("router", AlertLevel::Critical, true);
+        assert!(manager.hold_while_settling(problem).is_none());
+        assert!(manager.hold_while_settling(recovery).is_none());
+
+        manager.finish_settling().await;
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        assert_eq!(state.sends(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn settling_only_matches_recoveries_by_subject() {
+        let (mut manager, _sender) = manager("[alerts]\nboot_settle = 60", vec![]);
+
+        let problem = subject_alert("router", AlertLevel::Warning, false);
+        let recovery = subject_alert("nas", AlertLevel::Warning, true);
+        assert!(manager.hold_while_settling(problem).is_none());
+        assert!(manager.hold_while_settling(recovery).is_some());
+        assert_eq!(manager.settling.as_ref().unwrap().held.len(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn settling_passes_alarms_and_info_through() {
+        let (mut manager, _sender) = manager("[alerts]\nboot_settle = 60", vec![]);
+        assert!(manager
+            .hold_while_settling(alert("cctv", AlertLevel::Alarm))
+            .is_some());
+        assert!(manager
+            .hold_while_settling(alert("digest", AlertLevel::Info))
+            .is_some());
+        assert!(manager.settling.as_ref().unwrap().held.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn settling_passes_webhook_events_through() {
+        let provider = StubProvider::new(&[AlertLevel::Info]);
+        let state = provider.state();
+        let (mut manager, _sender) =
+            manager("[alerts]\nboot_settle = 60", vec![provider.boxed("stub")]);
+
+        let motion = alert("cctv-webhook", AlertLevel::Warning);
+        let motion = manager.hold_while_settling(motion).unwrap();
+        manager.execute(motion).await;
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+        assert_eq!(state.sends(), 1);
+        assert!(manager.settling.as_ref().unwrap().held.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn held_alerts_are_sent_once_the_window_ends() {
+        let provider = StubProvider::new(&[AlertLevel::Warning]);
+        let state = provider.state();
+        let (manager, sender) = manager("[alerts]\nboot_settle = 60", vec![provider.boxed("stub")]);
+        let (shutdown, receiver) = tokio::sync::oneshot::channel();
+        let handle = tokio::spawn(manager.run(receiver));
+
+        sender
+            .send(subject_alert("router", AlertLevel::Warning, false))
+            .await
+            .unwrap();
+        tokio::time::sleep(std::time::Duration::from_secs(30)).await;
+        assert_eq!(state.sends(), 0);
+
+        tokio::time::sleep(std::time::Duration::from_secs(31)).await;
+        assert_eq!(state.sends(), 1);
+
+        let _ = shutdown.send(("test finished".to_string(), AlertLevel::Warning));
+        handle.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,202 @@
+use crate::alerts::AlertInfo;
+use crate::communications::SendOutcome;
+use crate::config::AlertsConfig;
+use tokio::io::AsyncWriteExt;
+
+/*
+   Append-only JSONL record of every alert broadcast and how each provider
+   delivered it, independent of any provider logs. Once the file would grow
+   past the size limit, it's rotated to a single `.1` backup.
+*/
+
+#[derive(serde::Serialize)]
+struct AuditDelivery {
+    delivered: bool,
+    unsent: usize,
+    rejected: usize,
+}
+
+#[derive(serde::Serialize)]
+struct AuditRecord<'a> {
+    timestamp: u64,
+    alert: &'a AlertInfo,
+    deliveries: std::collections::BTreeMap<&'static str, AuditDelivery>,
+}
+
+pub(crate) struct AuditLog {
+    path: std::path::PathBuf,
+    max_size: u64,
+
+    // Serializes writes and rotation across concurrent alert tasks.
+    lock: tokio::sync::Mutex<()>,
+}
+impl AuditLog {
+    pub fn from_config(config: &AlertsConfig) -> Option<Self> {
+        config.audit_log_path.as_ref().map(|path| Self {
+            path: path.clone(),
+            max_size: config.audit_log_max_size,
+            lock: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    pub async fn append(
+        &self,
+        alert: &AlertInfo,
+        outcomes: &[(&'static str, SendOutcome)],
+    ) -> anyhow::Result<()> {
+        let record = AuditRecord {
+            timestamp: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)?
+                .as_secs(),
+            alert,
+            deliveries: outcomes
+                .iter()
+                .map(|(name, outcome)| {
+                    (
+                        *name,
+                        AuditDelivery {
+                            delivered: outcome.is_delivered(),
+                            unsent: outcome.unsent.len(),
+                            rejected: outcome.rejected.len(),
+                        },
+                    )
+                })
+                .collect(),
+        };
+        let mut line = serde_json::to_vec(&record)?;
+        line.push(b'\n');
+
+        let _guard = self.lock.lock().await;
+        append_rotating(&self.path, self.max_size, &line).await
+    }
+}
+
+/// Append to a file, first rotating it to a single `.1` backup if it would grow past
+/// the max size. Callers must serialize their own writes to the same path.
+pub(crate) async fn append_rotating(
+    path: &std::path::Path,
+    max_size: u64,
+    line: &[u8],
+) -> anyhow::Result<()> {
+    if let Ok(metadata) = tokio::fs::metadata(path).await {
+        if metadata.len() > 0 && metadata.len() + line.len() as u64 > max_size {
+            let mut rotated = path.to_path_buf().into_os_string();
+            rotated.push(".1");
+            tokio::fs::rename(path, rotated).await?;
+        }
+    }
+
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await?;
+    file.write_all(line).await?;
+    file.flush().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::alerts::AlertLevel;
+
+    /// A fresh audit log path in its own temporary directory.
+    pub(crate) fn audit_path(name: &str) -> std::path::PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("sentinel-audit-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join("audit.jsonl")
+    }
+
+    fn audit_log(path: &std::path::Path, max_size: u64) -> AuditLog {
+        AuditLog::from_config(&AlertsConfig {
+            audit_log_path: Some(path.to_path_buf()),
+            audit_log_max_size: max_size,
+            ..AlertsConfig::default()
+        })
+        .unwrap()
+    }
+
+    fn lines(path: &std::path::Path) -> Vec<serde_json::Value> {
+        std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn writes_a_line_per_alert_with_each_delivery() {
+        let path = audit_path("line");
+        let alert = AlertInfo::new(
+            "cctv-webhook".to_string(),
+            "Motion on Front".to_string(),
+            AlertLevel::Alarm,
+        )
+        .unwrap();
+        let outcomes = [
+            (
+                "pushover",
+                SendOutcome {
+                    unsent: Vec::new(),
+                    rejected: Vec::new(),
+                },
+            ),
+            (
+                "sms",
+                SendOutcome {
+                    unsent: vec![0],
+                    rejected: vec![1, 2],
+                },
+            ),
+        ];
+        let log = audit_log(&path, 1024 * 1024);
+        log.append(&alert, &outcomes).await.unwrap();
+        log.append(&alert, &outcomes[..1]).await.unwrap();
+
+        let lines = lines(&path);
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0]["timestamp"].as_u64().unwrap() > 1_700_000_000);
+        assert_eq!(lines[0]["alert"]["source"], "cctv-webhook");
+        assert_eq!(lines[0]["alert"]["message"], "Motion on Front");
+        assert_eq!(
+            lines[0]["deliveries"],
+            serde_json::json!({
+                "pushover": { "delivered": true, "unsent": 0, "rejected": 0 },
+                "sms": { "delivered": false, "unsent": 1, "rejected": 2 },
+            })
+        );
+        assert_eq!(lines[1]["deliveries"].as_object().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn rotates_once_the_size_threshold_is_reached() {
+        let path = audit_path("rotation");
+        let mut rotated = path.clone().into_os_string();
+        rotated.push(".1");
+
+        append_rotating(&path, 20, b"first 10\n").await.unwrap();
+        append_rotating(&path, 20, b"second 10\n").await.unwrap();
+        assert!(!std::path::Path::new(&rotated).exists());
+
+        // The third line would take the file past the limit.
+        append_rotating(&path, 20, b"third\n").await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&rotated).unwrap(),
+            "first 10\nsecond 10\n"
+        );
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "third\n");
+
+        // Only a single backup is kept.
+        append_rotating(&path, 20, b"fourth line fills it\n")
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&rotated).unwrap(), "third\n");
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "fourth line fills it\n"
+        );
+    }
+}
@@ -0,0 +1,76 @@
+use crate::communications::CommunicationRegistry;
+use crate::config::AppConfig;
+use crate::monitors::describe_monitors;
+
+/*
+   Describe every monitor and provider in this build, and whether the current
+   config enables it, so config tooling doesn't need to know the source.
+*/
+
+#[derive(Debug, serde::Serialize)]
+pub(crate) struct Capability {
+    pub name: &'static str,
+    pub description: &'static str,
+
+    /// Dotted config keys that must all be set for it to be enabled.
+    pub required_config: &'static [&'static str],
+    pub enabled: bool,
+}
+impl Capability {
+    /// Enabled if every required key is set in the serialized config.
+    pub fn new(
+        name: &'static str,
+        description: &'static str,
+        required_config: &'static [&'static str],
+        config: &serde_json::Value,
+    ) -> Self {
+        let enabled = required_config.iter().all(|key| {
+            config
+                .pointer(&format!("/{}", key.replace('.', "/")))
+                .is_some_and(|value| !value.is_null())
+        });
+        Self {
+            name,
+            description,
+            required_config,
+            enabled,
+        }
+    }
+}
+
+/// Describe everything in this build against the config.
+pub(crate) fn describe(config: &AppConfig) -> anyhow::Result<Capabilities> {
+    let serialized = serde_json::to_value(config)?;
+    Ok(Capabilities {
+        monitors: describe_monitors(&config.monitors, &serialized),
+        providers: CommunicationRegistry::describe_providers(&serialized),
+    })
+}
+
+#[derive(Debug, serde::Serialize)]
+pub(crate) struct Capabilities {
+    pub monitors: Vec<Capability>,
+    pub providers: Vec<Capability>,
+}
+impl std::fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (kind, capabilities) in [("Monitors", &self.monitors), ("Providers", &self.providers)] {
+            writeln!(f, "{kind}:")?;
+            for capability in capabilities {
+                writeln!(
+                    f,
+                    "  {} [{}] - {} (requires {})",
+                    capability.name,
+                    if capability.enabled {
+                        "enabled"
+                    } else {
+                        "disabled"
+                    },
+                    capability.description,
+                    capability.required_config.join(", ")
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
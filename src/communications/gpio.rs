@@ -0,0 +1,192 @@
+use crate::alerts::AlertInfo;
+use crate::communications::{CommunicationProvider, CommunicationSendResultKind};
+use crate::config::{CommunicationRecipient, CommunicationsConfig, GpioCommunicationConfig};
+
+/*
+   GPIO Communication Provider.
+   Drives a Raspberry Pi GPIO pin high to fire a local siren / relay,
+   releasing it once the configured duration passes without another alert,
+   or as soon as a recovery alert gives the all-clear.
+*/
+
+/// Output the provider drives, so tests don't need real GPIO.
+trait OutputPin: Send {
+    fn set_high(&mut self);
+    fn set_low(&mut self);
+}
+impl OutputPin for rppal::gpio::OutputPin {
+    fn set_high(&mut self) {
+        rppal::gpio::OutputPin::set_high(self);
+    }
+
+    fn set_low(&mut self) {
+        rppal::gpio::OutputPin::set_low(self);
+    }
+}
+
+pub(crate) struct GpioCommunicationProvider {
+    pin: std::sync::Arc<tokio::sync::Mutex<Box<dyn OutputPin>>>,
+    generation: std::sync::Arc<std::sync::atomic::AtomicU64>,
+    duration: std::time::Duration,
+    recipients: Vec<CommunicationRecipient>,
+}
+
+impl GpioCommunicationProvider {
+    fn with_pin(pin: Box<dyn OutputPin>, config: &GpioCommunicationConfig) -> Self {
+        Self {
+            pin: std::sync::Arc::new(tokio::sync::Mutex::new(pin)),
+            generation: std::sync::Arc::new(std::sync::atomic::AtomicU64::new(0)),
+            duration: std::time::Duration::from_secs(config.duration),
+            recipients: vec![CommunicationRecipient {
+                target: format!("gpio{}", config.pin),
+                level: config.level,
+            }],
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl CommunicationProvider for GpioCommunicationProvider {
+    fn name() -> &'static str {
+        "gpio"
+    }
+
+    fn description() -> &'static str {
+        "Drives a GPIO pin high for a siren or relay"
+    }
+
+    fn required_config() -> &'static [&'static str] {
+        &["communications.gpio.pin"]
+    }
+
+    fn from_config(config: &CommunicationsConfig) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        let config = match &config.gpio {
+            Some(config) => config,
+            None => anyhow::bail!("Missing any GPIO config!"),
+        };
+
+        let pin = rppal::gpio::Gpio::new()?.get(config.pin)?.into_output_low();
+        Ok(Self::with_pin(Box::new(pin), config))
+    }
+
+    #[inline]
+    fn get_all_recipients(&self) -> &Vec<CommunicationRecipient> {
+        &self.recipients
+    }
+
+    /// Recovery alerts are always sent regardless of level, since a recovery can be
+    /// below the trigger level and must still release the pin.
+    fn get_recipients(&self, alert: &AlertInfo) -> Vec<usize> {
+        let level_u8 = u8::from(&alert.level);
+        self.recipients
+            .iter()
+            .enumerate()
+            .filter(|(_, recipient)| alert.metadata.recovery || level_u8 >= recipient.level)
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    async fn send(&self, alert: &AlertInfo, _: &[usize]) -> CommunicationSendResultKind {
+        // Each alert extends the hold, only the latest one is allowed to release the pin.
+        let generation = self
+            .generation
+            .fetch_add(1, std::sync::atomic::Ordering::SeqCst)
+            + 1;
+        if alert.metadata.recovery {
+            self.pin.lock().await.set_low();
+            return CommunicationSendResultKind::Completed { failed: vec![] };
+        }
+        self.pin.lock().await.set_high();
+
+        let pin = self.pin.clone();
+        let current = self.generation.clone();
+        let duration = self.duration;
+        tokio::spawn(async move {
+            tokio::time::sleep(duration).await;
+            if current.load(std::sync::atomic::Ordering::SeqCst) == generation {
+                pin.lock().await.set_low();
+            }
+        });
+
+        CommunicationSendResultKind::Completed { failed: vec![] }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alerts::AlertLevel;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    struct MockPin(Arc<AtomicBool>);
+    impl OutputPin for MockPin {
+        fn set_high(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+
+        fn set_low(&mut self) {
+            self.0.store(false, Ordering::SeqCst);
+        }
+    }
+
+    fn provider() -> (GpioCommunicationProvider, Arc<AtomicBool>) {
+        let high = Arc::new(AtomicBool::new(false));
+        let config: GpioCommunicationConfig = toml::from_str("pin = 17\nduration = 60").unwrap();
+        (
+            GpioCommunicationProvider::with_pin(Box::new(MockPin(high.clone())), &config),
+            high,
+        )
+    }
+
+    fn alarm() -> AlertInfo {
+        AlertInfo::new("cctv".to_string(), "Motion".to_string(), AlertLevel::Alarm).unwrap()
+    }
+
+    fn recovery() -> AlertInfo {
+        let mut alert =
+            AlertInfo::new("cctv".to_string(), "Clear".to_string(), AlertLevel::Info).unwrap();
+        alert.metadata.recovery = true;
+        alert
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn drives_the_pin_on_alarm_and_releases_it() {
+        let (provider, high) = provider();
+        provider.send(&alarm(), &[0]).await;
+        assert!(high.load(Ordering::SeqCst));
+
+        tokio::time::sleep(std::time::Duration::from_secs(59)).await;
+        assert!(high.load(Ordering::SeqCst));
+        tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+        assert!(!high.load(Ordering::SeqCst));
+
+        // Released on clear before the duration ends, and not driven again by the old timer.
+        provider.send(&alarm(), &[0]).await;
+        tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+        let recovery = recovery();
+        assert!(provider.supports(&recovery));
+        assert_eq!(provider.get_recipients(&recovery), [0]);
+        provider.send(&recovery, &[0]).await;
+        assert!(!high.load(Ordering::SeqCst));
+
+        tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+        assert!(!high.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn another_alarm_extends_the_hold() {
+        let (provider, high) = provider();
+        provider.send(&alarm(), &[0]).await;
+        tokio::time::sleep(std::time::Duration::from_secs(30)).await;
+        provider.send(&alarm(), &[0]).await;
+
+        tokio::time::sleep(std::time::Duration::from_secs(45)).await;
+        assert!(high.load(Ordering::SeqCst));
+        tokio::time::sleep(std::time::Duration::from_secs(20)).await;
+        assert!(!high.load(Ordering::SeqCst));
+    }
+}
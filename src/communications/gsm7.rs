@@ -0,0 +1,140 @@
+/*
+   GSM-7 normalization for SMS. A single character outside of the GSM-7 alphabet
+   forces the whole message into UCS-2, which cuts a segment from 160 to 70
+   characters, so common lookalikes are transliterated and the rest replaced.
+*/
+
+const BASIC: &str = "@£$¥èéùìòÇ\nØø\rÅåΔ_ΦΓΛΩΠΨΣΘΞÆæßÉ !\"#¤%&'()*+,-./0123456789:;<=>?\
+    ¡ABCDEFGHIJKLMNOPQRSTUVWXYZÄÖÑÜ§¿abcdefghijklmnopqrstuvwxyzäöñüà";
+
+// Characters sent as an escape sequence, so each counts as two septets.
+const EXTENSION: &str = "\u{c}^{}\\[~]|€";
+
+#[inline]
+fn is_basic(c: char) -> bool {
+    BASIC.contains(c)
+}
+
+#[inline]
+fn is_extension(c: char) -> bool {
+    EXTENSION.contains(c)
+}
+
+fn transliterate(c: char) -> Option<&'static str> {
+    Some(match c {
+        '‘' | '’' | '‚' | '′' | '`' | '´' => "'",
+        '“' | '”' | '„' | '″' => "\"",
+        '–' | '—' | '‒' | '−' => "-",
+        '…' => "...",
+        '\u{a0}' | '\u{2009}' | '\u{200a}' | '\u{202f}' | '\t' => " ",
+        '•' | '·' => "*",
+        'á' | 'â' | 'ã' | 'ā' => "a",
+        'Á' | 'Â' | 'À' | 'Ã' | 'Ā' => "A",
+        'ê' | 'ë' | 'ē' => "e",
+        'Ê' | 'È' | 'Ë' | 'Ē' => "E",
+        'í' | 'î' | 'ï' => "i",
+        'Í' | 'Î' | 'Ì' | 'Ï' => "I",
+        'ó' | 'ô' | 'õ' => "o",
+        'Ó' | 'Ô' | 'Ò' | 'Õ' => "O",
+        'ú' | 'û' => "u",
+        'Ú' | 'Û' | 'Ù' => "U",
+        'ç' => "c",
+        'ý' | 'ÿ' => "y",
+        'Ý' => "Y",
+        _ => return None,
+    })
+}
+
+/// Normalize text to the GSM-7 alphabet, dropping characters with no close match
+/// such as emoji, and replacing any other characters with '?'.
+pub(crate) fn normalize(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    for c in text.chars() {
+        if is_basic(c) || is_extension(c) {
+            output.push(c);
+        } else if let Some(replacement) = transliterate(c) {
+            output.push_str(replacement);
+        } else if c.is_alphanumeric() || c.is_ascii() {
+            output.push('?');
+        }
+    }
+    output
+}
+
+/// Get the length of GSM-7 text in septets, as used for SMS segment limits.
+pub(crate) fn septet_length(text: &str) -> usize {
+    text.chars()
+        .map(|c| if is_extension(c) { 2 } else { 1 })
+        .sum()
+}
+
+/// Whether text can be sent in GSM-7, rather than falling back to UCS-2.
+pub(crate) fn is_gsm7(text: &str) -> bool {
+    text.chars().all(|c| is_basic(c) || is_extension(c))
+}
+
+/// Get the number of SMS segments needed to send text. Multipart messages lose
+/// some of each segment to the concatenation header.
+pub(crate) fn segments(text: &str) -> usize {
+    let (length, single, multipart) = if is_gsm7(text) {
+        (septet_length(text), 160, 153)
+    } else {
+        (text.encode_utf16().count(), 70, 67)
+    };
+    if length <= single {
+        1
+    } else {
+        length.div_ceil(multipart)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_gsm7_text() {
+        assert!(is_gsm7("Front door opened @ 12:00 £5 [€]"));
+        assert!(!is_gsm7("Front door opened 🚪"));
+        assert!(!is_gsm7("“Front door”"));
+    }
+
+    #[test]
+    fn counts_extension_characters_as_two_septets() {
+        assert_eq!(septet_length("abc"), 3);
+        assert_eq!(septet_length("[€]"), 6);
+    }
+
+    #[test]
+    fn counts_segments() {
+        for (text, segments) in [
+            (String::new(), 1),
+            ("a".repeat(160), 1),
+            ("a".repeat(161), 2),
+            ("a".repeat(306), 2),
+            ("a".repeat(307), 3),
+            ("€".repeat(80), 1),
+            ("€".repeat(81), 2),
+            ("ü".repeat(160), 1),
+            ("é".repeat(70) + "🚪", 2),
+            ("“".repeat(70), 1),
+            ("“".repeat(71), 2),
+            ("“".repeat(134), 2),
+            ("“".repeat(135), 3),
+        ] {
+            assert_eq!(super::segments(&text), segments, "'{text}'");
+        }
+    }
+
+    #[test]
+    fn normalizes_emoji_laden_messages() {
+        let text = "🚨 Alarm – “front door” opened… 🚪🔔";
+        assert_eq!(segments(&text.repeat(3)), 2);
+
+        let normalized = normalize(text);
+        assert_eq!(normalized, " Alarm - \"front door\" opened... ");
+        assert!(is_gsm7(&normalized));
+        assert_eq!(septet_length(&normalized), 32);
+        assert_eq!(segments(&normalized.repeat(3)), 1);
+    }
+}
@@ -0,0 +1,246 @@
+use crate::alerts::AlertInfo;
+use crate::communications::theme::Theme;
+use crate::communications::{CommunicationProvider, CommunicationSendResultKind, SendError};
+use crate::config::{
+    CommunicationRecipient, CommunicationsConfig, HomeAssistantCommunicationConfig, TimestampFormat,
+};
+use crate::outbound::{client_builder, ensure_host_allowed, send_with_reset_retry};
+use anyhow::Context;
+use futures::StreamExt;
+
+/*
+   Home Assistant Communication Provider.
+   Calls a notify service for each recipient, where the recipient target is the
+   service name, e.g. "mobile_app_phone" for `notify.mobile_app_phone`.
+   https://www.home-assistant.io/integrations/notify/
+*/
+
+#[derive(serde::Serialize)]
+struct HomeAssistantPayload {
+    pub title: String,
+    pub message: String,
+}
+
+pub(crate) struct HomeAssistantCommunicationProvider {
+    client: reqwest::Client,
+    config: HomeAssistantCommunicationConfig,
+    timestamp_format: TimestampFormat,
+    theme: Theme,
+}
+impl HomeAssistantCommunicationProvider {
+    fn service_url(&self, service: &str) -> String {
+        format!(
+            "{}/api/services/notify/{service}",
+            self.config.base_url.trim_end_matches('/')
+        )
+    }
+
+    fn create_payload(&self, alert: &AlertInfo) -> HomeAssistantPayload {
+        let theme = self.theme.get(&alert.level);
+        HomeAssistantPayload {
+            title: format!(
+                "{} sentinel {} - {}",
+                theme.emoji, theme.label, alert.source
+            ),
+            message: match alert.format_timestamp(&self.timestamp_format) {
+                Some(timestamp) => format!("{} ({timestamp})", alert.message),
+                None => alert.message.clone(),
+            },
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl CommunicationProvider for HomeAssistantCommunicationProvider {
+    fn name() -> &'static str {
+        "home_assistant"
+    }
+
+    fn description() -> &'static str {
+        "Calls Home Assistant notify services"
+    }
+
+    fn required_config() -> &'static [&'static str] {
+        &[
+            "communications.home_assistant.base_url",
+            "communications.home_assistant.token",
+            "communications.home_assistant.recipients",
+        ]
+    }
+
+    fn from_config(config: &CommunicationsConfig) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        let timestamp_format = config.timestamp_format.clone();
+        let theme = Theme::from_config(&config.theme);
+        let config = match &config.home_assistant {
+            Some(config) => config,
+            None => anyhow::bail!("Missing any Home Assistant config!"),
+        };
+
+        Ok(Self {
+            client: client_builder()
+                .timeout(std::time::Duration::from_secs(config.timeout))
+                .build()
+                .context("Failed to build Home Assistant HTTP client!")?,
+            config: config.clone(),
+            timestamp_format,
+            theme,
+        })
+    }
+
+    #[inline]
+    fn get_all_recipients(&self) -> &Vec<CommunicationRecipient> {
+        &self.config.recipients
+    }
+
+    async fn send(&self, alert: &AlertInfo, recipients: &[usize]) -> CommunicationSendResultKind {
+        if let Err(e) = ensure_host_allowed(&self.config.base_url) {
+            return CommunicationSendResultKind::Unavailable {
+                reason: e.to_string(),
+            };
+        }
+
+        let payload = self.create_payload(alert);
+        let concurrency = self.config.concurrency.unwrap_or(recipients.len()).max(1);
+        let futures: Vec<_> = recipients
+            .iter()
+            .map(|index| {
+                let url = self.service_url(&self.config.recipients[*index].target);
+                let request = self
+                    .client
+                    .post(url)
+                    .bearer_auth(&self.config.token)
+                    .json(&payload);
+
+                async move { (index, send_with_reset_retry(request).await) }
+            })
+            .collect();
+
+        let mut failed = Vec::with_capacity(recipients.len());
+        let mut results = futures::stream::iter(futures).buffer_unordered(concurrency);
+        while let Some((index, result)) = results.next().await {
+            match result {
+                Ok(response) if response.status().is_success() => {}
+                Ok(response) => {
+                    let status = response.status();
+                    let message = response.text().await.unwrap_or_default();
+
+                    // An unauthorized token won't work for any retry or recipient.
+                    let error = match status.as_u16() {
+                        401 => SendError::Invalid(message),
+                        status => SendError::from_status(status, message),
+                    };
+                    failed.push((*index, error));
+                }
+                Err(e) => failed.push((*index, SendError::Transient(e.to_string()))),
+            }
+        }
+        CommunicationSendResultKind::Completed { failed }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alerts::AlertLevel;
+
+    fn provider(base_url: &str) -> HomeAssistantCommunicationProvider {
+        let config = format!(
+            "{}\n[home_assistant]\nbase_url = \"{base_url}\"\ntoken = \"token\"\nrecipients = [{{ target = \"mobile_app_phone\", level = 0 }}, {{ target = \"revoked\", level = 0 }}]",
+            crate::communications::tests::THEME
+        );
+        HomeAssistantCommunicationProvider::from_config(&toml::from_str(&config).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn payload_reflects_the_theme() {
+        let provider = provider("http://127.0.0.1:8123");
+        let alert = |level| {
+            AlertInfo::new(
+                "ping monitor".to_string(),
+                "Router offline".to_string(),
+                level,
+            )
+            .unwrap()
+        };
+
+        let payload = provider.create_payload(&alert(AlertLevel::Critical));
+        assert_eq!(payload.title, "🔥 sentinel CRIT - ping monitor");
+        assert_eq!(payload.message, "Router offline");
+
+        let payload = provider.create_payload(&alert(AlertLevel::Alarm));
+        assert_eq!(payload.title, "🚨 sentinel Alarm - ping monitor");
+    }
+
+    type Requests = std::sync::Arc<std::sync::Mutex<Vec<(String, Option<String>)>>>;
+
+    /// Mock Home Assistant recording each request's path and Authorization header,
+    /// where the "revoked" service is unauthorized.
+    fn serve() -> (std::net::SocketAddr, Requests) {
+        use warp::Filter;
+
+        let requests = Requests::default();
+        let recorded = requests.clone();
+        let routes = warp::post()
+            .and(warp::path::full())
+            .and(warp::header::optional::<String>("authorization"))
+            .map(move |path: warp::path::FullPath, auth: Option<String>| {
+                let path = path.as_str().to_string();
+                let status = if path.ends_with("/revoked") {
+                    warp::http::StatusCode::UNAUTHORIZED
+                } else {
+                    warp::http::StatusCode::OK
+                };
+                recorded.lock().unwrap().push((path, auth));
+                warp::reply::with_status("", status)
+            });
+        let (address, server) = warp::serve(routes).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        (address, requests)
+    }
+
+    #[tokio::test]
+    async fn calls_the_notify_service_with_the_token() {
+        let (address, requests) = serve();
+        let provider = provider(&format!("http://{address}/"));
+        let alert = AlertInfo::new(
+            "ping monitor".to_string(),
+            "Router offline".to_string(),
+            AlertLevel::Critical,
+        )
+        .unwrap();
+
+        let CommunicationSendResultKind::Completed { failed } = provider.send(&alert, &[0]).await
+        else {
+            panic!("Home Assistant was unavailable!");
+        };
+        assert!(failed.is_empty());
+        assert_eq!(
+            *requests.lock().unwrap(),
+            [(
+                "/api/services/notify/mobile_app_phone".to_string(),
+                Some("Bearer token".to_string())
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn unauthorized_requests_are_invalid() {
+        let (address, _) = serve();
+        let provider = provider(&format!("http://{address}"));
+        let alert = AlertInfo::new(
+            "ping monitor".to_string(),
+            "Router offline".to_string(),
+            AlertLevel::Critical,
+        )
+        .unwrap();
+
+        let CommunicationSendResultKind::Completed { failed } = provider.send(&alert, &[1]).await
+        else {
+            panic!("Home Assistant was unavailable!");
+        };
+        assert!(matches!(failed[..], [(1, SendError::Invalid(_))]));
+    }
+}
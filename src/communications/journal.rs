@@ -0,0 +1,125 @@
+use crate::alerts::{AlertInfo, AlertLevel};
+use crate::communications::{CommunicationProvider, CommunicationSendResultKind, SendError};
+use crate::config::{CommunicationRecipient, CommunicationsConfig};
+use libsystemd::logging::Priority;
+
+/*
+   systemd Journal Communication Provider.
+   Writes alerts as structured journal entries, so it keeps working locally
+   even when the network is down.
+*/
+
+fn priority(level: &AlertLevel) -> Priority {
+    match level {
+        AlertLevel::Info => Priority::Info,
+        AlertLevel::Warning => Priority::Warning,
+        AlertLevel::Critical => Priority::Critical,
+        AlertLevel::Alarm => Priority::Alert,
+    }
+}
+
+pub(crate) struct JournalCommunicationProvider {
+    recipients: Vec<CommunicationRecipient>,
+}
+
+#[async_trait::async_trait]
+impl CommunicationProvider for JournalCommunicationProvider {
+    fn name() -> &'static str {
+        "journal"
+    }
+
+    fn description() -> &'static str {
+        "Writes alerts to the systemd journal"
+    }
+
+    fn required_config() -> &'static [&'static str] {
+        &["communications.journal"]
+    }
+
+    fn from_config(config: &CommunicationsConfig) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        let config = match &config.journal {
+            Some(config) => config,
+            None => anyhow::bail!("Missing any journal config!"),
+        };
+        if !libsystemd::daemon::booted() {
+            anyhow::bail!("System was not booted with systemd!");
+        }
+
+        // The journal is the only recipient, using the configured minimum level.
+        Ok(Self {
+            recipients: vec![CommunicationRecipient {
+                target: "journal".to_string(),
+                level: config.level,
+            }],
+        })
+    }
+
+    #[inline]
+    fn get_all_recipients(&self) -> &Vec<CommunicationRecipient> {
+        &self.recipients
+    }
+
+    async fn send(&self, alert: &AlertInfo, recipients: &[usize]) -> CommunicationSendResultKind {
+        let priority = priority(&alert.level);
+        let level = format!("{:?}", alert.level);
+        let fields = [
+            ("SOURCE", alert.source.as_str()),
+            ("ALERT_LEVEL", level.as_str()),
+        ];
+
+        match libsystemd::logging::journal_send(priority, &alert.to_string(), fields.into_iter()) {
+            Ok(()) => CommunicationSendResultKind::Completed { failed: vec![] },
+            Err(e) => CommunicationSendResultKind::Completed {
+                failed: recipients
+                    .iter()
+                    .map(|index| (*index, SendError::Transient(e.to_string())))
+                    .collect(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_levels_to_journal_priorities() {
+        for (level, expected) in [
+            (AlertLevel::Info, Priority::Info),
+            (AlertLevel::Warning, Priority::Warning),
+            (AlertLevel::Critical, Priority::Critical),
+            (AlertLevel::Alarm, Priority::Alert),
+        ] {
+            assert_eq!(priority(&level) as u8, expected as u8);
+        }
+    }
+
+    #[tokio::test]
+    async fn writes_to_the_journal() {
+        // Only runs where journald is listening, such as on the Pi itself.
+        if !std::path::Path::new("/run/systemd/journal/socket").exists() {
+            return;
+        }
+
+        let provider = JournalCommunicationProvider {
+            recipients: vec![CommunicationRecipient {
+                target: "journal".to_string(),
+                level: 1,
+            }],
+        };
+        let alert = AlertInfo::new(
+            "test".to_string(),
+            "Journal test".to_string(),
+            AlertLevel::Critical,
+        )
+        .unwrap();
+        assert!(matches!(
+            provider.send(&alert, &[0]).await,
+            CommunicationSendResultKind::Completed { failed } if failed.is_empty()
+        ));
+    }
+}
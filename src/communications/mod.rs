@@ -1,17 +1,75 @@
+#[cfg(target_os = "linux")]
+mod gpio;
+mod gsm7;
+mod home_assistant;
+#[cfg(target_os = "linux")]
+mod journal;
+mod pagerduty;
 mod pushover;
+mod recording;
 mod sms;
+mod stdout;
+mod theme;
 
-use crate::alerts::AlertInfo;
+use crate::alerts::{display_name, AlertInfo, AlertLevel};
+use crate::capabilities::Capability;
 use crate::communications::pushover::PushoverCommunicationProvider;
 use crate::communications::sms::SMSCommunicationProvider;
-use crate::config::{CommunicationRecipient, CommunicationsConfig};
+use crate::config::{
+    BroadcastStrategy, CommunicationRecipient, CommunicationsConfig, LevelRetryConfig,
+};
 use log::{debug, error, warn};
 
+const INIT_RETRY_DELAY_MIN: std::time::Duration = std::time::Duration::from_secs(1);
+const INIT_RETRY_DELAY_MAX: std::time::Duration = std::time::Duration::from_secs(30);
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum SendError {
+    /// The send may succeed if retried, e.g. network errors or rate limiting.
+    #[error("Transient failure: {0}")]
+    Transient(String),
+
+    /// The provider can't send to this recipient, e.g. failed authentication.
+    #[error("Permanent failure: {0}")]
+    Permanent(String),
+
+    /// The request was rejected as invalid, e.g. an unknown recipient.
+    #[error("Invalid request: {0}")]
+    Invalid(String),
+}
+impl SendError {
+    /// Classify a failed HTTP response status.
+    pub fn from_status(status: u16, message: String) -> Self {
+        match status {
+            429 | 500..=599 => SendError::Transient(message),
+            401 | 403 => SendError::Permanent(message),
+            _ => SendError::Invalid(message),
+        }
+    }
+
+    #[inline]
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, SendError::Transient(_))
+    }
+}
+
 pub enum CommunicationSendResultKind {
-    Completed { failed: Vec<usize> },
+    Completed { failed: Vec<(usize, SendError)> },
     Unavailable { reason: String },
 }
 
+/// Recipients left unsent after sending, split by whether retrying could help.
+pub(crate) struct SendOutcome {
+    pub unsent: Vec<usize>,
+    pub rejected: Vec<usize>,
+}
+impl SendOutcome {
+    #[inline]
+    pub fn is_delivered(&self) -> bool {
+        self.unsent.is_empty() && self.rejected.is_empty()
+    }
+}
+
 #[async_trait::async_trait]
 pub(crate) trait CommunicationProvider: Send + Sync + 'static {
     /// Returns the provider name for logging.
@@ -19,6 +77,16 @@ pub(crate) trait CommunicationProvider: Send + Sync + 'static {
     where
         Self: Sized;
 
+    /// Short description of how the provider sends alerts.
+    fn description() -> &'static str
+    where
+        Self: Sized;
+
+    /// Dotted config keys that must all be set for the provider to be enabled.
+    fn required_config() -> &'static [&'static str]
+    where
+        Self: Sized;
+
     /// Creates a new communication provider instance with given configuration.
     /// Implementations can override this for custom initialization.
     /// If None is returned, the provider is invalid / misconfigured and cannot be used.
@@ -40,23 +108,104 @@ pub(crate) trait CommunicationProvider: Send + Sync + 'static {
             .collect()
     }
 
+    /// Whether the provider can meaningfully send this alert at all. Unsupported
+    /// alerts are skipped without being counted as a failure.
+    fn supports(&self, _alert: &AlertInfo) -> bool {
+        true
+    }
+
     /// Send the alert via provider.
     async fn send(&self, alert: &AlertInfo, recipients: &[usize]) -> CommunicationSendResultKind;
 }
 
-fn try_from_config<T: CommunicationProvider>(
-    config: &CommunicationsConfig,
-) -> Option<(&'static str, Box<dyn CommunicationProvider>)> {
-    let name = T::name();
-    match T::from_config(config) {
-        Ok(provider) => {
-            debug!("Successfully created CommunicationProvider '{name}'.");
-            Some((name, Box::new(provider) as Box<dyn CommunicationProvider>))
+/// A provider in this build, with what's needed to describe it without creating it.
+struct ProviderFactory {
+    name: &'static str,
+    description: &'static str,
+    required_config: &'static [&'static str],
+    create: fn(&CommunicationsConfig) -> anyhow::Result<Box<dyn CommunicationProvider>>,
+}
+impl ProviderFactory {
+    fn of<T: CommunicationProvider>() -> Self {
+        Self {
+            name: T::name(),
+            description: T::description(),
+            required_config: T::required_config(),
+            create: |config| Ok(Box::new(T::from_config(config)?)),
+        }
+    }
+
+    fn describe(&self, serialized: &serde_json::Value) -> Capability {
+        Capability::new(
+            self.name,
+            self.description,
+            self.required_config,
+            serialized,
+        )
+    }
+
+    /// Configured if every required key is set, which doesn't mean it can be created.
+    fn is_configured(&self, serialized: &serde_json::Value) -> bool {
+        self.describe(serialized).enabled
+    }
+}
+
+/// Every provider in this build, in the order they're created.
+fn factories() -> Vec<ProviderFactory> {
+    vec![
+        ProviderFactory::of::<SMSCommunicationProvider>(),
+        ProviderFactory::of::<PushoverCommunicationProvider>(),
+        ProviderFactory::of::<home_assistant::HomeAssistantCommunicationProvider>(),
+        ProviderFactory::of::<pagerduty::PagerDutyCommunicationProvider>(),
+        ProviderFactory::of::<recording::RecordingCommunicationProvider>(),
+        ProviderFactory::of::<stdout::StdoutCommunicationProvider>(),
+        #[cfg(target_os = "linux")]
+        ProviderFactory::of::<journal::JournalCommunicationProvider>(),
+        #[cfg(target_os = "linux")]
+        ProviderFactory::of::<gpio::GpioCommunicationProvider>(),
+    ]
+}
+
+/// Spread the delay randomly by up to the jitter fraction either way, so retries of
+/// alerts that failed together don't all land on a recovering provider at once.
+fn jittered(delay: std::time::Duration, jitter: f64) -> std::time::Duration {
+    if jitter <= 0.0 {
+        return delay;
+    }
+    delay.mul_f64(1.0 + rand::Rng::gen_range(&mut rand::thread_rng(), -jitter..=jitter))
+}
+
+/// Once an alarm passes its deadline without enough confirmations, only the highest
+/// priority provider still sending keeps retrying, rather than fragmenting retries.
+struct DeliveryDeadline {
+    at: tokio::time::Instant,
+    required: usize,
+    confirmations: std::sync::atomic::AtomicUsize,
+
+    // Providers still sending, in priority order.
+    pending: std::sync::Mutex<Vec<&'static str>>,
+}
+impl DeliveryDeadline {
+    fn should_retry(&self, name: &'static str) -> bool {
+        if tokio::time::Instant::now() < self.at
+            || self
+                .confirmations
+                .load(std::sync::atomic::Ordering::Relaxed)
+                >= self.required
+        {
+            return true;
         }
-        Err(e) => {
-            warn!("CommunicationProvider '{name}' failed to initialize: {e:?}");
-            None
+        let pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        pending.first() == Some(&name)
+    }
+
+    fn finish(&self, name: &'static str, delivered: bool) {
+        if delivered {
+            self.confirmations
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
         }
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        pending.retain(|pending| *pending != name);
     }
 }
 
@@ -65,43 +214,340 @@ pub(crate) struct CommunicationRegistry {
         std::sync::Arc<std::collections::HashMap<&'static str, Box<dyn CommunicationProvider>>>,
     retry_max: u64,
     retry_delay: std::time::Duration,
+    retry_jitter: f64,
+    level_retry: std::collections::BTreeMap<AlertLevel, LevelRetryConfig>,
+    alarm_strategy: BroadcastStrategy,
+    alarm_confirmations: usize,
+    priority: Vec<String>,
+    info_providers: Option<std::collections::HashSet<String>>,
+    fallback_providers: std::collections::HashSet<String>,
+    alarm_stagger: std::time::Duration,
+    alarm_deadline: Option<std::time::Duration>,
+    send_spacing: std::time::Duration,
+    last_sent:
+        std::collections::HashMap<&'static str, tokio::sync::Mutex<Option<tokio::time::Instant>>>,
 }
 impl CommunicationRegistry {
-    pub fn new(config: &CommunicationsConfig) -> anyhow::Result<Self> {
-        // Attempt to create each provider from_config.
-        let providers_vec: Vec<_> = vec![
-            try_from_config::<SMSCommunicationProvider>(config),
-            try_from_config::<PushoverCommunicationProvider>(config),
-        ]
-        .into_iter()
-        .flatten()
-        .collect();
+    /// Describe every provider in this build, without creating any of them.
+    pub fn describe_providers(serialized: &serde_json::Value) -> Vec<Capability> {
+        factories()
+            .iter()
+            .map(|factory| factory.describe(serialized))
+            .collect()
+    }
 
-        let size = providers_vec.len();
-        if size == 0 {
+    pub async fn new(config: &CommunicationsConfig) -> anyhow::Result<Self> {
+        Self::create(config, &factories()).await
+    }
+
+    /// Create every configured provider. Providers may depend on a network that is still
+    /// coming up on boot, so those that fail keep being retried with backoff for a bounded time.
+    async fn create(
+        config: &CommunicationsConfig,
+        factories: &[ProviderFactory],
+    ) -> anyhow::Result<Self> {
+        let serialized = serde_json::json!({ "communications": config });
+        let mut pending: Vec<_> = factories
+            .iter()
+            .filter(|factory| factory.is_configured(&serialized))
+            .collect();
+
+        let deadline =
+            tokio::time::Instant::now() + std::time::Duration::from_secs(config.init_timeout);
+        let mut delay = INIT_RETRY_DELAY_MIN;
+        let mut providers_vec = Vec::with_capacity(pending.len());
+        loop {
+            pending.retain(|factory| match (factory.create)(config) {
+                Ok(provider) => {
+                    debug!(
+                        "Successfully created CommunicationProvider '{}'.",
+                        factory.name
+                    );
+                    providers_vec.push((factory.name, provider));
+                    false
+                }
+                Err(e) => {
+                    warn!(
+                        "CommunicationProvider '{}' failed to initialize: {e:?}",
+                        factory.name
+                    );
+                    true
+                }
+            });
+            if pending.is_empty() {
+                break;
+            }
+            if tokio::time::Instant::now() + delay > deadline {
+                // Configured providers are expected to send, so never drop one quietly.
+                for factory in &pending {
+                    error!(
+                        "CommunicationProvider '{}' is configured but failed to initialize, it won't send alerts!",
+                        factory.name
+                    );
+                }
+                break;
+            }
+
+            warn!(
+                "{} CommunicationProvider(s) failed to initialize, retrying in {}s!",
+                pending.len(),
+                delay.as_secs()
+            );
+            tokio::time::sleep(delay).await;
+            delay = std::cmp::min(delay * 2, INIT_RETRY_DELAY_MAX);
+        }
+
+        if providers_vec.is_empty() {
             anyhow::bail!("Failed to create any CommunicationProviders!");
         }
+        Ok(Self::from_providers(config, providers_vec))
+    }
+
+    fn from_providers(
+        config: &CommunicationsConfig,
+        providers_vec: Vec<(&'static str, Box<dyn CommunicationProvider>)>,
+    ) -> Self {
+        let size = providers_vec.len();
 
         let mut providers = std::collections::HashMap::with_capacity(size);
         for (name, provider) in providers_vec {
             providers.insert(name, provider);
         }
 
-        Ok(Self {
+        let last_sent = providers
+            .keys()
+            .map(|name| (*name, tokio::sync::Mutex::new(None)))
+            .collect();
+
+        Self {
             providers: std::sync::Arc::new(providers),
             retry_max: config.retry_max,
             retry_delay: std::time::Duration::from_secs(config.retry_delay),
-        })
+            retry_jitter: config.retry_jitter,
+            level_retry: config.level_retry.clone(),
+            alarm_strategy: config.alarm_strategy.clone(),
+            alarm_confirmations: config.alarm_confirmations,
+            priority: config.priority.clone(),
+            info_providers: config.info_providers.clone(),
+            fallback_providers: config.fallback_providers.clone(),
+            alarm_stagger: std::time::Duration::from_millis(config.alarm_stagger),
+            alarm_deadline: config.alarm_deadline.map(std::time::Duration::from_secs),
+            send_spacing: std::time::Duration::from_millis(config.send_spacing),
+            last_sent,
+        }
     }
 
-    pub async fn broadcast(&self, alert: &AlertInfo) {
-        let futures: Vec<_> = self
+    /// Broadcast the alert, returning the outcome for each provider that was sent to.
+    pub async fn broadcast(&self, alert: &AlertInfo) -> Vec<(&'static str, SendOutcome)> {
+        let mut providers: Vec<_> = self
             .providers
             .iter()
-            .map(|(name, provider)| self.send_with_retry(name, provider.as_ref(), alert))
+            .filter(|(name, _)| {
+                let allowed = alert.metadata.allows_provider(name);
+                if !allowed {
+                    debug!("Skipping '{name}' for alert due to its metadata.");
+                }
+                allowed
+            })
+            .filter(|(name, _)| {
+                let allowed = alert.level != AlertLevel::Info
+                    || self
+                        .info_providers
+                        .as_ref()
+                        .is_none_or(|info_providers| info_providers.contains(**name));
+                if !allowed {
+                    debug!("Skipping '{name}' since it isn't an Info provider.");
+                }
+                allowed
+            })
+            .filter_map(|(name, provider)| {
+                if !provider.supports(alert) {
+                    debug!("Skipping '{name}' since it doesn't support the alert.");
+                    return None;
+                }
+
+                let recipients = provider.get_recipients(alert);
+                if recipients.is_empty() {
+                    debug!(
+                        "There are no recipients for '{}' with level {:?}",
+                        name, alert.level
+                    );
+                    return None;
+                }
+                Some((*name, provider.as_ref(), recipients))
+            })
             .collect();
 
-        futures::future::join_all(futures).await;
+        if providers.is_empty() && !self.fallback_providers.is_empty() {
+            debug!("No providers matched the alert, using fallback providers.");
+            providers = self.fallback_recipients(alert);
+        }
+
+        if alert.is_alarm()
+            && !alert.metadata.unsuppressible
+            && self.alarm_strategy == BroadcastStrategy::Sequential
+        {
+            self.broadcast_sequential(alert, providers).await
+        } else {
+            self.broadcast_parallel(alert, providers).await
+        }
+    }
+
+    /// Every recipient of the fallback providers, ignoring levels but still honoring the alert's metadata.
+    fn fallback_recipients(
+        &self,
+        alert: &AlertInfo,
+    ) -> Vec<(&'static str, &dyn CommunicationProvider, Vec<usize>)> {
+        self.providers
+            .iter()
+            .filter(|(name, provider)| {
+                self.fallback_providers.contains(**name)
+                    && alert.metadata.allows_provider(name)
+                    && provider.supports(alert)
+                    && !provider.get_all_recipients().is_empty()
+            })
+            .map(|(name, provider)| {
+                let recipients = (0..provider.get_all_recipients().len()).collect();
+                (*name, provider.as_ref(), recipients)
+            })
+            .collect()
+    }
+
+    async fn broadcast_parallel(
+        &self,
+        alert: &AlertInfo,
+        providers: Vec<(&'static str, &dyn CommunicationProvider, Vec<usize>)>,
+    ) -> Vec<(&'static str, SendOutcome)> {
+        // Give higher priority providers a head start on alarms, so the fastest
+        // confirmation isn't competing with slower providers for the network.
+        let stagger = if alert.is_alarm() {
+            self.alarm_stagger
+        } else {
+            std::time::Duration::ZERO
+        };
+        let deadline = self
+            .alarm_deadline
+            .filter(|_| alert.is_alarm())
+            .map(|deadline| {
+                let mut pending: Vec<_> = providers.iter().map(|(name, _, _)| *name).collect();
+                pending.sort_by_key(|name| (self.priority_position(name), *name));
+                DeliveryDeadline {
+                    at: tokio::time::Instant::now() + deadline,
+                    required: self.alarm_confirmations,
+                    confirmations: std::sync::atomic::AtomicUsize::new(0),
+                    pending: std::sync::Mutex::new(pending),
+                }
+            });
+
+        let deadline = deadline.as_ref();
+        let futures: Vec<_> = providers
+            .into_iter()
+            .map(|(name, provider, recipients)| async move {
+                let delay = stagger * self.priority_position(name) as u32;
+                if !delay.is_zero() {
+                    tokio::time::sleep(delay).await;
+                }
+                self.send_with_retry(name, provider, alert, recipients, deadline)
+                    .await
+            })
+            .collect();
+
+        futures::future::join_all(futures).await
+    }
+
+    /// Try each provider once in priority order, stopping once enough have fully sent.
+    /// If there aren't enough confirmations, the remaining providers retry in parallel
+    /// with the rest of the budget, so that first attempt counts towards it.
+    async fn broadcast_sequential(
+        &self,
+        alert: &AlertInfo,
+        mut providers: Vec<(&'static str, &dyn CommunicationProvider, Vec<usize>)>,
+    ) -> Vec<(&'static str, SendOutcome)> {
+        providers.sort_by_key(|(name, _, _)| (self.priority_position(name), *name));
+
+        let (retry_max, retry_delay) = self.get_retry(&alert.level);
+        let mut confirmations = 0;
+        let mut unconfirmed = Vec::new();
+        let mut outcomes = Vec::new();
+        for (name, provider, recipients) in providers {
+            if confirmations >= self.alarm_confirmations {
+                debug!("Skipping '{name}' since the alarm has enough confirmations.");
+                continue;
+            }
+
+            let outcome = self
+                .send_attempts(name, provider, alert, recipients, (0, retry_delay), None)
+                .await;
+            if outcome.is_delivered() {
+                confirmations += 1;
+            } else if !outcome.unsent.is_empty() {
+                unconfirmed.push((name, provider, outcome.unsent.clone()));
+            }
+            outcomes.push((name, outcome));
+        }
+
+        if confirmations < self.alarm_confirmations && retry_max > 0 && !unconfirmed.is_empty() {
+            warn!(
+                "Alarm only has {}/{} confirmations, retrying {} provider(s) in parallel after {}s!",
+                confirmations,
+                self.alarm_confirmations,
+                unconfirmed.len(),
+                retry_delay.as_secs()
+            );
+            tokio::time::sleep(jittered(retry_delay, self.retry_jitter)).await;
+
+            let retries = unconfirmed
+                .into_iter()
+                .map(|(name, provider, recipients)| async move {
+                    let retried = self
+                        .send_attempts(
+                            name,
+                            provider,
+                            alert,
+                            recipients,
+                            (retry_max - 1, retry_delay),
+                            None,
+                        )
+                        .await;
+                    (name, retried)
+                });
+            for (name, retried) in futures::future::join_all(retries).await {
+                if !retried.unsent.is_empty() {
+                    error!(
+                        "{} met retry limit with {} recipients left unsent for {:?}!",
+                        display_name(name).unwrap_or(name),
+                        retried.unsent.len(),
+                        alert
+                    );
+                }
+                if let Some((_, outcome)) = outcomes.iter_mut().find(|(n, _)| *n == name) {
+                    outcome.unsent = retried.unsent;
+                    outcome.rejected.extend(retried.rejected);
+                }
+            }
+        }
+        outcomes
+    }
+
+    /// Position of the provider in the priority list, with unlisted providers last.
+    fn priority_position(&self, name: &str) -> usize {
+        self.priority
+            .iter()
+            .position(|priority| priority == name)
+            .unwrap_or(self.priority.len())
+    }
+
+    /// Get the retry budget and delay for the alert level, falling back to the global values.
+    fn get_retry(&self, level: &AlertLevel) -> (u64, std::time::Duration) {
+        let Some(retry) = self.level_retry.get(level) else {
+            return (self.retry_max, self.retry_delay);
+        };
+        (
+            retry.retry_max.unwrap_or(self.retry_max),
+            retry
+                .retry_delay
+                .map_or(self.retry_delay, std::time::Duration::from_secs),
+        )
     }
 
     async fn send_with_retry(
@@ -109,45 +555,965 @@ impl CommunicationRegistry {
         name: &'static str,
         provider: &dyn CommunicationProvider,
         alert: &AlertInfo,
-    ) {
-        let mut recipients = provider.get_recipients(alert);
-        if recipients.is_empty() {
-            debug!(
-                "There are no recipients for '{}' with level {:?}",
-                name, alert.level
+        recipients: Vec<usize>,
+        deadline: Option<&DeliveryDeadline>,
+    ) -> (&'static str, SendOutcome) {
+        if !alert.is_alarm() {
+            self.wait_for_spacing(name).await;
+        }
+
+        let retry = self.get_retry(&alert.level);
+        let outcome = self
+            .send_attempts(name, provider, alert, recipients, retry, deadline)
+            .await;
+        if let Some(deadline) = deadline {
+            deadline.finish(name, outcome.is_delivered());
+        }
+
+        if !outcome.unsent.is_empty() {
+            error!(
+                "{} met retry limit with {} recipients left unsent for {:?}!",
+                display_name(name).unwrap_or(name),
+                outcome.unsent.len(),
+                alert
             );
+        }
+        (name, outcome)
+    }
+
+    /// Wait until the provider's last send is at least the spacing ago. The lock is held
+    /// while waiting, so concurrent broadcasts to one provider queue up behind each other.
+    async fn wait_for_spacing(&self, name: &'static str) {
+        if self.send_spacing.is_zero() {
+            return;
+        }
+        let Some(last_sent) = self.last_sent.get(name) else {
             return;
+        };
+
+        let mut last_sent = last_sent.lock().await;
+        if let Some(last) = *last_sent {
+            let ready = last + self.send_spacing;
+            if ready > tokio::time::Instant::now() {
+                debug!("Spacing send to '{name}' after its previous send.");
+                tokio::time::sleep_until(ready).await;
+            }
         }
+        *last_sent = Some(tokio::time::Instant::now());
+    }
 
-        for attempt in 1..=self.retry_max + 1 {
+    /// Send to the recipients, only retrying transient failures.
+    async fn send_attempts(
+        &self,
+        name: &'static str,
+        provider: &dyn CommunicationProvider,
+        alert: &AlertInfo,
+        mut recipients: Vec<usize>,
+        (retry_max, retry_delay): (u64, std::time::Duration),
+        deadline: Option<&DeliveryDeadline>,
+    ) -> SendOutcome {
+        let display = display_name(name).unwrap_or(name);
+        let mut rejected = Vec::new();
+        for attempt in 1..=retry_max + 1 {
+            if attempt > 1 && deadline.is_some_and(|deadline| !deadline.should_retry(name)) {
+                warn!("Alarm deadline passed, '{display}' is leaving retries to a higher priority provider!");
+                break;
+            }
             match provider.send(alert, &recipients).await {
                 CommunicationSendResultKind::Completed { failed } if failed.is_empty() => {
-                    debug!("Sent to all recipients of '{name}' in {attempt} attempt(s)!");
-                    return;
+                    debug!("Sent to all recipients of '{display}' in {attempt} attempt(s)!");
+                    return SendOutcome {
+                        unsent: Vec::new(),
+                        rejected,
+                    };
                 }
                 CommunicationSendResultKind::Completed { failed } => {
+                    recipients = Vec::with_capacity(failed.len());
+                    for (index, e) in failed {
+                        if e.is_retryable() {
+                            recipients.push(index);
+                        } else {
+                            error!("'{display}' will not retry recipient #{index}: {e}");
+                            rejected.push(index);
+                        }
+                    }
+                    if recipients.is_empty() || attempt > retry_max {
+                        break;
+                    }
+
                     debug!(
                         "Attempt #{} for '{}': {} recipients failed, retrying after {}s",
                         attempt,
-                        name,
-                        failed.len(),
-                        self.retry_delay.as_secs()
+                        display,
+                        recipients.len(),
+                        retry_delay.as_secs()
                     );
-                    recipients = failed;
-                    tokio::time::sleep(self.retry_delay).await;
+                    tokio::time::sleep(jittered(retry_delay, self.retry_jitter)).await;
                 }
                 CommunicationSendResultKind::Unavailable { reason } => {
-                    error!("CommunicationProvider '{name}' is unavailable: {reason}");
-                    return;
+                    error!("CommunicationProvider '{display}' is unavailable: {reason}");
+                    break;
                 }
             }
         }
+        SendOutcome {
+            unsent: recipients,
+            rejected,
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    /// A theme overriding every part of Critical, with the other levels left as default.
+    pub(crate) const THEME: &str = r##"
+        [theme.Critical]
+        color = "#123456"
+        emoji = "🔥"
+        label = "CRIT"
+    "##;
+
+    /// What a stub provider was asked to send, shared with the test.
+    #[derive(Default)]
+    pub(crate) struct StubState {
+        sent: Mutex<Vec<(String, AlertLevel, Vec<usize>)>>,
+        timestamps: Mutex<Vec<Option<u64>>>,
+        active: AtomicUsize,
+        peak: AtomicUsize,
+    }
+    impl StubState {
+        pub fn sends(&self) -> usize {
+            self.sent.lock().unwrap().len()
+        }
+
+        pub fn messages(&self) -> Vec<(String, AlertLevel)> {
+            self.sent
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(message, level, _)| (message.clone(), level.clone()))
+                .collect()
+        }
+
+        /// The timestamp of each alert sent.
+        pub fn timestamps(&self) -> Vec<Option<u64>> {
+            self.timestamps.lock().unwrap().clone()
+        }
+
+        /// Most sends that were in flight at once.
+        pub fn peak(&self) -> usize {
+            self.peak.load(Ordering::SeqCst)
+        }
+    }
+
+    /// Provider that records its sends, failing recipients per attempt as scripted.
+    pub(crate) struct StubProvider {
+        recipients: Vec<CommunicationRecipient>,
+        failures: Mutex<std::collections::VecDeque<Vec<(usize, SendError)>>>,
+        delay: std::time::Duration,
+        unsupported: bool,
+        state: Arc<StubState>,
+    }
+    impl StubProvider {
+        /// A provider with a recipient at each of the levels.
+        pub fn new(levels: &[AlertLevel]) -> Self {
+            Self {
+                recipients: levels
+                    .iter()
+                    .enumerate()
+                    .map(|(index, level)| CommunicationRecipient {
+                        target: format!("recipient-{index}"),
+                        level: u8::from(level),
+                    })
+                    .collect(),
+                failures: Mutex::new(std::collections::VecDeque::new()),
+                delay: std::time::Duration::ZERO,
+                unsupported: false,
+                state: Arc::new(StubState::default()),
+            }
+        }
+
+        /// Fail the given recipients on each attempt in turn, then succeed.
+        pub fn failing(self, failures: Vec<Vec<(usize, SendError)>>) -> Self {
+            *self.failures.lock().unwrap() = failures.into();
+            self
+        }
+
+        pub fn delayed(mut self, delay: std::time::Duration) -> Self {
+            self.delay = delay;
+            self
+        }
+
+        /// Report every alert as unsupported.
+        pub fn unsupported(mut self) -> Self {
+            self.unsupported = true;
+            self
+        }
+
+        pub fn state(&self) -> Arc<StubState> {
+            self.state.clone()
+        }
+
+        pub fn boxed(self, name: &'static str) -> (&'static str, Box<dyn CommunicationProvider>) {
+            (name, Box::new(self))
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl CommunicationProvider for StubProvider {
+        fn name() -> &'static str {
+            "stub"
+        }
+
+        fn description() -> &'static str {
+            "Records alerts for tests"
+        }
+
+        fn required_config() -> &'static [&'static str] {
+            &[]
+        }
+
+        fn from_config(_config: &CommunicationsConfig) -> anyhow::Result<Self> {
+            anyhow::bail!("StubProvider can't be configured!")
+        }
+
+        fn get_all_recipients(&self) -> &Vec<CommunicationRecipient> {
+            &self.recipients
+        }
+
+        fn supports(&self, _alert: &AlertInfo) -> bool {
+            !self.unsupported
+        }
+
+        async fn send(
+            &self,
+            alert: &AlertInfo,
+            recipients: &[usize],
+        ) -> CommunicationSendResultKind {
+            let active = self.state.active.fetch_add(1, Ordering::SeqCst) + 1;
+            self.state.peak.fetch_max(active, Ordering::SeqCst);
+            self.state.sent.lock().unwrap().push((
+                alert.message.clone(),
+                alert.level.clone(),
+                recipients.to_vec(),
+            ));
+            self.state.timestamps.lock().unwrap().push(alert.timestamp);
+            if !self.delay.is_zero() {
+                tokio::time::sleep(self.delay).await;
+            }
+            self.state.active.fetch_sub(1, Ordering::SeqCst);
+
+            let failed = self
+                .failures
+                .lock()
+                .unwrap()
+                .pop_front()
+                .unwrap_or_default();
+            CommunicationSendResultKind::Completed { failed }
+        }
+    }
+
+    /// A registry of the stub providers with an otherwise default config.
+    pub(crate) fn registry(
+        providers: Vec<(&'static str, Box<dyn CommunicationProvider>)>,
+    ) -> CommunicationRegistry {
+        CommunicationRegistry::from_providers(&CommunicationsConfig::default(), providers)
+    }
 
-        error!(
-            "{} met retry limit with {} recipients left unsent for {:?}!",
+    static FLAKY_ATTEMPTS: AtomicUsize = AtomicUsize::new(0);
+
+    fn factory(
+        name: &'static str,
+        create: fn(&CommunicationsConfig) -> anyhow::Result<Box<dyn CommunicationProvider>>,
+    ) -> ProviderFactory {
+        ProviderFactory {
             name,
-            recipients.len(),
-            alert
+            description: "Test provider",
+            required_config: &[],
+            create,
+        }
+    }
+
+    fn stub(_: &CommunicationsConfig) -> anyhow::Result<Box<dyn CommunicationProvider>> {
+        Ok(Box::new(StubProvider::new(&[AlertLevel::Warning])))
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retries_providers_that_fail_to_initialize() {
+        let factories = [
+            factory("stable", stub),
+            factory("flaky", |config| {
+                if FLAKY_ATTEMPTS.fetch_add(1, Ordering::SeqCst) < 3 {
+                    anyhow::bail!("Network is unreachable");
+                }
+                stub(config)
+            }),
+        ];
+        let started = tokio::time::Instant::now();
+        let registry = CommunicationRegistry::create(&CommunicationsConfig::default(), &factories)
+            .await
+            .unwrap();
+
+        assert_eq!(FLAKY_ATTEMPTS.load(Ordering::SeqCst), 4);
+        assert!(registry.providers.contains_key("stable"));
+        assert!(registry.providers.contains_key("flaky"));
+        // Retried after 1s, 2s and 4s.
+        assert_eq!(started.elapsed(), std::time::Duration::from_secs(7));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn keeps_the_providers_that_initialized_after_the_timeout() {
+        let factories = [
+            factory("stable", stub),
+            factory("broken", |_| anyhow::bail!("Invalid credentials")),
+        ];
+        let config: CommunicationsConfig = toml::from_str("init_timeout = 10").unwrap();
+        let registry = CommunicationRegistry::create(&config, &factories)
+            .await
+            .unwrap();
+
+        assert!(registry.providers.contains_key("stable"));
+        assert!(!registry.providers.contains_key("broken"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn fails_when_no_provider_initializes() {
+        let factories = [factory("broken", |_| anyhow::bail!("Invalid credentials"))];
+        let config: CommunicationsConfig = toml::from_str("init_timeout = 10").unwrap();
+        assert!(CommunicationRegistry::create(&config, &factories)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn client_build_failures_disable_the_provider() {
+        let factories = [
+            factory("stable", stub),
+            factory("unbuildable", |config| {
+                // No TLS version is left to support, so the client fails to build.
+                anyhow::Context::context(
+                    crate::outbound::client_builder()
+                        .min_tls_version(reqwest::tls::Version::TLS_1_3)
+                        .max_tls_version(reqwest::tls::Version::TLS_1_2)
+                        .build(),
+                    "Failed to build HTTP client!",
+                )?;
+                stub(config)
+            }),
+        ];
+        let config: CommunicationsConfig = toml::from_str("init_timeout = 10").unwrap();
+        let registry = CommunicationRegistry::create(&config, &factories)
+            .await
+            .unwrap();
+
+        assert!(registry.providers.contains_key("stable"));
+        assert!(!registry.providers.contains_key("unbuildable"));
+    }
+
+    #[tokio::test]
+    async fn only_creates_configured_providers() {
+        let factories = [
+            factory("stable", stub),
+            ProviderFactory {
+                required_config: &["communications.stdout"],
+                ..factory("unconfigured", |_| {
+                    panic!("Unconfigured provider was created!")
+                })
+            },
+        ];
+        let registry = CommunicationRegistry::create(&CommunicationsConfig::default(), &factories)
+            .await
+            .unwrap();
+        assert_eq!(registry.providers.len(), 1);
+    }
+
+    /// Providers are only created once their required keys are set, so every key must
+    /// be a field that's serialized when the provider is configured.
+    #[test]
+    fn example_config_sets_every_required_key() {
+        let config: crate::config::AppConfig =
+            toml::from_str(include_str!("../../config.example.toml")).unwrap();
+        let serialized = serde_json::json!({ "communications": config.communications });
+        for factory in factories() {
+            assert!(
+                factory.is_configured(&serialized),
+                "'{}' requires config keys that aren't serialized: {:?}",
+                factory.name,
+                factory.required_config
+            );
+        }
+    }
+
+    fn registry_with(
+        config: &str,
+        providers: Vec<(&'static str, Box<dyn CommunicationProvider>)>,
+    ) -> CommunicationRegistry {
+        CommunicationRegistry::from_providers(&toml::from_str(config).unwrap(), providers)
+    }
+
+    /// Three alarm providers that each take a second to send, in priority order.
+    /// The first one is given, so it can be scripted to fail.
+    fn alarm_providers(
+        strategy: &str,
+        confirmations: usize,
+        first: StubProvider,
+    ) -> (CommunicationRegistry, [Arc<StubState>; 3]) {
+        let second = StubProvider::new(&[AlertLevel::Alarm]);
+        let third = StubProvider::new(&[AlertLevel::Alarm]);
+        let states = [first.state(), second.state(), third.state()];
+        let delay = std::time::Duration::from_secs(1);
+        let registry = registry_with(
+            &format!(
+                r#"
+                alarm_strategy = "{strategy}"
+                alarm_confirmations = {confirmations}
+                priority = ["first", "second", "third"]
+                retry_max = 3
+                retry_delay = 10
+                "#
+            ),
+            vec![
+                first.delayed(delay).boxed("first"),
+                second.delayed(delay).boxed("second"),
+                third.delayed(delay).boxed("third"),
+            ],
+        );
+        (registry, states)
+    }
+
+    fn sends(states: &[Arc<StubState>; 3]) -> [usize; 3] {
+        states.each_ref().map(|state| state.sends())
+    }
+
+    async fn sends_at(
+        states: &[Arc<StubState>; 3],
+        started: tokio::time::Instant,
+        secs: f32,
+    ) -> [usize; 3] {
+        tokio::time::sleep_until(started + std::time::Duration::from_secs_f32(secs)).await;
+        sends(states)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn parallel_alarms_start_every_provider_at_once() {
+        let (registry, states) =
+            alarm_providers("parallel", 1, StubProvider::new(&[AlertLevel::Alarm]));
+        let started = tokio::time::Instant::now();
+        let broadcast = async {
+            registry.broadcast(&alert(AlertLevel::Alarm)).await;
+        };
+        let (_, sent) = tokio::join!(broadcast, sends_at(&states, started, 0.5));
+
+        assert_eq!(sent, [1, 1, 1]);
+        assert_eq!(sends(&states), [1, 1, 1]);
+    }
+
+    /// Three providers with a 100ms stagger, registered out of their priority order.
+    fn staggered_providers() -> (CommunicationRegistry, [Arc<StubState>; 3]) {
+        let providers = [(); 3].map(|_| StubProvider::new(&[AlertLevel::Warning]));
+        let states = providers.each_ref().map(StubProvider::state);
+        let [first, second, third] = providers;
+        let registry = registry_with(
+            r#"
+            alarm_stagger = 100
+            priority = ["first", "second", "third"]
+            "#,
+            vec![
+                third.boxed("third"),
+                first.boxed("first"),
+                second.boxed("second"),
+            ],
+        );
+        (registry, states)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn staggered_alarms_start_higher_priority_providers_first() {
+        let (registry, states) = staggered_providers();
+        let started = tokio::time::Instant::now();
+        let broadcast = async {
+            registry.broadcast(&alert(AlertLevel::Alarm)).await;
+        };
+        let (_, sent) = tokio::join!(broadcast, async {
+            [
+                sends_at(&states, started, 0.05).await,
+                sends_at(&states, started, 0.15).await,
+                sends_at(&states, started, 0.25).await,
+            ]
+        });
+
+        assert_eq!(sent, [[1, 0, 0], [1, 1, 0], [1, 1, 1]]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn only_alarms_are_staggered() {
+        let (registry, states) = staggered_providers();
+        let started = tokio::time::Instant::now();
+        let broadcast = async {
+            registry.broadcast(&alert(AlertLevel::Critical)).await;
+        };
+        let (_, sent) = tokio::join!(broadcast, sends_at(&states, started, 0.0));
+
+        assert_eq!(sent, [1, 1, 1]);
+    }
+
+    /// Broadcast each level in turn to two providers with a 500ms send spacing, returning
+    /// how long after starting each was sent, along with the other provider's sends.
+    async fn spaced_sends(levels: &[AlertLevel]) -> (Vec<std::time::Duration>, usize) {
+        let spaced = StubProvider::new(&[AlertLevel::Info]);
+        let other = StubProvider::new(&[AlertLevel::Info]);
+        let other_state = other.state();
+        let registry = registry_with(
+            "send_spacing = 500",
+            vec![spaced.boxed("spaced"), other.boxed("other")],
         );
+
+        let started = tokio::time::Instant::now();
+        let mut elapsed = Vec::new();
+        for level in levels {
+            registry.broadcast(&alert(level.clone())).await;
+            elapsed.push(started.elapsed());
+        }
+        (elapsed, other_state.sends())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn back_to_back_sends_are_spaced() {
+        let millis = std::time::Duration::from_millis;
+        let (elapsed, other_sends) =
+            spaced_sends(&[AlertLevel::Info, AlertLevel::Warning, AlertLevel::Critical]).await;
+        assert_eq!(elapsed, [millis(0), millis(500), millis(1000)]);
+        assert_eq!(other_sends, 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn alarms_bypass_send_spacing() {
+        let millis = std::time::Duration::from_millis;
+        let (elapsed, _) =
+            spaced_sends(&[AlertLevel::Warning, AlertLevel::Alarm, AlertLevel::Alarm]).await;
+        assert_eq!(elapsed, [millis(0); 3]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn concurrent_sends_queue_behind_each_other() {
+        let provider = StubProvider::new(&[AlertLevel::Info]);
+        let state = provider.state();
+        let registry = registry_with("send_spacing = 500", vec![provider.boxed("spaced")]);
+        let started = tokio::time::Instant::now();
+
+        let alert = alert(AlertLevel::Warning);
+        let broadcasts = futures::future::join_all((0..3).map(|_| registry.broadcast(&alert)));
+        let (_, sent) = tokio::join!(broadcasts, async {
+            let at =
+                |secs| tokio::time::sleep_until(started + std::time::Duration::from_secs_f32(secs));
+            at(0.25).await;
+            let first = state.sends();
+            at(0.75).await;
+            let second = state.sends();
+            at(1.25).await;
+            [first, second, state.sends()]
+        });
+        assert_eq!(sent, [1, 2, 3]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn sequential_alarms_stop_at_the_first_confirmation() {
+        let (registry, states) =
+            alarm_providers("sequential", 1, StubProvider::new(&[AlertLevel::Alarm]));
+        registry.broadcast(&alert(AlertLevel::Alarm)).await;
+
+        assert_eq!(sends(&states), [1, 0, 0]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn sequential_alarms_move_on_in_priority_order() {
+        let first = StubProvider::new(&[AlertLevel::Alarm])
+            .failing(vec![vec![(0, SendError::Permanent("unauthorized".into()))]]);
+        let (registry, states) = alarm_providers("sequential", 1, first);
+        let started = tokio::time::Instant::now();
+        let broadcast = async {
+            registry.broadcast(&alert(AlertLevel::Alarm)).await;
+        };
+        let (_, sent) = tokio::join!(broadcast, async {
+            [
+                sends_at(&states, started, 0.5).await,
+                sends_at(&states, started, 1.5).await,
+            ]
+        });
+
+        assert_eq!(sent, [[1, 0, 0], [1, 1, 0]]);
+        assert_eq!(sends(&states), [1, 1, 0]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn sequential_alarms_hand_over_the_remaining_budget() {
+        let first = StubProvider::new(&[AlertLevel::Alarm]).failing(vec![
+            vec![(
+                0,
+                SendError::Transient("timeout".into())
+            )];
+            10
+        ]);
+        let (registry, states) = alarm_providers("sequential", 3, first);
+        let started = tokio::time::Instant::now();
+        let broadcast = async { registry.broadcast(&alert(AlertLevel::Alarm)).await };
+        let (outcomes, sent) = tokio::join!(broadcast, async {
+            [
+                sends_at(&states, started, 12.5).await,
+                sends_at(&states, started, 13.5).await,
+            ]
+        });
+
+        // Only retried once the delay after the sequence passes, rather than immediately.
+        assert_eq!(sent, [[1, 1, 1], [2, 1, 1]]);
+        // One attempt in sequence, then the remaining three retries.
+        assert_eq!(sends(&states), [4, 1, 1]);
+        assert_eq!(outcomes[0].0, "first");
+        assert_eq!(outcomes[0].1.unsent, vec![0]);
+    }
+
+    fn transient_failures() -> Vec<Vec<(usize, SendError)>> {
+        vec![vec![(0, SendError::Transient("timeout".into()))]; 10]
+    }
+
+    /// Parallel alarm providers with a 15s deadline, where all but the given first always
+    /// fail transiently.
+    fn unconfirmed_alarm_providers(
+        first: StubProvider,
+    ) -> (CommunicationRegistry, [Arc<StubState>; 3]) {
+        let second = StubProvider::new(&[AlertLevel::Alarm]).failing(transient_failures());
+        let third = StubProvider::new(&[AlertLevel::Alarm]).failing(transient_failures());
+        let states = [first.state(), second.state(), third.state()];
+        let registry = registry_with(
+            r#"
+            alarm_deadline = 15
+            priority = ["first", "second", "third"]
+            retry_max = 3
+            retry_delay = 10
+            "#,
+            vec![
+                first.boxed("first"),
+                second.boxed("second"),
+                third.boxed("third"),
+            ],
+        );
+        (registry, states)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn unconfirmed_alarms_focus_on_the_priority_provider_after_the_deadline() {
+        let first = StubProvider::new(&[AlertLevel::Alarm]).failing(transient_failures());
+        let (registry, states) = unconfirmed_alarm_providers(first);
+        let started = tokio::time::Instant::now();
+        let broadcast = async {
+            registry.broadcast(&alert(AlertLevel::Alarm)).await;
+        };
+        let (_, sent) = tokio::join!(broadcast, sends_at(&states, started, 15.0));
+
+        // Everyone retries before the deadline, then only the first keeps going.
+        assert_eq!(sent, [2, 2, 2]);
+        assert_eq!(sends(&states), [4, 2, 2]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn the_next_priority_provider_takes_over_once_the_first_gives_up() {
+        let first = StubProvider::new(&[AlertLevel::Alarm])
+            .failing(vec![vec![(0, SendError::Permanent("unauthorized".into()))]]);
+        let (registry, states) = unconfirmed_alarm_providers(first);
+        registry.broadcast(&alert(AlertLevel::Alarm)).await;
+
+        assert_eq!(sends(&states), [1, 4, 2]);
+    }
+
+    #[test]
+    fn jitter_spreads_retry_delays_within_its_fraction() {
+        let delay = std::time::Duration::from_secs(10);
+        assert_eq!(jittered(delay, 0.0), delay);
+
+        let delays: Vec<_> = (0..100).map(|_| jittered(delay, 0.2)).collect();
+        assert!(delays.iter().all(|jittered| {
+            (std::time::Duration::from_secs(8)..=std::time::Duration::from_secs(12))
+                .contains(jittered)
+        }));
+        assert!(delays.iter().any(|jittered| *jittered != delays[0]));
+    }
+
+    /// Broadcast at the level to a provider that always fails transiently, returning
+    /// how many attempts were made and how long they took.
+    async fn retry_budget(level: AlertLevel) -> (usize, std::time::Duration) {
+        let provider = StubProvider::new(&[AlertLevel::Info]).failing(vec![
+            vec![(
+                0,
+                SendError::Transient("timeout".into())
+            )];
+            10
+        ]);
+        let state = provider.state();
+        let registry = registry_with(
+            r#"
+            alarm_strategy = "parallel"
+            retry_max = 2
+            retry_delay = 10
+
+            [level_retry.Info]
+            retry_max = 1
+            retry_delay = 5
+
+            [level_retry.Critical]
+            retry_max = 4
+
+            [level_retry.Alarm]
+            retry_delay = 1
+            "#,
+            vec![provider.boxed("stub")],
+        );
+
+        let started = tokio::time::Instant::now();
+        let outcomes = registry.broadcast(&alert(level)).await;
+        assert_eq!(outcomes[0].1.unsent, vec![0]);
+        (state.sends(), started.elapsed())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn each_level_uses_its_retry_budget() {
+        let secs = std::time::Duration::from_secs;
+        assert_eq!(retry_budget(AlertLevel::Info).await, (2, secs(5)));
+        // Without an override, the global budget is used.
+        assert_eq!(retry_budget(AlertLevel::Warning).await, (3, secs(20)));
+        // Overrides only replace the values they set.
+        assert_eq!(retry_budget(AlertLevel::Critical).await, (5, secs(40)));
+        assert_eq!(retry_budget(AlertLevel::Alarm).await, (3, secs(2)));
+    }
+
+    /// Send a Warning to the stub's one recipient, retrying with the default budget.
+    async fn send_attempts(provider: StubProvider) -> (SendOutcome, usize) {
+        let state = provider.state();
+        let registry = registry(vec![]);
+        let outcome = registry
+            .send_attempts(
+                "stub",
+                &provider,
+                &alert(AlertLevel::Warning),
+                vec![0],
+                (2, std::time::Duration::from_secs(1)),
+                None,
+            )
+            .await;
+        (outcome, state.sends())
+    }
+
+    fn alert(level: AlertLevel) -> AlertInfo {
+        AlertInfo::new("test".to_string(), "Test alert".to_string(), level).unwrap()
+    }
+
+    #[tokio::test]
+    async fn skip_providers_still_reaches_the_rest() {
+        let sms = StubProvider::new(&[AlertLevel::Warning]);
+        let pushover = StubProvider::new(&[AlertLevel::Warning]);
+        let (sms_state, pushover_state) = (sms.state(), pushover.state());
+        let registry = registry(vec![sms.boxed("sms"), pushover.boxed("pushover")]);
+
+        let mut alert = alert(AlertLevel::Warning);
+        alert.metadata.skip_providers = Some(["sms".to_string()].into());
+        let outcomes = registry.broadcast(&alert).await;
+
+        assert_eq!(sms_state.sends(), 0);
+        assert_eq!(pushover_state.sends(), 1);
+        assert_eq!(outcomes.len(), 1);
+        assert_eq!(outcomes[0].0, "pushover");
+        assert!(outcomes[0].1.is_delivered());
+    }
+
+    #[tokio::test]
+    async fn only_providers_limits_the_broadcast() {
+        let sms = StubProvider::new(&[AlertLevel::Warning]);
+        let pushover = StubProvider::new(&[AlertLevel::Warning]);
+        let (sms_state, pushover_state) = (sms.state(), pushover.state());
+        let registry = registry(vec![sms.boxed("sms"), pushover.boxed("pushover")]);
+
+        let mut alert = alert(AlertLevel::Warning);
+        alert.metadata.only_providers = Some(["sms".to_string()].into());
+        registry.broadcast(&alert).await;
+
+        assert_eq!(sms_state.sends(), 1);
+        assert_eq!(pushover_state.sends(), 0);
+    }
+
+    #[tokio::test]
+    async fn info_alerts_only_reach_the_info_providers() {
+        let journal = StubProvider::new(&[AlertLevel::Info]);
+        let pushover = StubProvider::new(&[AlertLevel::Info]);
+        let (journal_state, pushover_state) = (journal.state(), pushover.state());
+        let registry = registry_with(
+            r#"info_providers = ["journal"]"#,
+            vec![journal.boxed("journal"), pushover.boxed("pushover")],
+        );
+
+        registry.broadcast(&alert(AlertLevel::Info)).await;
+        assert_eq!(journal_state.sends(), 1);
+        assert_eq!(pushover_state.sends(), 0);
+
+        // Higher levels still reach every provider.
+        registry.broadcast(&alert(AlertLevel::Warning)).await;
+        assert_eq!(journal_state.sends(), 2);
+        assert_eq!(pushover_state.sends(), 1);
+    }
+
+    /// A pushover provider for alarms only, and a journal fallback for criticals.
+    fn fallback_providers() -> (CommunicationRegistry, Arc<StubState>, Arc<StubState>) {
+        let pushover = StubProvider::new(&[AlertLevel::Alarm]);
+        let journal = StubProvider::new(&[AlertLevel::Critical]);
+        let (pushover_state, journal_state) = (pushover.state(), journal.state());
+        let registry = registry_with(
+            r#"fallback_providers = ["journal"]"#,
+            vec![pushover.boxed("pushover"), journal.boxed("journal")],
+        );
+        (registry, pushover_state, journal_state)
+    }
+
+    #[tokio::test]
+    async fn alerts_matching_no_providers_use_the_fallback() {
+        let (registry, pushover_state, journal_state) = fallback_providers();
+        let outcomes = registry.broadcast(&alert(AlertLevel::Info)).await;
+
+        assert_eq!(pushover_state.sends(), 0);
+        assert_eq!(journal_state.sends(), 1);
+        // Every fallback recipient is sent to, regardless of its level.
+        assert_eq!(journal_state.sent.lock().unwrap()[0].2, [0]);
+        assert_eq!(outcomes.len(), 1);
+        assert_eq!(outcomes[0].0, "journal");
+    }
+
+    #[tokio::test]
+    async fn the_fallback_is_unused_while_a_provider_matches() {
+        let (registry, pushover_state, journal_state) = fallback_providers();
+        registry.broadcast(&alert(AlertLevel::Alarm)).await;
+
+        assert_eq!(pushover_state.sends(), 1);
+        // Only sent to since its own level matches, not as the fallback.
+        assert_eq!(journal_state.sends(), 1);
+    }
+
+    #[tokio::test]
+    async fn aliased_providers_are_still_routed_by_their_internal_name() {
+        crate::alerts::tests::set_display_names();
+        assert_eq!(display_name("aliased_sms"), Some("SMS"));
+        let sms = StubProvider::new(&[AlertLevel::Warning]);
+        let pushover = StubProvider::new(&[AlertLevel::Warning]);
+        let (sms_state, pushover_state) = (sms.state(), pushover.state());
+        let registry = registry(vec![sms.boxed("aliased_sms"), pushover.boxed("pushover")]);
+
+        let mut alert = alert(AlertLevel::Warning);
+        alert.metadata.only_providers = Some(["aliased_sms".to_string()].into());
+        let outcomes = registry.broadcast(&alert).await;
+
+        assert_eq!(sms_state.sends(), 1);
+        assert_eq!(pushover_state.sends(), 0);
+        assert_eq!(outcomes[0].0, "aliased_sms");
+    }
+
+    #[tokio::test]
+    async fn unsupported_providers_are_skipped_without_failing() {
+        let sms = StubProvider::new(&[AlertLevel::Warning]).unsupported();
+        let pushover = StubProvider::new(&[AlertLevel::Warning]);
+        let (sms_state, pushover_state) = (sms.state(), pushover.state());
+        let registry = registry(vec![sms.boxed("sms"), pushover.boxed("pushover")]);
+
+        let outcomes = registry.broadcast(&alert(AlertLevel::Warning)).await;
+
+        assert_eq!(sms_state.sends(), 0);
+        assert_eq!(pushover_state.sends(), 1);
+        assert_eq!(outcomes.len(), 1);
+        assert_eq!(outcomes[0].0, "pushover");
+        assert!(outcomes[0].1.is_delivered());
+    }
+
+    #[tokio::test]
+    async fn recipients_below_the_alert_level_are_skipped() {
+        let pushover = StubProvider::new(&[AlertLevel::Warning, AlertLevel::Alarm]);
+        let state = pushover.state();
+        let registry = registry(vec![pushover.boxed("pushover")]);
+
+        registry.broadcast(&alert(AlertLevel::Critical)).await;
+
+        let sent = state.sent.lock().unwrap();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].2, vec![0]);
+    }
+
+    #[test]
+    fn classifies_http_statuses() {
+        for status in [429, 500, 502, 503, 599] {
+            assert!(
+                matches!(
+                    SendError::from_status(status, String::new()),
+                    SendError::Transient(_)
+                ),
+                "{status} should be transient"
+            );
+        }
+        for status in [401, 403] {
+            assert!(
+                matches!(
+                    SendError::from_status(status, String::new()),
+                    SendError::Permanent(_)
+                ),
+                "{status} should be permanent"
+            );
+        }
+        for status in [400, 404, 410, 413, 422] {
+            assert!(
+                matches!(
+                    SendError::from_status(status, String::new()),
+                    SendError::Invalid(_)
+                ),
+                "{status} should be invalid"
+            );
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retries_transient_failures() {
+        let provider = StubProvider::new(&[AlertLevel::Warning]).failing(vec![
+            vec![(0, SendError::Transient("timeout".to_string()))],
+            vec![(0, SendError::Transient("timeout".to_string()))],
+        ]);
+        let (outcome, sends) = send_attempts(provider).await;
+        assert_eq!(sends, 3);
+        assert!(outcome.is_delivered());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn gives_up_after_the_retry_budget() {
+        let provider = StubProvider::new(&[AlertLevel::Warning]).failing(vec![
+            vec![(
+                0,
+                SendError::Transient("timeout".to_string())
+            )];
+            5
+        ]);
+        let (outcome, sends) = send_attempts(provider).await;
+        assert_eq!(sends, 3);
+        assert_eq!(outcome.unsent, vec![0]);
+        assert!(outcome.rejected.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn never_retries_permanent_or_invalid_failures() {
+        for error in [
+            SendError::Permanent("unauthorized".to_string()),
+            SendError::Invalid("unknown recipient".to_string()),
+        ] {
+            let provider =
+                StubProvider::new(&[AlertLevel::Warning]).failing(vec![vec![(0, error)]]);
+            let (outcome, sends) = send_attempts(provider).await;
+            assert_eq!(sends, 1);
+            assert!(outcome.unsent.is_empty());
+            assert_eq!(outcome.rejected, vec![0]);
+        }
     }
 }
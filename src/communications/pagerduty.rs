@@ -0,0 +1,281 @@
+use crate::alerts::{AlertInfo, AlertLevel};
+use crate::communications::theme::Theme;
+use crate::communications::{CommunicationProvider, CommunicationSendResultKind, SendError};
+use crate::config::{CommunicationRecipient, CommunicationsConfig};
+use crate::outbound::{client_builder, ensure_host_allowed, send_with_reset_retry};
+use anyhow::Context;
+
+/*
+   PagerDuty Communication Provider.
+   Sends Events API v2 events with a dedup key derived from the alert source and
+   subject, so repeated alerts update one incident and recovery alerts resolve it,
+   without one target's recovery resolving another target's incident.
+   https://developer.pagerduty.com/docs/events-api-v2/trigger-events/
+*/
+
+const PAGERDUTY_URL: &str = "https://events.pagerduty.com/v2/enqueue";
+const PAGERDUTY_SUMMARY_MAX: usize = 1024;
+
+#[derive(serde::Serialize)]
+struct PagerDutyPayload {
+    pub summary: String,
+    pub source: String,
+    pub severity: &'static str,
+}
+
+#[derive(serde::Serialize)]
+struct PagerDutyEvent<'a> {
+    pub routing_key: &'a str,
+    pub event_action: &'static str,
+    pub dedup_key: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payload: Option<PagerDutyPayload>,
+}
+
+pub(crate) struct PagerDutyCommunicationProvider {
+    client: reqwest::Client,
+    url: String,
+    routing_key: String,
+    recipients: Vec<CommunicationRecipient>,
+    theme: Theme,
+}
+impl PagerDutyCommunicationProvider {
+    fn create_event(&self, alert: &AlertInfo) -> PagerDutyEvent<'_> {
+        let dedup_key = match &alert.metadata.subject {
+            Some(subject) => format!("sentinel-{}-{subject}", alert.source),
+            None => format!("sentinel-{}", alert.source),
+        };
+        if alert.metadata.recovery {
+            return PagerDutyEvent {
+                routing_key: &self.routing_key,
+                event_action: "resolve",
+                dedup_key,
+                payload: None,
+            };
+        }
+
+        let theme = self.theme.get(&alert.level);
+        PagerDutyEvent {
+            routing_key: &self.routing_key,
+            event_action: "trigger",
+            dedup_key,
+            payload: Some(PagerDutyPayload {
+                summary: format!("{} {} - {alert}", theme.emoji, theme.label)
+                    .chars()
+                    .take(PAGERDUTY_SUMMARY_MAX)
+                    .collect(),
+                source: alert.source.clone(),
+                severity: match alert.level {
+                    AlertLevel::Info => "info",
+                    AlertLevel::Warning => "warning",
+                    AlertLevel::Critical => "error",
+                    AlertLevel::Alarm => "critical",
+                },
+            }),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl CommunicationProvider for PagerDutyCommunicationProvider {
+    fn name() -> &'static str {
+        "pagerduty"
+    }
+
+    fn description() -> &'static str {
+        "Triggers and resolves PagerDuty incidents"
+    }
+
+    fn required_config() -> &'static [&'static str] {
+        &["communications.pagerduty.routing_key"]
+    }
+
+    fn from_config(config: &CommunicationsConfig) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        let theme = Theme::from_config(&config.theme);
+        let config = match &config.pagerduty {
+            Some(config) => config,
+            None => anyhow::bail!("Missing any PagerDuty config!"),
+        };
+
+        Ok(Self {
+            client: client_builder()
+                .timeout(std::time::Duration::from_secs(config.timeout))
+                .build()
+                .context("Failed to build PagerDuty HTTP client!")?,
+            url: PAGERDUTY_URL.to_string(),
+            routing_key: config.routing_key.clone(),
+            recipients: vec![CommunicationRecipient {
+                target: "pagerduty".to_string(),
+                level: config.level,
+            }],
+            theme,
+        })
+    }
+
+    #[inline]
+    fn get_all_recipients(&self) -> &Vec<CommunicationRecipient> {
+        &self.recipients
+    }
+
+    /// Recovery alerts are always sent regardless of level, since a recovery can be
+    /// below the trigger level. Resolving an incident that was never triggered is a no-op.
+    fn get_recipients(&self, alert: &AlertInfo) -> Vec<usize> {
+        let level_u8 = u8::from(&alert.level);
+        self.recipients
+            .iter()
+            .enumerate()
+            .filter(|(_, recipient)| alert.metadata.recovery || level_u8 >= recipient.level)
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    async fn send(&self, alert: &AlertInfo, recipients: &[usize]) -> CommunicationSendResultKind {
+        if let Err(e) = ensure_host_allowed(&self.url) {
+            return CommunicationSendResultKind::Unavailable {
+                reason: e.to_string(),
+            };
+        }
+
+        let event = self.create_event(alert);
+        let result = send_with_reset_retry(self.client.post(&self.url).json(&event)).await;
+        let error = match result {
+            Ok(response) if response.status().is_success() => None,
+            Ok(response) => {
+                let status = response.status();
+                let message = response.text().await.unwrap_or_default();
+                Some(SendError::from_status(status.as_u16(), message))
+            }
+            Err(e) => Some(SendError::Transient(e.to_string())),
+        };
+
+        CommunicationSendResultKind::Completed {
+            failed: match error {
+                Some(error) => recipients
+                    .iter()
+                    .map(|index| (*index, error.clone()))
+                    .collect(),
+                None => vec![],
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provider() -> PagerDutyCommunicationProvider {
+        let config = format!(
+            "{}\n[pagerduty]\nrouting_key = \"key\"",
+            crate::communications::tests::THEME
+        );
+        PagerDutyCommunicationProvider::from_config(&toml::from_str(&config).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn summary_reflects_the_theme() {
+        let provider = provider();
+        let alert = |level| {
+            AlertInfo::new(
+                "ping monitor".to_string(),
+                "Router offline".to_string(),
+                level,
+            )
+            .unwrap()
+        };
+
+        let event = provider.create_event(&alert(AlertLevel::Critical));
+        let payload = event.payload.unwrap();
+        assert_eq!(payload.summary, "🔥 CRIT - ping monitor - Router offline");
+        assert_eq!(payload.severity, "error");
+
+        let event = provider.create_event(&alert(AlertLevel::Warning));
+        assert_eq!(
+            event.payload.unwrap().summary,
+            "⚠️ Warning - ping monitor - Router offline"
+        );
+    }
+
+    fn subject_alert(subject: &str, recovery: bool) -> AlertInfo {
+        let mut alert = AlertInfo::new(
+            "system_ctl monitor".to_string(),
+            format!("{subject} changed"),
+            AlertLevel::Critical,
+        )
+        .unwrap();
+        alert.metadata.subject = Some(subject.to_string());
+        alert.metadata.recovery = recovery;
+        alert
+    }
+
+    /// Mock Events API recording the body of each event it accepts.
+    fn serve() -> (
+        String,
+        std::sync::Arc<std::sync::Mutex<Vec<serde_json::Value>>>,
+    ) {
+        use warp::Filter;
+
+        let events = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = events.clone();
+        let routes = warp::post()
+            .and(warp::path!("v2" / "enqueue"))
+            .and(warp::body::json())
+            .map(move |event: serde_json::Value| {
+                recorded.lock().unwrap().push(event);
+                warp::reply::with_status("", warp::http::StatusCode::ACCEPTED)
+            });
+        let (address, server) = warp::serve(routes).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        (format!("http://{address}/v2/enqueue"), events)
+    }
+
+    #[tokio::test]
+    async fn recoveries_only_resolve_their_own_subject() {
+        let (url, events) = serve();
+        let mut provider = provider();
+        provider.url = url;
+
+        for alert in [
+            subject_alert("security_alarm_modem", false),
+            subject_alert("sms_server", false),
+            subject_alert("security_alarm_modem", true),
+        ] {
+            let CommunicationSendResultKind::Completed { failed } =
+                provider.send(&alert, &[0]).await
+            else {
+                panic!("PagerDuty was unavailable!");
+            };
+            assert!(failed.is_empty());
+        }
+
+        let events = events.lock().unwrap();
+        let sent: Vec<_> = events
+            .iter()
+            .map(|event| {
+                (
+                    event["event_action"].as_str().unwrap(),
+                    event["dedup_key"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            sent,
+            [
+                (
+                    "trigger",
+                    "sentinel-system_ctl monitor-security_alarm_modem"
+                ),
+                ("trigger", "sentinel-system_ctl monitor-sms_server"),
+                (
+                    "resolve",
+                    "sentinel-system_ctl monitor-security_alarm_modem"
+                ),
+            ]
+        );
+        assert_eq!(events[0]["routing_key"], "key");
+    }
+}
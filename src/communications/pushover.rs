@@ -1,6 +1,12 @@
 use crate::alerts::{AlertInfo, AlertLevel};
-use crate::communications::{CommunicationProvider, CommunicationSendResultKind};
-use crate::config::{CommunicationRecipient, CommunicationsConfig, PushoverCommunicationConfig};
+use crate::communications::theme::Theme;
+use crate::communications::{CommunicationProvider, CommunicationSendResultKind, SendError};
+use crate::config::{
+    CommunicationRecipient, CommunicationsConfig, PushoverCommunicationConfig, TimestampFormat,
+};
+use crate::outbound::{client_builder, ensure_host_allowed, send_with_reset_retry};
+use anyhow::Context;
+use futures::StreamExt;
 
 /*
    Pushover Communication Provider.
@@ -15,6 +21,7 @@ struct PushoverPayload {
     pub user: String,
     pub title: String,
     pub message: String,
+    pub html: u8,
     pub priority: i8,
     pub retry: Option<u32>,
     pub expire: Option<u32>,
@@ -23,9 +30,18 @@ struct PushoverPayload {
 
 pub(crate) struct PushoverCommunicationProvider {
     client: reqwest::Client,
+    url: String,
     config: PushoverCommunicationConfig,
+    timestamp_format: TimestampFormat,
+    theme: Theme,
 }
 impl PushoverCommunicationProvider {
+    fn escape_html(text: &str) -> String {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+    }
+
     /// Create a payload to send to Pushover.
     fn create_payload(
         &self,
@@ -33,13 +49,26 @@ impl PushoverCommunicationProvider {
         alert: &AlertInfo,
     ) -> PushoverPayload {
         let is_emergency = alert.level == AlertLevel::Alarm;
+        let theme = self.theme.get(&alert.level);
+        let message = match alert.format_timestamp(&self.timestamp_format) {
+            Some(timestamp) => format!("{} ({timestamp})", alert.message),
+            None => alert.message.clone(),
+        };
 
         // TODO: Reduce clones, maybe Arc<str>?
         PushoverPayload {
             token: self.config.token.clone(),
             user: recipient.target.clone(),
-            title: format!("sentinel - {}", alert.source.clone()),
-            message: alert.message.clone(),
+            title: format!(
+                "{} sentinel {} - {}",
+                theme.emoji, theme.label, alert.source
+            ),
+            message: format!(
+                "<font color=\"{}\">{}</font>",
+                theme.color,
+                Self::escape_html(&message)
+            ),
+            html: 1,
             priority: match alert.level {
                 AlertLevel::Info => -1,
                 AlertLevel::Warning => 0,
@@ -59,21 +88,37 @@ impl CommunicationProvider for PushoverCommunicationProvider {
         "pushover"
     }
 
+    fn description() -> &'static str {
+        "Sends Pushover notifications"
+    }
+
+    fn required_config() -> &'static [&'static str] {
+        &[
+            "communications.pushover.token",
+            "communications.pushover.recipients",
+        ]
+    }
+
     fn from_config(config: &CommunicationsConfig) -> anyhow::Result<Self>
     where
         Self: Sized,
     {
+        let timestamp_format = config.timestamp_format.clone();
+        let theme = Theme::from_config(&config.theme);
         let config = match &config.pushover {
             Some(config) => config,
             None => anyhow::bail!("Missing any Pushover config!"),
         };
 
         Ok(Self {
-            client: reqwest::Client::builder()
+            client: client_builder()
                 .timeout(std::time::Duration::from_secs(config.timeout))
                 .build()
-                .unwrap_or_default(),
+                .context("Failed to build Pushover HTTP client!")?,
+            url: PUSHOVER_URL.to_string(),
             config: config.clone(),
+            timestamp_format,
+            theme,
         })
     }
 
@@ -83,31 +128,148 @@ impl CommunicationProvider for PushoverCommunicationProvider {
     }
 
     async fn send(&self, alert: &AlertInfo, recipients: &[usize]) -> CommunicationSendResultKind {
+        if let Err(e) = ensure_host_allowed(&self.url) {
+            return CommunicationSendResultKind::Unavailable {
+                reason: e.to_string(),
+            };
+        }
+
         // Create a request future for each recipient since Pushover can handle simultaneous requests.
-        let futures = recipients.iter().map(|index| {
-            let payload = self.create_payload(&self.config.recipients[*index], alert);
+        let concurrency = self.config.concurrency.unwrap_or(recipients.len()).max(1);
+        let futures: Vec<_> = recipients
+            .iter()
+            .map(|index| {
+                let payload = self.create_payload(&self.config.recipients[*index], alert);
 
-            async move {
-                let result = self
-                    .client
-                    .post(PUSHOVER_URL)
-                    .header("Content-Type", "application/json")
-                    .header("Accept", "application/json")
-                    .json(&payload)
-                    .send()
+                async move {
+                    let result = send_with_reset_retry(
+                        self.client
+                            .post(&self.url)
+                            .header("Content-Type", "application/json")
+                            .header("Accept", "application/json")
+                            .json(&payload),
+                    )
                     .await;
-                (index, result)
-            }
-        });
+                    (index, result)
+                }
+            })
+            .collect();
 
         // Join all futures, tracking each failed send.
         let mut failed = Vec::with_capacity(recipients.len());
-        for (index, result) in futures::future::join_all(futures).await {
+        let mut results = futures::stream::iter(futures).buffer_unordered(concurrency);
+        while let Some((index, result)) = results.next().await {
             match result {
-                Ok(_) => {}
-                Err(_) => failed.push(*index),
+                Ok(response) if response.status().is_success() => {}
+                Ok(response) => {
+                    let status = response.status();
+                    let message = response.text().await.unwrap_or_default();
+                    failed.push((*index, SendError::from_status(status.as_u16(), message)));
+                }
+                Err(e) => failed.push((*index, SendError::Transient(e.to_string()))),
             }
         }
         CommunicationSendResultKind::Completed { failed }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provider() -> PushoverCommunicationProvider {
+        provider_with("recipients = [{ target = \"user\", level = 0 }]")
+    }
+
+    fn provider_with(options: &str) -> PushoverCommunicationProvider {
+        let config = format!(
+            "{}\n[pushover]\ntoken = \"token\"\n{options}",
+            crate::communications::tests::THEME
+        );
+        PushoverCommunicationProvider::from_config(&toml::from_str(&config).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn payload_reflects_the_theme() {
+        let provider = provider();
+        let recipient = &provider.config.recipients[0];
+        let alert = |level| {
+            AlertInfo::new(
+                "ping monitor".to_string(),
+                "Router <offline>".to_string(),
+                level,
+            )
+            .unwrap()
+        };
+
+        let payload = provider.create_payload(recipient, &alert(AlertLevel::Critical));
+        assert_eq!(payload.title, "🔥 sentinel CRIT - ping monitor");
+        assert_eq!(
+            payload.message,
+            "<font color=\"#123456\">Router &lt;offline&gt;</font>"
+        );
+        assert_eq!(payload.priority, 1);
+
+        let payload = provider.create_payload(recipient, &alert(AlertLevel::Warning));
+        assert_eq!(payload.title, "⚠️ sentinel Warning - ping monitor");
+        assert!(payload.message.starts_with("<font color=\"#FF9800\">"));
+    }
+
+    /// Mock Pushover that holds each request for 100ms, returning the most in flight at once.
+    fn serve() -> (String, std::sync::Arc<std::sync::atomic::AtomicUsize>) {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use warp::Filter;
+
+        let active = std::sync::Arc::new(AtomicUsize::new(0));
+        let peak = std::sync::Arc::new(AtomicUsize::new(0));
+        let recorded = peak.clone();
+        let routes = warp::post().then(move || {
+            let (active, peak) = (active.clone(), peak.clone());
+            async move {
+                let current = active.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(current, Ordering::SeqCst);
+                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                active.fetch_sub(1, Ordering::SeqCst);
+                warp::reply()
+            }
+        });
+        let (address, server) = warp::serve(routes).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        (format!("http://{address}/1/messages.json"), recorded)
+    }
+
+    /// Send to six recipients, returning the most requests that were in flight at once.
+    async fn peak_requests(concurrency: &str) -> usize {
+        let (url, peak) = serve();
+        let recipients = (0..6)
+            .map(|index| format!("{{ target = \"user-{index}\", level = 0 }}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let mut provider = provider_with(&format!("{concurrency}\nrecipients = [{recipients}]"));
+        provider.url = url;
+
+        let alert = AlertInfo::new(
+            "ping monitor".to_string(),
+            "Router offline".to_string(),
+            AlertLevel::Warning,
+        )
+        .unwrap();
+        let CommunicationSendResultKind::Completed { failed } =
+            provider.send(&alert, &[0, 1, 2, 3, 4, 5]).await
+        else {
+            panic!("Pushover was unavailable!");
+        };
+        assert!(failed.is_empty());
+        peak.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    #[tokio::test]
+    async fn concurrency_bounds_simultaneous_requests() {
+        assert_eq!(peak_requests("concurrency = 2").await, 2);
+    }
+
+    #[tokio::test]
+    async fn recipients_are_sent_at_once_by_default() {
+        assert_eq!(peak_requests("").await, 6);
+    }
+}
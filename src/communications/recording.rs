@@ -0,0 +1,119 @@
+use crate::alerts::AlertInfo;
+use crate::audit::append_rotating;
+use crate::communications::{CommunicationProvider, CommunicationSendResultKind, SendError};
+use crate::config::{CommunicationRecipient, CommunicationsConfig};
+
+/*
+   Recording Communication Provider.
+   "Sends" alerts by appending them to a rotating JSONL file, so a new site
+   can be validated end-to-end before any real providers are enabled.
+*/
+
+#[derive(serde::Serialize)]
+struct RecordedAlert<'a> {
+    recorded_at: u64,
+    alert: &'a AlertInfo,
+}
+
+pub(crate) struct RecordingCommunicationProvider {
+    path: std::path::PathBuf,
+    max_size: u64,
+    lock: tokio::sync::Mutex<()>,
+    recipients: Vec<CommunicationRecipient>,
+}
+impl RecordingCommunicationProvider {
+    async fn record(&self, alert: &AlertInfo) -> anyhow::Result<()> {
+        let mut line = serde_json::to_vec(&RecordedAlert {
+            recorded_at: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)?
+                .as_secs(),
+            alert,
+        })?;
+        line.push(b'\n');
+
+        let _guard = self.lock.lock().await;
+        append_rotating(&self.path, self.max_size, &line).await
+    }
+}
+
+#[async_trait::async_trait]
+impl CommunicationProvider for RecordingCommunicationProvider {
+    fn name() -> &'static str {
+        "recording"
+    }
+
+    fn description() -> &'static str {
+        "Records alerts to a file instead of sending them"
+    }
+
+    fn required_config() -> &'static [&'static str] {
+        &["communications.recording.path"]
+    }
+
+    fn from_config(config: &CommunicationsConfig) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        let config = match &config.recording {
+            Some(config) => config,
+            None => anyhow::bail!("Missing any recording config!"),
+        };
+
+        Ok(Self {
+            path: config.path.clone(),
+            max_size: config.max_size,
+            lock: tokio::sync::Mutex::new(()),
+            recipients: vec![CommunicationRecipient {
+                target: config.path.display().to_string(),
+                level: config.level,
+            }],
+        })
+    }
+
+    #[inline]
+    fn get_all_recipients(&self) -> &Vec<CommunicationRecipient> {
+        &self.recipients
+    }
+
+    async fn send(&self, alert: &AlertInfo, recipients: &[usize]) -> CommunicationSendResultKind {
+        match self.record(alert).await {
+            Ok(()) => CommunicationSendResultKind::Completed { failed: vec![] },
+            Err(e) => CommunicationSendResultKind::Completed {
+                failed: recipients
+                    .iter()
+                    .map(|index| (*index, SendError::Transient(e.to_string())))
+                    .collect(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::alerts::{AlertInfo, AlertLevel};
+    use crate::communications::CommunicationRegistry;
+
+    #[tokio::test]
+    async fn records_alerts_as_delivered() {
+        let path = crate::audit::tests::audit_path("recording");
+        let config = toml::from_str(&format!("[recording]\npath = {path:?}")).unwrap();
+        let registry = CommunicationRegistry::new(&config).await.unwrap();
+
+        let alert = AlertInfo::new(
+            "test".to_string(),
+            "Front door opened".to_string(),
+            AlertLevel::Critical,
+        )
+        .unwrap();
+        let outcomes = registry.broadcast(&alert).await;
+        assert_eq!(outcomes.len(), 1);
+        assert_eq!(outcomes[0].0, "recording");
+        assert!(outcomes[0].1.is_delivered());
+
+        let line: serde_json::Value =
+            serde_json::from_str(std::fs::read_to_string(&path).unwrap().trim_end()).unwrap();
+        assert!(line["recorded_at"].as_u64().is_some());
+        assert_eq!(line["alert"]["message"], "Front door opened");
+        assert_eq!(line["alert"]["level"], "Critical");
+    }
+}
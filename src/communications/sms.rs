@@ -1,12 +1,40 @@
 use crate::alerts::AlertInfo;
-use crate::communications::{CommunicationProvider, CommunicationSendResultKind};
-use crate::config::{CommunicationRecipient, CommunicationsConfig, SMSCommunicationConfig};
+use crate::communications::{gsm7, CommunicationProvider, CommunicationSendResultKind, SendError};
+use crate::config::{
+    CommunicationRecipient, CommunicationsConfig, SMSCommunicationConfig, TimestampFormat,
+};
+use crate::outbound::ensure_host_allowed;
+use futures::StreamExt;
+use log::debug;
 
 pub(crate) struct SMSCommunicationProvider {
     client: sms_client::Client,
     config: SMSCommunicationConfig,
+    timestamp_format: TimestampFormat,
 }
 impl SMSCommunicationProvider {
+    fn classify_error(error: sms_client::http::error::HttpError) -> SendError {
+        match error {
+            sms_client::http::error::HttpError::HttpStatus { status, message } => {
+                SendError::from_status(status, message)
+            }
+            sms_client::http::error::HttpError::ApiError(message) => SendError::Invalid(message),
+            e => SendError::Transient(e.to_string()),
+        }
+    }
+
+    fn content(&self, alert: &AlertInfo) -> String {
+        let content = match alert.format_timestamp(&self.timestamp_format) {
+            Some(timestamp) => format!("sentinel - {alert} ({timestamp})"),
+            None => format!("sentinel - {alert}"),
+        };
+        if self.config.gsm7 {
+            gsm7::normalize(&content)
+        } else {
+            content
+        }
+    }
+
     fn create_message(
         &self,
         recipient: &CommunicationRecipient,
@@ -14,7 +42,7 @@ impl SMSCommunicationProvider {
     ) -> sms_client::types::sms::SmsOutgoingMessage {
         sms_client::types::sms::SmsOutgoingMessage::simple_message(
             recipient.target.clone(),
-            format!("sentinel - {alert}"),
+            self.content(alert),
         )
     }
 }
@@ -25,19 +53,33 @@ impl CommunicationProvider for SMSCommunicationProvider {
         "sms"
     }
 
+    fn description() -> &'static str {
+        "Sends SMS messages through sms-server"
+    }
+
+    fn required_config() -> &'static [&'static str] {
+        &[
+            "communications.sms.http_base",
+            "communications.sms.recipients",
+        ]
+    }
+
     fn from_config(config: &CommunicationsConfig) -> anyhow::Result<Self>
     where
         Self: Sized,
     {
+        let timestamp_format = config.timestamp_format.clone();
         let config = match &config.sms {
             Some(config) => config,
             None => anyhow::bail!("Missing any SMS config!"),
         };
 
+        ensure_host_allowed(config.get_http_base())?;
         Ok(Self {
-            client: sms_client::Client::new(config.get_sms_config())
+            client: sms_client::Client::new(config.get_sms_config()?)
                 .map_err(|e| anyhow::anyhow!(e))?,
             config: config.clone(),
+            timestamp_format,
         })
     }
 
@@ -46,6 +88,18 @@ impl CommunicationProvider for SMSCommunicationProvider {
         &self.config.recipients
     }
 
+    fn supports(&self, alert: &AlertInfo) -> bool {
+        let Some(max_segments) = self.config.max_segments else {
+            return true;
+        };
+        let segments = gsm7::segments(&self.content(alert));
+        if segments > max_segments {
+            debug!("SMS needs {segments} segments, over the limit of {max_segments}.");
+            return false;
+        }
+        true
+    }
+
     async fn send(&self, alert: &AlertInfo, recipients: &[usize]) -> CommunicationSendResultKind {
         let http = match self.client.http() {
             Ok(http) => http,
@@ -56,16 +110,90 @@ impl CommunicationProvider for SMSCommunicationProvider {
             }
         };
 
-        // There is no point in using futures here since the SMS server queues operations anyway.
-        let mut failed = Vec::with_capacity(recipients.len());
-        for index in recipients.iter() {
-            let message = self.create_message(&self.config.recipients[*index], alert);
+        // Sequential unless configured otherwise, since the SMS server queues operations anyway.
+        let futures: Vec<_> = recipients
+            .iter()
+            .map(|index| {
+                let message = self.create_message(&self.config.recipients[*index], alert);
+                let http = &http;
+                async move { (index, http.send_sms(&message).await) }
+            })
+            .collect();
 
-            match http.send_sms(&message).await {
-                Ok(_) => {}
-                Err(_) => failed.push(*index),
+        let mut failed = Vec::with_capacity(recipients.len());
+        let mut results =
+            futures::stream::iter(futures).buffer_unordered(self.config.concurrency.max(1));
+        while let Some((index, result)) = results.next().await {
+            if let Err(e) = result {
+                failed.push((*index, Self::classify_error(e)));
             }
         }
         CommunicationSendResultKind::Completed { failed }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alerts::AlertLevel;
+
+    /// An SMS provider with the given options added to its config.
+    fn provider(options: &str) -> SMSCommunicationProvider {
+        let config = format!(
+            r#"
+            timestamp_format = "none"
+
+            [sms]
+            http_base = "http://127.0.0.1:1"
+            recipients = [{{ target = "+4412345678", level = 0 }}]
+            {options}
+            "#
+        );
+        SMSCommunicationProvider::from_config(&toml::from_str(&config).unwrap()).unwrap()
+    }
+
+    fn alert(message: &str) -> AlertInfo {
+        AlertInfo::new("test".to_string(), message.to_string(), AlertLevel::Alarm).unwrap()
+    }
+
+    #[test]
+    fn invalid_certificate_paths_fail_to_initialize() {
+        let config: CommunicationsConfig = toml::from_str(
+            r#"
+            [sms]
+            http_base = "http://127.0.0.1:1"
+            recipients = []
+            certificate_path = "/nonexistent/sms.crt"
+            "#,
+        )
+        .unwrap();
+        assert!(config.sms.as_ref().unwrap().get_sms_config().is_err());
+        assert!(SMSCommunicationProvider::from_config(&config).is_err());
+    }
+
+    #[test]
+    fn supports_alerts_within_the_segment_limit() {
+        let provider = provider("max_segments = 1");
+        assert!(provider.supports(&alert("Front door opened")));
+        assert!(!provider.supports(&alert(&"a".repeat(160))));
+    }
+
+    #[test]
+    fn supports_any_length_without_a_limit() {
+        assert!(provider("").supports(&alert(&"a".repeat(1000))));
+    }
+
+    #[test]
+    fn normalizes_messages_before_counting_segments() {
+        let alert = alert(&"🚨 Front door opened 🚪 ".repeat(4));
+        let normalizing = provider("gsm7 = true\nmax_segments = 1");
+
+        let content = normalizing.content(&alert);
+        assert!(gsm7::is_gsm7(&content));
+        assert!(content.starts_with("sentinel - test -  Front door opened  "));
+        assert!(normalizing.supports(&alert));
+
+        // Sent as UCS-2 without normalizing, so it no longer fits in a segment.
+        assert!(!provider("max_segments = 1").supports(&alert));
+    }
+}
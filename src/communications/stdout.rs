@@ -0,0 +1,105 @@
+use crate::alerts::AlertInfo;
+use crate::communications::{CommunicationProvider, CommunicationSendResultKind, SendError};
+use crate::config::{CommunicationRecipient, CommunicationsConfig};
+use std::io::Write;
+
+/*
+   Stdout Communication Provider.
+   Writes each alert as a JSON line to stdout, for containerized deployments
+   where the platform already collects and ships stdout.
+*/
+
+pub(crate) struct StdoutCommunicationProvider {
+    recipients: Vec<CommunicationRecipient>,
+}
+impl StdoutCommunicationProvider {
+    /// Write the alert as a single JSON line, flushing it so it's collected immediately.
+    fn write(mut writer: impl Write, alert: &AlertInfo) -> anyhow::Result<()> {
+        let mut line = serde_json::to_vec(alert)?;
+        line.push(b'\n');
+
+        writer.write_all(&line)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl CommunicationProvider for StdoutCommunicationProvider {
+    fn name() -> &'static str {
+        "stdout"
+    }
+
+    fn description() -> &'static str {
+        "Writes alerts as JSON lines to stdout"
+    }
+
+    fn required_config() -> &'static [&'static str] {
+        &["communications.stdout"]
+    }
+
+    fn from_config(config: &CommunicationsConfig) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        let config = match &config.stdout {
+            Some(config) => config,
+            None => anyhow::bail!("Missing any stdout config!"),
+        };
+
+        Ok(Self {
+            recipients: vec![CommunicationRecipient {
+                target: "stdout".to_string(),
+                level: config.level,
+            }],
+        })
+    }
+
+    #[inline]
+    fn get_all_recipients(&self) -> &Vec<CommunicationRecipient> {
+        &self.recipients
+    }
+
+    async fn send(&self, alert: &AlertInfo, recipients: &[usize]) -> CommunicationSendResultKind {
+        match Self::write(std::io::stdout().lock(), alert) {
+            Ok(()) => CommunicationSendResultKind::Completed { failed: vec![] },
+            Err(e) => CommunicationSendResultKind::Completed {
+                failed: recipients
+                    .iter()
+                    .map(|index| (*index, SendError::Transient(e.to_string())))
+                    .collect(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alerts::AlertLevel;
+
+    #[test]
+    fn writes_an_alert_as_a_json_line() {
+        let mut alert = AlertInfo::new(
+            "ping monitor".to_string(),
+            "[router] Now offline!".to_string(),
+            AlertLevel::Critical,
+        )
+        .unwrap();
+        alert.timestamp = Some(1_700_000_000);
+        alert.metadata.subject = Some("router".to_string());
+
+        let mut output = Vec::new();
+        StdoutCommunicationProvider::write(&mut output, &alert).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        let (line, rest) = output.split_once('\n').unwrap();
+        assert_eq!(rest, "");
+        let line: serde_json::Value = serde_json::from_str(line).unwrap();
+        assert_eq!(line["source"], "ping monitor");
+        assert_eq!(line["message"], "[router] Now offline!");
+        assert_eq!(line["level"], "Critical");
+        assert_eq!(line["timestamp"], 1_700_000_000);
+        assert_eq!(line["metadata"]["subject"], "router");
+    }
+}
@@ -0,0 +1,65 @@
+use crate::alerts::AlertLevel;
+use crate::config::LevelThemeConfig;
+
+/*
+   Shared visual language for providers, so each AlertLevel looks the same
+   wherever it's sent. Any part of a level's theme can be overridden in config.
+*/
+
+#[derive(Clone)]
+pub(crate) struct LevelTheme {
+    pub color: String,
+    pub emoji: String,
+    pub label: String,
+}
+
+#[derive(Clone)]
+pub(crate) struct Theme {
+    levels: [LevelTheme; 4],
+}
+impl Theme {
+    fn default_theme(level: &AlertLevel) -> LevelTheme {
+        let (color, emoji, label) = match level {
+            AlertLevel::Info => ("#2196F3", "ℹ️", "Info"),
+            AlertLevel::Warning => ("#FF9800", "⚠️", "Warning"),
+            AlertLevel::Critical => ("#F44336", "🔴", "Critical"),
+            AlertLevel::Alarm => ("#B71C1C", "🚨", "Alarm"),
+        };
+        LevelTheme {
+            color: color.to_string(),
+            emoji: emoji.to_string(),
+            label: label.to_string(),
+        }
+    }
+
+    pub fn from_config(config: &std::collections::BTreeMap<AlertLevel, LevelThemeConfig>) -> Self {
+        let levels = [
+            AlertLevel::Info,
+            AlertLevel::Warning,
+            AlertLevel::Critical,
+            AlertLevel::Alarm,
+        ]
+        .map(|level| {
+            let mut theme = Self::default_theme(&level);
+            if let Some(overrides) = config.get(&level) {
+                if let Some(color) = &overrides.color {
+                    theme.color = color.clone();
+                }
+                if let Some(emoji) = &overrides.emoji {
+                    theme.emoji = emoji.clone();
+                }
+                if let Some(label) = &overrides.label {
+                    theme.label = label.clone();
+                }
+            }
+            theme
+        });
+
+        Self { levels }
+    }
+
+    #[inline]
+    pub fn get(&self, level: &AlertLevel) -> &LevelTheme {
+        &self.levels[usize::from(u8::from(level)) - 1]
+    }
+}
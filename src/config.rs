@@ -1,7 +1,14 @@
 use crate::alerts::AlertLevel;
 use anyhow::Context;
+use log::warn;
 
-#[derive(Debug, serde::Deserialize)]
+const RETRY_DELAY_FLOOR: u64 = 1;
+const RETRY_DELAY_CEILING: u64 = 3600;
+const RETRY_JITTER_CEILING: f64 = 0.5;
+const POLL_INTERVAL_FLOOR: u64 = 1;
+const ALARM_COOLDOWN_CEILING: u64 = 3600;
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub(crate) struct AppConfig {
     #[serde(default)]
     pub http: HttpConfig,
@@ -17,6 +24,9 @@ pub(crate) struct AppConfig {
 
     #[serde(default)]
     pub communications: CommunicationsConfig,
+
+    #[serde(default)]
+    pub outbound: OutboundConfig,
 }
 impl AppConfig {
     pub fn load(config_filepath: Option<std::path::PathBuf>) -> anyhow::Result<Self> {
@@ -28,52 +38,368 @@ impl AppConfig {
 
         let config: AppConfig = toml::from_str(&config_content)
             .with_context(|| format!("Failed to parse TOML config file: {config_path:?}"))?;
+        config
+            .validate()
+            .with_context(|| format!("Invalid config file: {config_path:?}"))?;
 
         Ok(config)
     }
+
+    /// Reject values that can't be clamped to anything sensible.
+    fn validate(&self) -> anyhow::Result<()> {
+        // A zero watchdog would restart a healthy monitor before it could ever heartbeat.
+        if let Some((name, _)) = self
+            .monitors
+            .watchdog_timeouts
+            .iter()
+            .find(|(_, timeout)| **timeout == 0)
+        {
+            anyhow::bail!("Config monitors.watchdog_timeouts.{name} must be above zero!");
+        }
+
+        // Zero confirmations would count a sequential alarm as sent before any provider tried it.
+        if self.communications.alarm_confirmations == 0 {
+            anyhow::bail!("Config communications.alarm_confirmations must be above zero!");
+        }
+
+        // Outside this range every non-alarm webhook is shed, or none ever are.
+        let shed_watermark = self.http.shed_watermark;
+        if !(shed_watermark > 0.0 && shed_watermark <= 1.0) {
+            anyhow::bail!(
+                "Config http.shed_watermark of {shed_watermark} must be above zero and at most one!"
+            );
+        }
+        Ok(())
+    }
+
+    /// Clamp values into their bounds, since a zero retry delay would hammer providers on
+    /// failures, a zero interval would spin and a huge retry delay would never retry in
+    /// time to matter. A zero alarm cooldown is allowed and sends every alarm, but a huge
+    /// one would suppress every alarm after the first. Called once logging is initialized.
+    pub fn clamp_limits(&mut self) {
+        let communications = &mut self.communications;
+        clamp_range(
+            "communications.retry_delay",
+            &mut communications.retry_delay,
+            RETRY_DELAY_FLOOR,
+            RETRY_DELAY_CEILING,
+        );
+        for (level, retry) in communications.level_retry.iter_mut() {
+            if let Some(retry_delay) = retry.retry_delay.as_mut() {
+                clamp_range(
+                    &format!("communications.level_retry.{level:?}.retry_delay"),
+                    retry_delay,
+                    RETRY_DELAY_FLOOR,
+                    RETRY_DELAY_CEILING,
+                );
+            }
+        }
+        clamp_range(
+            "communications.retry_jitter",
+            &mut communications.retry_jitter,
+            0.0,
+            RETRY_JITTER_CEILING,
+        );
+
+        clamp_range(
+            "alerts.alarm_cooldown",
+            &mut self.alerts.alarm_cooldown,
+            0,
+            ALARM_COOLDOWN_CEILING,
+        );
+        clamp_floor(
+            "alerts.source_rate_window",
+            &mut self.alerts.source_rate_window,
+            POLL_INTERVAL_FLOOR,
+        );
+
+        let monitors = &mut self.monitors;
+        clamp_range(
+            "monitors.systemctl_retry_delay",
+            &mut monitors.systemctl_retry_delay,
+            RETRY_DELAY_FLOOR,
+            RETRY_DELAY_CEILING,
+        );
+        clamp_range(
+            "monitors.systemctl_retry_delay_max",
+            &mut monitors.systemctl_retry_delay_max,
+            RETRY_DELAY_FLOOR,
+            RETRY_DELAY_CEILING,
+        );
+        clamp_floor(
+            "monitors.systemctl_poll_interval",
+            &mut monitors.systemctl_poll_interval,
+            POLL_INTERVAL_FLOOR,
+        );
+        clamp_floor(
+            "monitors.healthcheck_interval",
+            &mut monitors.healthcheck_interval,
+            POLL_INTERVAL_FLOOR,
+        );
+        for target in monitors.pings.iter_mut().flatten() {
+            if let Some(interval) = target.interval.as_mut() {
+                clamp_floor(
+                    &format!("monitors.pings.{}.interval", target.name),
+                    interval,
+                    POLL_INTERVAL_FLOOR,
+                );
+            }
+        }
+        if let Some(modem) = monitors.modem.as_mut() {
+            clamp_floor(
+                "monitors.modem.interval",
+                &mut modem.interval,
+                POLL_INTERVAL_FLOOR,
+            );
+        }
+        if let Some(clock) = monitors.clock.as_mut() {
+            clamp_floor(
+                "monitors.clock.interval",
+                &mut clock.interval,
+                POLL_INTERVAL_FLOOR,
+            );
+        }
+        if let Some(digest_interval) = monitors.digest_interval.as_mut() {
+            clamp_floor(
+                "monitors.digest_interval",
+                digest_interval,
+                POLL_INTERVAL_FLOOR,
+            );
+        }
+    }
 }
 
-#[derive(Debug, serde::Deserialize)]
+fn clamp_floor<T: PartialOrd + std::fmt::Display + Copy>(name: &str, value: &mut T, floor: T) {
+    if *value < floor {
+        warn!("Config {name} of {value} is below the minimum, clamping to {floor}!");
+        *value = floor;
+    }
+}
+
+fn clamp_range<T: PartialOrd + std::fmt::Display + Copy>(
+    name: &str,
+    value: &mut T,
+    floor: T,
+    ceiling: T,
+) {
+    clamp_floor(name, value, floor);
+    if *value > ceiling {
+        warn!("Config {name} of {value} is above the maximum, clamping to {ceiling}!");
+        *value = ceiling;
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub(crate) struct HttpConfig {
     #[serde(default = "default_bind_address")]
     pub bind_address: std::net::SocketAddr,
+
+    /// Required Authorization header value. While unset every authenticated request is
+    /// rejected, unless auth is explicitly disabled.
+    #[serde(default, serialize_with = "redact_option")]
+    pub auth_token: Option<String>,
+
+    /// Accept every request without an Authorization header, for trusted networks only.
+    #[serde(default)]
+    pub auth_disabled: bool,
+
+    /// Token that was rotated out, still accepted for the overlap after its rotation.
+    #[serde(default, serialize_with = "redact_option")]
+    pub previous_auth_token: Option<String>,
+
+    #[serde(default = "default_http_previous_auth_token_overlap")]
+    pub previous_auth_token_overlap: u64,
+
+    /// Unix timestamp the previous token was rotated out at, otherwise startup.
+    #[serde(default)]
+    pub previous_auth_token_rotated_at: Option<u64>,
+
+    #[serde(default = "default_cctv_forwarded_fields")]
+    pub cctv_forwarded_fields: Vec<String>,
+
+    #[serde(default, serialize_with = "serialize_sorted")]
+    pub cctv_dropped_fields: std::collections::HashSet<String>,
+
+    #[serde(default = "default_http_enqueue_timeout")]
+    pub enqueue_timeout: u64,
+
+    /// Maximum simultaneous HTTP connections, with the rest left queued in the listen backlog.
+    #[serde(default)]
+    pub max_connections: Option<usize>,
+
+    /// Milliseconds after a queued CCTV event in which further events like it are coalesced into it.
+    #[serde(default)]
+    pub cctv_debounce: u64,
+
+    /// Fraction of the alert channel that can be filled before non-alarm webhooks are shed.
+    #[serde(default = "default_http_shed_watermark")]
+    pub shed_watermark: f32,
+
+    /// Addresses to serve on, each optionally with TLS or its own token. Defaults to bind_address.
+    #[serde(default)]
+    pub listeners: Vec<HttpListenerConfig>,
+}
+impl HttpConfig {
+    pub fn get_listeners(&self) -> Vec<HttpListenerConfig> {
+        if !self.listeners.is_empty() {
+            return self.listeners.clone();
+        }
+        vec![HttpListenerConfig {
+            bind_address: self.bind_address,
+            tls: None,
+            auth_token: None,
+        }]
+    }
 }
 impl Default for HttpConfig {
     fn default() -> Self {
         Self {
             bind_address: default_bind_address(),
+            auth_token: None,
+            auth_disabled: false,
+            previous_auth_token: None,
+            previous_auth_token_overlap: default_http_previous_auth_token_overlap(),
+            previous_auth_token_rotated_at: None,
+            cctv_forwarded_fields: default_cctv_forwarded_fields(),
+            cctv_dropped_fields: std::collections::HashSet::new(),
+            enqueue_timeout: default_http_enqueue_timeout(),
+            max_connections: None,
+            cctv_debounce: 0,
+            shed_watermark: default_http_shed_watermark(),
+            listeners: vec![],
         }
     }
 }
 
-#[derive(Default, Debug, serde::Deserialize)]
-pub(crate) struct SentryConfig {
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) struct HttpListenerConfig {
+    pub bind_address: std::net::SocketAddr, // REQUIRED
+
+    #[serde(default)]
+    pub tls: Option<HttpTlsConfig>,
+
+    /// Required Authorization header value on this listener, instead of the http auth_token.
+    #[serde(default, serialize_with = "redact_option")]
+    pub auth_token: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) struct HttpTlsConfig {
+    pub cert_path: std::path::PathBuf, // REQUIRED
+    pub key_path: std::path::PathBuf,  // REQUIRED
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) struct OutboundConfig {
+    #[serde(default, serialize_with = "serialize_sorted_option")]
+    pub host_allowlist: Option<std::collections::HashSet<String>>,
+
+    #[serde(default = "default_outbound_pool_max_idle_per_host")]
+    pub pool_max_idle_per_host: usize,
+
+    #[serde(default = "default_outbound_pool_idle_timeout")]
+    pub pool_idle_timeout: u64,
+
+    #[serde(default = "default_true")]
+    pub tcp_nodelay: bool,
+
     #[serde(default)]
+    pub http2_prior_knowledge: bool,
+}
+impl Default for OutboundConfig {
+    fn default() -> Self {
+        Self {
+            host_allowlist: None,
+            pool_max_idle_per_host: default_outbound_pool_max_idle_per_host(),
+            pool_idle_timeout: default_outbound_pool_idle_timeout(),
+            tcp_nodelay: true,
+            http2_prior_knowledge: false,
+        }
+    }
+}
+
+#[derive(Default, Debug, serde::Serialize, serde::Deserialize)]
+pub(crate) struct SentryConfig {
+    #[serde(default, serialize_with = "redact_option")]
     pub dsn: Option<String>,
 }
 
-#[derive(Debug, serde::Deserialize)]
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub(crate) struct AlertsConfig {
     #[serde(default = "default_alarm_cooldown")]
     pub alarm_cooldown: u64,
 
     #[serde(default = "default_alerts_send_concurrency_limit")]
     pub send_concurrency_limit: usize,
+
+    #[serde(default)]
+    pub message_template: Option<String>,
+
+    #[serde(default)]
+    pub site: Option<String>,
+
+    /// If set, an alarm only fires once a second alarm arrives within this many seconds.
+    #[serde(default)]
+    pub alarm_confirmation_window: Option<u64>,
+
+    /// Alarm sources that need confirmation, or all alarms if unset.
+    #[serde(default, serialize_with = "serialize_sorted_option")]
+    pub alarm_confirmation_sources: Option<std::collections::HashSet<String>>,
+
+    /// Append every broadcast alert and its delivery outcome to this JSONL file.
+    #[serde(default)]
+    pub audit_log_path: Option<std::path::PathBuf>,
+
+    #[serde(default = "default_audit_log_max_size")]
+    pub audit_log_max_size: u64,
+
+    /// Friendly names for monitors and providers, keyed by their internal name.
+    #[serde(default)]
+    pub display_names: std::collections::BTreeMap<String, String>,
+
+    /// Maximum non-alarm alerts queued per source in each rate window, unlimited if unset.
+    #[serde(default)]
+    pub source_rate_limit: Option<u32>,
+
+    #[serde(default = "default_alerts_source_rate_window")]
+    pub source_rate_window: u64,
+
+    /// Seconds after startup in which recoverable problem alerts from monitors are held, only sent if not recovered by the end.
+    #[serde(default)]
+    pub boot_settle: Option<u64>,
 }
 impl Default for AlertsConfig {
     fn default() -> Self {
         Self {
             alarm_cooldown: default_alarm_cooldown(),
             send_concurrency_limit: default_alerts_send_concurrency_limit(),
+            message_template: None,
+            site: None,
+            alarm_confirmation_window: None,
+            alarm_confirmation_sources: None,
+            audit_log_path: None,
+            audit_log_max_size: default_audit_log_max_size(),
+            source_rate_limit: None,
+            source_rate_window: default_alerts_source_rate_window(),
+            boot_settle: None,
+            display_names: std::collections::BTreeMap::new(),
         }
     }
 }
 
-#[derive(Debug, serde::Deserialize)]
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub(crate) struct MonitorsConfig {
-    #[serde(default)]
+    #[serde(default, serialize_with = "serialize_sorted_option")]
     pub disabled: Option<std::collections::HashSet<String>>,
 
+    /// Only these monitors may run if set, though disabled still takes precedence.
+    #[serde(default, serialize_with = "serialize_sorted_option")]
+    pub enabled: Option<std::collections::HashSet<String>>,
+
+    /// Fail startup if no monitors are enabled, rather than only serving webhooks.
+    #[serde(default)]
+    pub require_any: bool,
+
     #[serde(default = "default_poll_interval")]
     pub systemctl_poll_interval: u64,
 
@@ -83,40 +409,93 @@ pub(crate) struct MonitorsConfig {
     #[serde(default = "default_systemctl_retry_delay")]
     pub systemctl_retry_delay: u64,
 
+    #[serde(default = "default_systemctl_retry_delay_max")]
+    pub systemctl_retry_delay_max: u64,
+
+    #[serde(default = "default_systemctl_stable_duration")]
+    pub systemctl_stable_duration: u64,
+
+    /// Skip the ONLINE alert when a service recovered without being restarted by us.
+    #[serde(default)]
+    pub systemctl_suppress_self_recovery: bool,
+
     #[serde(default)]
     pub systemctl: Option<Vec<MonitoredService>>,
 
     #[serde(default)]
     pub pings: Option<Vec<MonitoredPingTarget>>,
 
-    #[serde(default)]
+    #[serde(default = "default_ping_default_port")]
+    pub ping_default_port: u16,
+
+    #[serde(default, serialize_with = "redact_option")]
     pub healthcheck: Option<String>,
 
     #[serde(default = "default_poll_interval")]
     pub healthcheck_interval: u64,
+
+    #[serde(default)]
+    pub modem: Option<ModemMonitorConfig>,
+
+    #[serde(default)]
+    pub clock: Option<ClockMonitorConfig>,
+
+    #[serde(default)]
+    pub self_test: Option<SelfTestMonitorConfig>,
+
+    /// Monitors whose run returning Ok is a normal restart, rather than alerted as an anomaly.
+    #[serde(default, serialize_with = "serialize_sorted")]
+    pub completion_expected: std::collections::HashSet<String>,
+
+    /// Seconds without a heartbeat before a monitor is restarted, keyed by monitor name.
+    #[serde(default)]
+    pub watchdog_timeouts: std::collections::BTreeMap<String, u64>,
+
+    /// Seconds between "all systems normal" digests, disabled if unset.
+    #[serde(default)]
+    pub digest_interval: Option<u64>,
 }
 impl Default for MonitorsConfig {
     fn default() -> Self {
         Self {
             disabled: None,
+            enabled: None,
+            require_any: false,
             systemctl_poll_interval: default_poll_interval(),
             systemctl_retry_attempts: default_systemctl_retry_attempts(),
             systemctl_retry_delay: default_systemctl_retry_delay(),
+            systemctl_retry_delay_max: default_systemctl_retry_delay_max(),
+            systemctl_stable_duration: default_systemctl_stable_duration(),
+            systemctl_suppress_self_recovery: false,
             systemctl: None,
             pings: None,
+            ping_default_port: default_ping_default_port(),
             healthcheck: None,
             healthcheck_interval: default_poll_interval(),
+            modem: None,
+            clock: None,
+            self_test: None,
+            completion_expected: std::collections::HashSet::new(),
+            watchdog_timeouts: std::collections::BTreeMap::new(),
+            digest_interval: None,
         }
     }
 }
 
-#[derive(Debug, Clone, serde::Deserialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub(crate) struct MonitoredService {
     pub name: String,
     pub level: u8,
+
+    #[serde(default)]
+    pub recovery_level: Option<u8>,
+
+    /// Send the OFFLINE alert as an Alarm, regardless of level.
+    #[serde(default)]
+    pub alarm_on_offline: bool,
 }
 
-#[derive(Debug, Clone, serde::Deserialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub(crate) struct MonitoredPingTarget {
     pub name: String,
     pub addr: String,
@@ -127,9 +506,58 @@ pub(crate) struct MonitoredPingTarget {
 
     #[serde(default)]
     pub interval: Option<u64>,
+
+    /// Seconds the target must stay offline before alerting, so brief blips are ignored.
+    #[serde(default)]
+    pub sustained: Option<u64>,
+
+    /// If set, also GET this path over HTTP and treat a non-2xx response as offline.
+    #[serde(default)]
+    pub http_path: Option<String>,
 }
 
-#[derive(Debug, Clone, serde::Deserialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) struct ModemMonitorConfig {
+    pub device: String, // REQUIRED
+
+    #[serde(default = "default_modem_baud_rate")]
+    pub baud_rate: u32,
+
+    #[serde(default = "default_modem_signal_threshold")]
+    pub signal_threshold: u8,
+
+    /// Also check the modem is attached for packet data with AT+CGATT.
+    #[serde(default)]
+    pub check_data_attach: bool,
+
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+
+    #[serde(default = "default_poll_interval")]
+    pub interval: u64,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) struct ClockMonitorConfig {
+    /// Seconds the clock may be offset from NTP, checked with chronyc if set.
+    #[serde(default)]
+    pub max_offset: Option<f64>,
+
+    #[serde(default = "default_poll_interval")]
+    pub interval: u64,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) struct SelfTestMonitorConfig {
+    /// UTC time of day to send the test alarm, as "HH:MM".
+    pub time: String, // REQUIRED
+
+    /// Only send the test alarm on this day of the week, such as "sunday", instead of daily.
+    #[serde(default)]
+    pub weekday: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub(crate) struct CommunicationsConfig {
     #[serde(default)]
     pub pushover: Option<PushoverCommunicationConfig>,
@@ -137,67 +565,316 @@ pub(crate) struct CommunicationsConfig {
     #[serde(default)]
     pub sms: Option<SMSCommunicationConfig>,
 
+    #[serde(default)]
+    pub journal: Option<JournalCommunicationConfig>,
+
+    #[serde(default)]
+    pub gpio: Option<GpioCommunicationConfig>,
+
+    #[serde(default)]
+    pub recording: Option<RecordingCommunicationConfig>,
+
+    #[serde(default)]
+    pub stdout: Option<StdoutCommunicationConfig>,
+
+    #[serde(default)]
+    pub home_assistant: Option<HomeAssistantCommunicationConfig>,
+
+    #[serde(default)]
+    pub pagerduty: Option<PagerDutyCommunicationConfig>,
+
     #[serde(default = "default_communications_retry_max")]
     pub retry_max: u64,
 
     #[serde(default = "default_communications_retry_delay")]
     pub retry_delay: u64,
+
+    /// Fraction each retry delay is randomly spread by either way, so retries don't align.
+    #[serde(default)]
+    pub retry_jitter: f64,
+
+    /// Per-level overrides of the color, emoji and label used by providers.
+    #[serde(default)]
+    pub theme: std::collections::BTreeMap<AlertLevel, LevelThemeConfig>,
+
+    /// Per-level overrides of retry_max and retry_delay.
+    #[serde(default)]
+    pub level_retry: std::collections::BTreeMap<AlertLevel, LevelRetryConfig>,
+
+    #[serde(default = "default_communications_init_timeout")]
+    pub init_timeout: u64,
+
+    #[serde(default)]
+    pub timestamp_format: TimestampFormat,
+
+    #[serde(default)]
+    pub alarm_strategy: BroadcastStrategy,
+
+    #[serde(default = "default_communications_alarm_confirmations")]
+    pub alarm_confirmations: usize,
+
+    #[serde(default)]
+    pub priority: Vec<String>,
+
+    /// Only send Info alerts to these providers if set, leaving other levels unaffected.
+    #[serde(default, serialize_with = "serialize_sorted_option")]
+    pub info_providers: Option<std::collections::HashSet<String>>,
+
+    /// Providers sent to at every recipient when level filtering would otherwise leave none.
+    #[serde(default, serialize_with = "serialize_sorted")]
+    pub fallback_providers: std::collections::HashSet<String>,
+
+    /// Milliseconds between each provider's start for parallel alarms, in priority order.
+    #[serde(default)]
+    pub alarm_stagger: u64,
+
+    /// Seconds after which an unconfirmed parallel alarm only keeps retrying its top priority provider.
+    #[serde(default)]
+    pub alarm_deadline: Option<u64>,
+
+    /// Minimum milliseconds between consecutive non-alarm sends to the same provider.
+    #[serde(default)]
+    pub send_spacing: u64,
 }
 impl Default for CommunicationsConfig {
     fn default() -> Self {
         Self {
             pushover: None,
             sms: None,
+            journal: None,
+            gpio: None,
+            recording: None,
+            stdout: None,
+            home_assistant: None,
+            pagerduty: None,
             retry_max: default_communications_retry_max(),
             retry_delay: default_communications_retry_delay(),
+            retry_jitter: 0.0,
+            theme: std::collections::BTreeMap::new(),
+            level_retry: std::collections::BTreeMap::new(),
+            init_timeout: default_communications_init_timeout(),
+            timestamp_format: TimestampFormat::default(),
+            alarm_strategy: BroadcastStrategy::default(),
+            alarm_confirmations: default_communications_alarm_confirmations(),
+            priority: Vec::new(),
+            info_providers: None,
+            fallback_providers: std::collections::HashSet::new(),
+            alarm_stagger: 0,
+            alarm_deadline: None,
+            send_spacing: 0,
         }
     }
 }
 
-#[derive(Debug, Clone, serde::Deserialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) struct LevelThemeConfig {
+    #[serde(default)]
+    pub color: Option<String>,
+
+    #[serde(default)]
+    pub emoji: Option<String>,
+
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) struct LevelRetryConfig {
+    #[serde(default)]
+    pub retry_max: Option<u64>,
+
+    #[serde(default)]
+    pub retry_delay: Option<u64>,
+}
+
+#[derive(Default, Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum TimestampFormat {
+    #[default]
+    None,
+    Absolute,
+    Relative,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum BroadcastStrategy {
+    #[default]
+    Parallel,
+    Sequential,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub(crate) struct CommunicationRecipient {
+    #[serde(serialize_with = "redact")]
     pub target: String,
 
     #[serde(default = "default_sms_recipient_level")]
     pub level: u8,
 }
 
-#[derive(Debug, Clone, serde::Deserialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub(crate) struct PushoverCommunicationConfig {
-    pub token: String,                           // REQUIRED
+    #[serde(serialize_with = "redact")]
+    pub token: String, // REQUIRED
     pub recipients: Vec<CommunicationRecipient>, // REQUIRED
 
     #[serde(default = "default_timeout")]
     pub timeout: u64,
+
+    /// Maximum recipients sent to at once, defaulting to all of them.
+    #[serde(default)]
+    pub concurrency: Option<usize>,
 }
 
-#[derive(Debug, Clone, serde::Deserialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) struct HomeAssistantCommunicationConfig {
+    pub base_url: String, // REQUIRED
+
+    #[serde(serialize_with = "redact")]
+    pub token: String, // REQUIRED
+    pub recipients: Vec<CommunicationRecipient>, // REQUIRED
+
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+
+    /// Maximum recipients sent to at once, defaulting to all of them.
+    #[serde(default)]
+    pub concurrency: Option<usize>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) struct PagerDutyCommunicationConfig {
+    #[serde(serialize_with = "redact")]
+    pub routing_key: String, // REQUIRED
+
+    #[serde(default = "default_pagerduty_level")]
+    pub level: u8,
+
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub(crate) struct SMSCommunicationConfig {
     http_base: String,                           // REQUIRED
     pub recipients: Vec<CommunicationRecipient>, // REQUIRED
 
-    #[serde(default)]
+    #[serde(default, serialize_with = "redact_option")]
     auth: Option<String>,
 
     #[serde(default)]
     certificate_path: Option<String>,
+
+    /// Normalize messages to the GSM-7 alphabet, so they aren't sent as UCS-2.
+    #[serde(default)]
+    pub gsm7: bool,
+
+    /// Skip alerts that would take more SMS segments than this to send.
+    #[serde(default)]
+    pub max_segments: Option<usize>,
+
+    /// Maximum recipients sent to at once. Sequential by default since the server queues sends.
+    #[serde(default = "default_sms_concurrency")]
+    pub concurrency: usize,
 }
 impl SMSCommunicationConfig {
-    pub fn get_sms_config(&self) -> sms_client::config::ClientConfig {
+    #[inline]
+    pub fn get_http_base(&self) -> &str {
+        &self.http_base
+    }
+
+    pub fn get_sms_config(&self) -> anyhow::Result<sms_client::config::ClientConfig> {
         let mut config = sms_client::config::ClientConfig::http_only(&self.http_base);
         if let Some(auth) = &self.auth {
             config = config.with_auth(auth);
         }
         if let Some(certificate_path) = &self.certificate_path {
-            config = config.add_tls(
-                sms_client::config::TLSConfig::new(certificate_path)
-                    .expect("Invalid SMS certificate filepath!"),
-            );
+            let tls = sms_client::config::TLSConfig::new(certificate_path)
+                .map_err(|e| anyhow::anyhow!("Invalid SMS certificate filepath: {e}"))?;
+            config = config.add_tls(tls);
         }
-        config
+        Ok(config)
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) struct JournalCommunicationConfig {
+    #[serde(default = "default_journal_level")]
+    pub level: u8,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) struct StdoutCommunicationConfig {
+    #[serde(default = "default_stdout_level")]
+    pub level: u8,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) struct RecordingCommunicationConfig {
+    pub path: std::path::PathBuf, // REQUIRED
+
+    #[serde(default = "default_recording_max_size")]
+    pub max_size: u64,
+
+    #[serde(default = "default_recording_level")]
+    pub level: u8,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) struct GpioCommunicationConfig {
+    pub pin: u8, // REQUIRED
+
+    #[serde(default = "default_gpio_duration")]
+    pub duration: u64,
+
+    #[serde(default = "default_sms_recipient_level")]
+    pub level: u8,
+}
+
+/*
+   Serialization helpers, used when exposing the effective config.
+   Secrets are always redacted, and sets are sorted to keep the output stable.
+*/
+
+const REDACTED: &str = "***";
+
+fn redact<S: serde::Serializer>(_: &str, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(REDACTED)
+}
+fn redact_option<S: serde::Serializer>(
+    value: &Option<String>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match value {
+        Some(_) => serializer.serialize_some(REDACTED),
+        None => serializer.serialize_none(),
+    }
+}
+fn serialize_sorted<S: serde::Serializer>(
+    value: &std::collections::HashSet<String>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serde::Serialize::serialize(
+        &value.iter().collect::<std::collections::BTreeSet<_>>(),
+        serializer,
+    )
+}
+fn serialize_sorted_option<S: serde::Serializer>(
+    value: &Option<std::collections::HashSet<String>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match value {
+        Some(value) => {
+            serializer.serialize_some(&value.iter().collect::<std::collections::BTreeSet<_>>())
+        }
+        None => serializer.serialize_none(),
     }
 }
 
+fn default_true() -> bool {
+    true
+}
 fn default_poll_interval() -> u64 {
     60
 }
@@ -210,9 +887,33 @@ fn default_bind_address() -> std::net::SocketAddr {
         8080,
     )
 }
+fn default_http_enqueue_timeout() -> u64 {
+    500
+}
+fn default_http_previous_auth_token_overlap() -> u64 {
+    3600
+}
+fn default_http_shed_watermark() -> f32 {
+    0.8
+}
+fn default_cctv_forwarded_fields() -> Vec<String> {
+    vec!["ExtraText".to_string(), "DateTime".to_string()]
+}
+fn default_outbound_pool_max_idle_per_host() -> usize {
+    4
+}
+fn default_outbound_pool_idle_timeout() -> u64 {
+    300
+}
+fn default_alerts_source_rate_window() -> u64 {
+    60
+}
 fn default_alarm_cooldown() -> u64 {
     300
 }
+fn default_audit_log_max_size() -> u64 {
+    10 * 1024 * 1024
+}
 fn default_alerts_send_concurrency_limit() -> usize {
     10
 }
@@ -222,12 +923,192 @@ fn default_systemctl_retry_attempts() -> u8 {
 fn default_systemctl_retry_delay() -> u64 {
     5
 }
+fn default_systemctl_retry_delay_max() -> u64 {
+    300
+}
+fn default_systemctl_stable_duration() -> u64 {
+    300
+}
+fn default_ping_default_port() -> u16 {
+    80
+}
+fn default_modem_baud_rate() -> u32 {
+    115200
+}
+fn default_modem_signal_threshold() -> u8 {
+    10
+}
 fn default_communications_retry_max() -> u64 {
     60
 }
 fn default_communications_retry_delay() -> u64 {
     60
 }
+fn default_communications_init_timeout() -> u64 {
+    60
+}
+fn default_communications_alarm_confirmations() -> usize {
+    1
+}
+fn default_sms_concurrency() -> usize {
+    1
+}
 fn default_sms_recipient_level() -> u8 {
     u8::from(&AlertLevel::Alarm)
 }
+fn default_gpio_duration() -> u64 {
+    60
+}
+fn default_pagerduty_level() -> u8 {
+    u8::from(&AlertLevel::Critical)
+}
+fn default_journal_level() -> u8 {
+    u8::from(&AlertLevel::Info)
+}
+fn default_stdout_level() -> u8 {
+    u8::from(&AlertLevel::Info)
+}
+fn default_recording_level() -> u8 {
+    u8::from(&AlertLevel::Info)
+}
+fn default_recording_max_size() -> u64 {
+    10 * 1024 * 1024
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(config: &str) -> AppConfig {
+        toml::from_str(config).unwrap()
+    }
+
+    #[test]
+    fn rejects_zero_watchdog_timeouts() {
+        let error = config("[monitors]\nwatchdog_timeouts = { ping = 300, digest = 0 }")
+            .validate()
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Config monitors.watchdog_timeouts.digest must be above zero!"
+        );
+        assert!(config("[monitors]\nwatchdog_timeouts = { ping = 1 }")
+            .validate()
+            .is_ok());
+    }
+
+    #[test]
+    fn rejects_zero_alarm_confirmations() {
+        let error = config("[communications]\nalarm_confirmations = 0")
+            .validate()
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Config communications.alarm_confirmations must be above zero!"
+        );
+        assert!(config("[communications]\nalarm_confirmations = 1")
+            .validate()
+            .is_ok());
+    }
+
+    #[test]
+    fn rejects_shed_watermarks_outside_their_range() {
+        for shed_watermark in ["0.0", "-0.5", "1.5", "nan"] {
+            let error = config(&format!("[http]\nshed_watermark = {shed_watermark}"))
+                .validate()
+                .unwrap_err();
+            assert!(error.to_string().starts_with("Config http.shed_watermark"));
+        }
+        for shed_watermark in ["0.01", "1.0"] {
+            assert!(
+                config(&format!("[http]\nshed_watermark = {shed_watermark}"))
+                    .validate()
+                    .is_ok()
+            );
+        }
+    }
+
+    #[test]
+    fn clamps_zero_delays_and_intervals_to_their_floors() {
+        let mut config = config(
+            r#"
+            [alerts]
+            source_rate_window = 0
+
+            [communications]
+            retry_delay = 0
+
+            [communications.level_retry.Info]
+            retry_delay = 0
+
+            [monitors]
+            systemctl_retry_delay = 0
+            systemctl_poll_interval = 0
+            healthcheck_interval = 0
+            digest_interval = 0
+            pings = [{ name = "router", addr = "127.0.0.1:80", level = 2, interval = 0 }]
+
+            [monitors.modem]
+            device = "/dev/ttyUSB2"
+            interval = 0
+
+            [monitors.clock]
+            interval = 0
+            "#,
+        );
+        config.clamp_limits();
+
+        assert_eq!(config.alerts.source_rate_window, 1);
+        assert_eq!(config.communications.retry_delay, 1);
+        assert_eq!(
+            config.communications.level_retry[&AlertLevel::Info].retry_delay,
+            Some(1)
+        );
+        let monitors = &config.monitors;
+        assert_eq!(monitors.systemctl_retry_delay, 1);
+        assert_eq!(monitors.systemctl_poll_interval, 1);
+        assert_eq!(monitors.healthcheck_interval, 1);
+        assert_eq!(monitors.digest_interval, Some(1));
+        assert_eq!(monitors.pings.as_ref().unwrap()[0].interval, Some(1));
+        assert_eq!(monitors.modem.as_ref().unwrap().interval, 1);
+        assert_eq!(monitors.clock.as_ref().unwrap().interval, 1);
+    }
+
+    #[test]
+    fn clamps_retry_delays_and_jitter_to_their_ceilings() {
+        let mut config = config(
+            r#"
+            [alerts]
+            alarm_cooldown = 86400
+
+            [communications]
+            retry_delay = 86400
+            retry_jitter = 2.0
+
+            [monitors]
+            systemctl_retry_delay_max = 86400
+            "#,
+        );
+        config.clamp_limits();
+
+        assert_eq!(config.alerts.alarm_cooldown, ALARM_COOLDOWN_CEILING);
+        assert_eq!(config.communications.retry_delay, RETRY_DELAY_CEILING);
+        assert_eq!(config.communications.retry_jitter, RETRY_JITTER_CEILING);
+        assert_eq!(
+            config.monitors.systemctl_retry_delay_max,
+            RETRY_DELAY_CEILING
+        );
+    }
+
+    #[test]
+    fn leaves_values_within_their_limits() {
+        let mut config = config(
+            "[alerts]\nalarm_cooldown = 0\n[communications]\nretry_delay = 30\nretry_jitter = 0.2",
+        );
+        config.clamp_limits();
+        // No cooldown at all is valid, so every alarm is sent.
+        assert_eq!(config.alerts.alarm_cooldown, 0);
+        assert_eq!(config.communications.retry_delay, 30);
+        assert_eq!(config.communications.retry_jitter, 0.2);
+    }
+}
@@ -1,28 +1,110 @@
-use crate::alerts::initialize_alert_manager;
+use crate::alerts::{initialize_alert_manager, AlertLevel};
+use crate::capabilities::Capability;
 use crate::config::AppConfig;
-use crate::monitors::spawn_monitors;
-use crate::webhooks::get_routes;
+use crate::monitors::{spawn_monitors, stop_monitors};
+use crate::webhooks::{bind_listeners, get_routes, serve};
 use anyhow::Context;
 use log::{debug, info, warn};
 
 mod alerts;
+mod audit;
+mod capabilities;
 mod communications;
 mod config;
+mod metrics;
 mod monitors;
+mod outbound;
+mod transforms;
 mod webhooks;
 
+const MONITORS_SHUTDOWN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+enum ShutdownReason {
+    NoMonitors,
+    MonitorStopped(&'static str),
+    ServerStopped,
+    Signal,
+}
+impl ShutdownReason {
+    /// A requested shutdown is expected, anything else means the system stopped itself.
+    fn alert_level(&self) -> AlertLevel {
+        match self {
+            Self::Signal => AlertLevel::Warning,
+            _ => AlertLevel::Critical,
+        }
+    }
+}
+impl std::fmt::Display for ShutdownReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoMonitors => write!(f, "no monitors are enabled, but they are required"),
+            Self::MonitorStopped(name) => write!(f, "'{name}' monitor stopped unexpectedly"),
+            Self::ServerStopped => write!(f, "HTTP server stopped unexpectedly"),
+            Self::Signal => write!(f, "received shutdown signal"),
+        }
+    }
+}
+
+/// Report what would run for the config, without starting anything or sending alerts.
+/// Monitors and providers are judged by their config alone, since creating them can
+/// claim hardware or run commands.
+fn dry_run(config: &AppConfig) -> anyhow::Result<()> {
+    info!("Dry run, nothing will be started or sent.");
+    for listener in config.http.get_listeners() {
+        let scheme = if listener.tls.is_some() {
+            "HTTPS"
+        } else {
+            "HTTP"
+        };
+        info!("{scheme} server would listen on {}", listener.bind_address);
+    }
+
+    let enabled = |capabilities: &[Capability]| {
+        capabilities
+            .iter()
+            .filter(|capability| capability.enabled)
+            .map(|capability| capability.name)
+            .collect::<Vec<_>>()
+    };
+    let capabilities = capabilities::describe(config)?;
+
+    let monitors = enabled(&capabilities.monitors);
+    if monitors.is_empty() && config.monitors.require_any {
+        anyhow::bail!("No monitors would be enabled, but they are required!");
+    } else if monitors.is_empty() {
+        warn!("No monitors would be enabled!");
+    } else {
+        info!("Enabled monitors: {}", monitors.join(", "));
+    }
+
+    let providers = enabled(&capabilities.providers);
+    if providers.is_empty() {
+        anyhow::bail!("No CommunicationProviders would be enabled!");
+    }
+    info!("Enabled providers: {}", providers.join(", "));
+    Ok(())
+}
+
 fn main() -> anyhow::Result<()> {
     dotenv::dotenv().ok();
 
     // TODO: Make into clap cli argument.
-    let config = AppConfig::load(Some("config.toml".into()))?;
+    let is_dry_run = std::env::args().skip(1).any(|arg| arg == "--dry-run");
+    let is_list = std::env::args().skip(1).any(|arg| arg == "--list");
+    let mut config = AppConfig::load(Some("config.toml".into()))?;
 
     let mut log_builder = env_logger::Builder::new();
     log_builder
         .filter_level(log::LevelFilter::Info)
         .parse_env(env_logger::Env::default());
 
-    let _guard = if let Some(ref sentry_dsn) = config.sentry.dsn {
+    // Neither a dry run nor listing starts anything, so they must never report to Sentry.
+    let sentry_dsn = config
+        .sentry
+        .dsn
+        .as_ref()
+        .filter(|_| !is_dry_run && !is_list);
+    let _guard = if let Some(sentry_dsn) = sentry_dsn {
         info!("Initializing Sentry...");
 
         // Ensure Sentry can capture error logs.
@@ -64,65 +146,146 @@ fn main() -> anyhow::Result<()> {
         log::set_boxed_logger(Box::new(logger))
             .context("Failed to set non Sentry logger as boxed logger!")?;
         log::set_max_level(log::LevelFilter::Trace);
-        warn!("Sentry DSN is unset! Not initializing.");
+        if config.sentry.dsn.is_none() {
+            warn!("Sentry DSN is unset! Not initializing.");
+        }
         None
     };
+    config.clamp_limits();
+
+    if is_dry_run {
+        return dry_run(&config);
+    }
+    if is_list {
+        print!("{}", capabilities::describe(&config)?);
+        return Ok(());
+    }
+    outbound::initialize(&config.outbound);
 
-    tokio::runtime::Builder::new_multi_thread()
+    let reason = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()?
         .block_on(async {
+            // Bind before starting anything, so a bad listener fails startup by its address.
+            let listeners = bind_listeners(get_routes(&config)).await?;
+
             // Create alarm manager task with shutdown signals.
-            let (alerts_shutdown_tx, alarm_shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+            let (alerts_shutdown_tx, alerts_shutdown_rx) = tokio::sync::oneshot::channel();
             let manager = initialize_alert_manager(&config)
                 .await
                 .expect("Failed to initialize AlertManager!");
             let manager_handle = tokio::spawn(async move {
-                tokio::select! {
-                    _ = manager.run() => warn!("AlertManager stopped unexpectedly."),
-                   _ = alarm_shutdown_rx => {}
+                if let Err(e) = manager.run(alerts_shutdown_rx).await {
+                    warn!("AlertManager stopped unexpectedly: {e:#}");
                 }
             });
 
-            // Create Warp HTTP server task with shutdown signals.
-            let (warp_shutdown_tx, warp_shutdown_rx) = tokio::sync::oneshot::channel::<()>();
-            let warp_handle = tokio::spawn(async move {
-                let (addr, server) = warp::serve(get_routes()).bind_with_graceful_shutdown(
-                    config.http.bind_address,
-                    async move {
-                        let _ = warp_shutdown_rx.await;
-                    },
-                );
-
-                info!("HTTP server listening on {addr}");
-                server.await;
+            // Create Warp HTTP server task with shutdown signals, serving every listener.
+            let (warp_shutdown_tx, warp_shutdown_rx) = tokio::sync::watch::channel(false);
+            let max_connections = config.http.max_connections;
+            let mut warp_handle = tokio::spawn(async move {
+                let servers = listeners.into_iter().map(|(listener, routes)| {
+                    serve(listener, routes, max_connections, warp_shutdown_rx.clone())
+                });
+                futures::future::join_all(servers).await;
             });
 
             // If there are monitors, create and join them.
             let ctrl_c = tokio::signal::ctrl_c();
-            let monitor_handles = spawn_monitors(&config.monitors).await;
-            if !monitor_handles.is_empty() {
-                debug!("Joining with {} monitor handle(s)!", monitor_handles.len());
+            let (monitors_shutdown_tx, monitors_shutdown_rx) = tokio::sync::watch::channel(false);
+            let mut monitor_handles = spawn_monitors(&config.monitors, monitors_shutdown_rx).await;
+            let reason = if monitor_handles.is_empty() && config.monitors.require_any {
+                ShutdownReason::NoMonitors
+            } else {
+                let monitor_stopped = async {
+                    if monitor_handles.is_empty() {
+                        debug!("There are no monitor handles!");
+                        return std::future::pending().await;
+                    }
+
+                    debug!("Joining with {} monitor handle(s)!", monitor_handles.len());
+                    let handles = monitor_handles.iter_mut().map(|(_, handle)| handle);
+                    let (_, index, _) = futures::future::select_all(handles).await;
+                    index
+                };
                 tokio::select! {
-                    _ = futures::future::select_all(monitor_handles) => warn!("A monitor has stopped unexpectedly!"),
-                    _ = ctrl_c => warn!("Received shutdown signal!")
+                    index = monitor_stopped => {
+                        let (name, _) = monitor_handles.swap_remove(index);
+                        ShutdownReason::MonitorStopped(name)
+                    },
+                    _ = &mut warp_handle => ShutdownReason::ServerStopped,
+                    _ = ctrl_c => ShutdownReason::Signal,
                 }
-            } else {
-                debug!("There are no monitor handles!");
-                let _ = ctrl_c.await;
-                warn!("Received shutdown signal!");
+            };
+            warn!("Shutting down, {reason}!");
+            sentry::capture_message(&format!("Shutting down, {reason}"), sentry::Level::Warning);
+
+            // Stop monitors first, giving them a chance to release their resources.
+            info!("Shutting down monitors...");
+            if !stop_monitors(
+                &monitors_shutdown_tx,
+                monitor_handles,
+                MONITORS_SHUTDOWN_TIMEOUT,
+            )
+            .await
+            {
+                warn!("Timed out waiting for monitors to shut down!");
             }
 
             // Send shutdown signals.
             info!("Shutting down services...");
-            let _ = alerts_shutdown_tx.send(());
-            let _ = warp_shutdown_tx.send(());
+            let _ = alerts_shutdown_tx.send((reason.to_string(), reason.alert_level()));
+            let _ = warp_shutdown_tx.send(true);
 
-            // Wait for tasks to terminate gracefully.
+            // Wait for tasks to terminate gracefully, unless the server has already.
             let _ = manager_handle.await;
-            let _ = warp_handle.await;
-        });
+            if !matches!(reason, ShutdownReason::ServerStopped) {
+                let _ = warp_handle.await;
+            }
+            Ok::<_, anyhow::Error>(reason)
+        })?;
 
+    if let ShutdownReason::NoMonitors = reason {
+        anyhow::bail!("Startup failed, {reason}!");
+    }
     info!("Finished!");
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_signals_are_expected_shutdowns() {
+        assert_eq!(ShutdownReason::Signal.alert_level(), AlertLevel::Warning);
+        for reason in [
+            ShutdownReason::NoMonitors,
+            ShutdownReason::MonitorStopped("ping"),
+            ShutdownReason::ServerStopped,
+        ] {
+            assert_eq!(reason.alert_level(), AlertLevel::Critical, "{reason}");
+        }
+    }
+
+    #[test]
+    fn describes_each_shutdown_reason() {
+        for (reason, expected) in [
+            (
+                ShutdownReason::NoMonitors,
+                "no monitors are enabled, but they are required",
+            ),
+            (
+                ShutdownReason::MonitorStopped("ping"),
+                "'ping' monitor stopped unexpectedly",
+            ),
+            (
+                ShutdownReason::ServerStopped,
+                "HTTP server stopped unexpectedly",
+            ),
+            (ShutdownReason::Signal, "received shutdown signal"),
+        ] {
+            assert_eq!(reason.to_string(), expected);
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/*
+   Basic in-process metrics for the webhook server, rendered in the Prometheus
+   text format. Routes are bucketed into a fixed set of labels so that probing
+   random paths can't grow the registry.
+*/
+
+const LATENCY_BUCKETS: [f64; 8] = [0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0];
+const KNOWN_ROUTES: &[&str] = &[
+    "/cctv",
+    "/panic",
+    "/config",
+    "/capabilities",
+    "/metrics",
+    "/monitors",
+];
+
+#[derive(Default)]
+struct LatencyHistogram {
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+#[derive(Default)]
+struct WebhookMetrics {
+    requests: std::sync::Mutex<HashMap<(&'static str, u16), u64>>,
+    latency: std::sync::Mutex<HashMap<&'static str, LatencyHistogram>>,
+    auth_failures: AtomicU64,
+}
+
+static METRICS: std::sync::LazyLock<WebhookMetrics> =
+    std::sync::LazyLock::new(WebhookMetrics::default);
+
+fn route_label(path: &str) -> &'static str {
+    KNOWN_ROUTES
+        .iter()
+        .find(|route| path == **route || path.starts_with(&format!("{route}/")))
+        .copied()
+        .unwrap_or("other")
+}
+
+/// Record a completed request, used with `warp::log::custom`.
+pub(crate) fn record_request(info: warp::log::Info) {
+    let route = route_label(info.path());
+    let elapsed = info.elapsed().as_secs_f64();
+
+    if let Ok(mut requests) = METRICS.requests.lock() {
+        *requests.entry((route, info.status().as_u16())).or_default() += 1;
+    }
+    if let Ok(mut latency) = METRICS.latency.lock() {
+        let histogram = latency.entry(route).or_default();
+        for (bucket, le) in histogram.buckets.iter_mut().zip(LATENCY_BUCKETS) {
+            if elapsed <= le {
+                *bucket += 1;
+            }
+        }
+        histogram.count += 1;
+        histogram.sum += elapsed;
+    }
+}
+
+pub(crate) fn record_auth_failure() {
+    METRICS.auth_failures.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Render all metrics in the Prometheus text exposition format.
+pub(crate) fn render() -> String {
+    let mut output = String::new();
+
+    let _ = writeln!(output, "# TYPE sentinel_http_requests_total counter");
+    if let Ok(requests) = METRICS.requests.lock() {
+        let mut requests = requests.iter().collect::<Vec<_>>();
+        requests.sort();
+        for ((route, status), count) in requests {
+            let _ = writeln!(
+                output,
+                "sentinel_http_requests_total{{route=\"{route}\",status=\"{status}\"}} {count}"
+            );
+        }
+    }
+
+    let _ = writeln!(
+        output,
+        "# TYPE sentinel_http_request_duration_seconds histogram"
+    );
+    if let Ok(latency) = METRICS.latency.lock() {
+        let mut latency = latency.iter().collect::<Vec<_>>();
+        latency.sort_by_key(|(route, _)| **route);
+        for (route, histogram) in latency {
+            for (count, le) in histogram.buckets.iter().zip(LATENCY_BUCKETS) {
+                let _ = writeln!(
+                    output,
+                    "sentinel_http_request_duration_seconds_bucket{{route=\"{route}\",le=\"{le}\"}} {count}"
+                );
+            }
+            let _ = writeln!(
+                output,
+                "sentinel_http_request_duration_seconds_bucket{{route=\"{route}\",le=\"+Inf\"}} {}",
+                histogram.count
+            );
+            let _ = writeln!(
+                output,
+                "sentinel_http_request_duration_seconds_sum{{route=\"{route}\"}} {}",
+                histogram.sum
+            );
+            let _ = writeln!(
+                output,
+                "sentinel_http_request_duration_seconds_count{{route=\"{route}\"}} {}",
+                histogram.count
+            );
+        }
+    }
+
+    // Alerts waiting for a spawn permit aren't pulled from the channel, so this covers
+    // everything that's queued but not yet being sent.
+    if let Some(len) = crate::alerts::alert_channel_len() {
+        let _ = writeln!(output, "# TYPE sentinel_alerts_queued gauge");
+        let _ = writeln!(output, "sentinel_alerts_queued {len}");
+    }
+
+    let _ = writeln!(output, "# TYPE sentinel_http_auth_failures_total counter");
+    let _ = writeln!(
+        output,
+        "sentinel_http_auth_failures_total {}",
+        METRICS.auth_failures.load(Ordering::Relaxed)
+    );
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alerts::tests::CapturedAlerts;
+    use crate::alerts::{send_alert, AlertInfo, AlertLevel};
+    use warp::Filter;
+
+    /// Get a rendered sample's value, or zero if it hasn't been recorded yet.
+    fn sample(output: &str, name: &str) -> f64 {
+        output
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(' '))
+            .map_or(0.0, |value| value.parse().unwrap())
+    }
+
+    #[tokio::test]
+    async fn requests_increment_their_route_and_status() {
+        // Other tests record requests too, so only compare routes they never use.
+        let routes = warp::path("metrics")
+            .map(|| "ok")
+            .with(warp::log::custom(record_request));
+        let requests = r#"sentinel_http_requests_total{route="/metrics",status="200"}"#;
+        let others = r#"sentinel_http_requests_total{route="other",status="404"}"#;
+        let latency = r#"sentinel_http_request_duration_seconds_count{route="/metrics"}"#;
+        let before = render();
+
+        for path in ["/metrics", "/metrics", "/wp-login.php"] {
+            warp::test::request().path(path).reply(&routes).await;
+        }
+
+        let after = render();
+        assert_eq!(sample(&after, requests) - sample(&before, requests), 2.0);
+        assert_eq!(sample(&after, others) - sample(&before, others), 1.0);
+        assert_eq!(sample(&after, latency) - sample(&before, latency), 2.0);
+    }
+
+    #[test]
+    fn auth_failures_are_counted() {
+        let name = "sentinel_http_auth_failures_total";
+        let before = sample(&render(), name);
+        record_auth_failure();
+        assert!(sample(&render(), name) > before);
+    }
+
+    #[tokio::test]
+    async fn queued_alerts_are_counted() {
+        let alerts = CapturedAlerts::new(10);
+        alerts
+            .scope(async {
+                for _ in 0..3 {
+                    let alert = AlertInfo::new(
+                        "test".to_string(),
+                        "Test alert".to_string(),
+                        AlertLevel::Warning,
+                    )
+                    .unwrap();
+                    send_alert(alert).await.unwrap();
+                }
+                assert_eq!(sample(&render(), "sentinel_alerts_queued"), 3.0);
+            })
+            .await;
+    }
+
+    #[test]
+    fn labels_known_routes() {
+        assert_eq!(route_label("/capabilities"), "/capabilities");
+        assert_eq!(route_label("/metrics"), "/metrics");
+        assert_eq!(route_label("/monitors/ping/check"), "/monitors");
+    }
+
+    #[test]
+    fn buckets_unknown_routes() {
+        assert_eq!(route_label("/"), "other");
+        assert_eq!(route_label("/capabilitiesx"), "other");
+        assert_eq!(route_label("/wp-login.php"), "other");
+    }
+}
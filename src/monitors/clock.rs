@@ -0,0 +1,197 @@
+use crate::alerts::AlertLevel;
+use crate::config::{ClockMonitorConfig, MonitorsConfig};
+use crate::monitors::{heartbeat, sleep_or_triggered, Monitor};
+use log::{debug, warn};
+
+/*
+   Check the clock is synchronized with timedatectl per interval, and optionally
+   that chrony's offset is within a threshold. A Pi without an RTC can drift badly
+   after a reboot without network, breaking alert timestamps and TLS validation.
+*/
+
+#[derive(Debug, Clone, PartialEq)]
+enum ClockState {
+    Synced,
+    Unsynced,
+    Drifted(f64),
+}
+
+pub(crate) struct ClockMonitor {
+    config: ClockMonitorConfig,
+}
+impl ClockMonitor {
+    fn is_available(program: &str) -> bool {
+        std::process::Command::new(program)
+            .arg("--version")
+            .output()
+            .is_ok_and(|output| output.status.success())
+    }
+
+    /// Parse the `NTPSynchronized` property value, which is either "yes" or "no".
+    fn parse_synchronized(output: &str) -> Option<bool> {
+        match output.trim() {
+            "yes" => Some(true),
+            "no" => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Parse the absolute system time offset in seconds from `chronyc -c tracking`,
+    /// which is the fifth comma separated field.
+    fn parse_offset(output: &str) -> Option<f64> {
+        let offset = output.trim().split(',').nth(4)?.parse::<f64>().ok()?;
+        Some(offset.abs())
+    }
+
+    async fn query_synchronized() -> anyhow::Result<bool> {
+        let output = tokio::process::Command::new("timedatectl")
+            .args(["show", "--property=NTPSynchronized", "--value"])
+            .output()
+            .await?;
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        Self::parse_synchronized(&stdout)
+            .ok_or_else(|| anyhow::anyhow!("Invalid timedatectl output: {stdout:?}"))
+    }
+
+    async fn query_offset() -> anyhow::Result<f64> {
+        let output = tokio::process::Command::new("chronyc")
+            .args(["-c", "tracking"])
+            .output()
+            .await?;
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        Self::parse_offset(&stdout)
+            .ok_or_else(|| anyhow::anyhow!("Invalid chronyc output: {stdout:?}"))
+    }
+
+    /// Returns None if the state couldn't be checked, so an unknown result never alerts.
+    async fn check_state(&self) -> Option<ClockState> {
+        match Self::query_synchronized().await {
+            Ok(true) => {}
+            Ok(false) => return Some(ClockState::Unsynced),
+            Err(e) => {
+                warn!("Failed to query clock synchronization: {e:#?}");
+                return None;
+            }
+        }
+
+        let Some(max_offset) = self.config.max_offset else {
+            return Some(ClockState::Synced);
+        };
+        match Self::query_offset().await {
+            Ok(offset) if offset > max_offset => Some(ClockState::Drifted(offset)),
+            Ok(offset) => {
+                debug!("Clock offset is {offset}s.");
+                Some(ClockState::Synced)
+            }
+            Err(e) => {
+                warn!("Failed to query clock offset: {e:#?}");
+                None
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Monitor for ClockMonitor {
+    fn name() -> &'static str {
+        "clock"
+    }
+
+    fn description() -> &'static str {
+        "Checks the clock is NTP synchronized, and optionally its chrony offset"
+    }
+
+    fn required_config() -> &'static [&'static str] {
+        &["monitors.clock"]
+    }
+
+    fn from_config(config: &MonitorsConfig) -> anyhow::Result<Self> {
+        let config = config
+            .clock
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Missing clock!"))?
+            .clone();
+
+        if !Self::is_available("timedatectl") {
+            anyhow::bail!("The timedatectl command is unavailable!");
+        }
+        if config.max_offset.is_some() && !Self::is_available("chronyc") {
+            anyhow::bail!("The chronyc command is unavailable to check the max_offset!");
+        }
+
+        Ok(Self { config })
+    }
+
+    async fn run(&mut self) -> anyhow::Result<()> {
+        let mut state = ClockState::Synced;
+        let interval = std::time::Duration::from_secs(self.config.interval);
+
+        debug!(
+            "Started with an interval of {} seconds!",
+            self.config.interval
+        );
+        loop {
+            let current = self.check_state().await;
+            heartbeat();
+
+            // Only alert on entering or leaving an unsynchronized state.
+            if let Some(current) = current {
+                let message = match (&state, &current) {
+                    (ClockState::Synced, ClockState::Synced)
+                    | (ClockState::Unsynced, ClockState::Unsynced)
+                    | (ClockState::Drifted(_), ClockState::Drifted(_)) => None,
+                    (_, ClockState::Unsynced) => Some("Clock is not synchronized!".to_string()),
+                    (_, ClockState::Drifted(offset)) => Some(format!(
+                        "Clock is {offset:.1}s out, over the {}s threshold!",
+                        self.config.max_offset.unwrap_or_default()
+                    )),
+                    (_, ClockState::Synced) => Some("Clock is synchronized again!".to_string()),
+                };
+
+                let recovered = current == ClockState::Synced;
+                state = current;
+                if let Some(message) = message {
+                    debug!("{message}");
+                    if recovered {
+                        Self::send_recovery_alert(message, AlertLevel::Warning).await?;
+                    } else {
+                        Self::send_problem_alert(message, AlertLevel::Warning).await?;
+                    }
+                }
+            }
+
+            sleep_or_triggered(interval).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_synchronized_status() {
+        assert_eq!(ClockMonitor::parse_synchronized("yes\n"), Some(true));
+        assert_eq!(ClockMonitor::parse_synchronized("no\n"), Some(false));
+        assert_eq!(ClockMonitor::parse_synchronized(""), None);
+        assert_eq!(
+            ClockMonitor::parse_synchronized("Failed to connect to bus"),
+            None
+        );
+    }
+
+    #[test]
+    fn parses_chrony_offsets() {
+        let synced = "A9FEA9FE,169.254.169.254,3,1760400000.123456789,-0.000012345,0.000023456,0.000034567,-12.345,-0.001,0.010,0.000456,0.000789,64.2,Normal\n";
+        let drifted = "00000000,,0,0.000000000,-42.500000000,0.000000000,0.000000000,0.000,0.000,0.000,1.000000000,1.000000000,0.0,Not synchronised\n";
+        let synced_offset = ClockMonitor::parse_offset(synced).unwrap();
+        assert!((synced_offset - 0.000012345).abs() < f64::EPSILON);
+        assert_eq!(ClockMonitor::parse_offset(drifted), Some(42.5));
+        assert_eq!(
+            ClockMonitor::parse_offset("506 Cannot talk to daemon"),
+            None
+        );
+    }
+}
@@ -0,0 +1,146 @@
+use crate::alerts::{display_name, take_alert_counts, AlertLevel};
+use crate::config::MonitorsConfig;
+use crate::monitors::{heartbeat, running_monitors, sleep_or_triggered, Monitor};
+use log::debug;
+
+/*
+   Periodically send an Info digest of uptime, running monitors and alert counts, so users
+   know alerting itself is alive, since a silent system is otherwise indistinguishable
+   from a broken one.
+*/
+
+pub(crate) struct DigestMonitor {
+    interval: std::time::Duration,
+    started: tokio::time::Instant,
+}
+impl DigestMonitor {
+    fn format_uptime(uptime: std::time::Duration) -> String {
+        let secs = uptime.as_secs();
+        match secs {
+            0..3600 => format!("{}m", secs / 60),
+            3600..86400 => format!("{}h {}m", secs / 3600, (secs % 3600) / 60),
+            _ => format!("{}d {}h", secs / 86400, (secs % 86400) / 3600),
+        }
+    }
+
+    fn build_message(&self, [warning, critical, alarm]: [u64; 3], monitors: &[&str]) -> String {
+        let uptime = Self::format_uptime(self.started.elapsed());
+        let monitors = monitors
+            .iter()
+            .map(|name| display_name(name).unwrap_or(name))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        if warning + critical + alarm == 0 {
+            format!(
+                "All systems normal, up {uptime} running {monitors}. No alerts since the last digest."
+            )
+        } else {
+            format!(
+                "Up {uptime} running {monitors}. Since the last digest: {alarm} alarm, {critical} critical, {warning} warning alert(s)."
+            )
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Monitor for DigestMonitor {
+    fn name() -> &'static str {
+        "digest"
+    }
+
+    fn description() -> &'static str {
+        "Sends a periodic digest so a silent system is known to be alive"
+    }
+
+    fn required_config() -> &'static [&'static str] {
+        &["monitors.digest_interval"]
+    }
+
+    fn from_config(config: &MonitorsConfig) -> anyhow::Result<Self> {
+        let interval = config
+            .digest_interval
+            .ok_or_else(|| anyhow::anyhow!("Missing digest_interval!"))?;
+        if interval == 0 {
+            anyhow::bail!("The digest_interval must be greater than zero!");
+        }
+
+        Ok(Self {
+            interval: std::time::Duration::from_secs(interval),
+            started: tokio::time::Instant::now(),
+        })
+    }
+
+    async fn run(&mut self) -> anyhow::Result<()> {
+        debug!(
+            "Started with an interval of {} seconds!",
+            self.interval.as_secs()
+        );
+        loop {
+            sleep_or_triggered(self.interval).await;
+            heartbeat();
+
+            let message = self.build_message(take_alert_counts(), &running_monitors());
+            debug!("{message}");
+            Self::send_alert(message, AlertLevel::Info).await?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alerts::tests::CapturedAlerts;
+
+    fn monitor() -> DigestMonitor {
+        DigestMonitor::from_config(&toml::from_str("digest_interval = 3600").unwrap()).unwrap()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn summarizes_a_quiet_period() {
+        let monitor = monitor();
+        tokio::time::sleep(std::time::Duration::from_secs(90000)).await;
+        assert_eq!(
+            monitor.build_message([0, 0, 0], &["digest", "ping"]),
+            "All systems normal, up 1d 1h running digest, ping. No alerts since the last digest."
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn summarizes_alerts_since_the_last_digest() {
+        let monitor = monitor();
+        tokio::time::sleep(std::time::Duration::from_secs(5400)).await;
+        assert_eq!(
+            monitor.build_message([3, 2, 1], &["digest"]),
+            "Up 1h 30m running digest. Since the last digest: 1 alarm, 2 critical, 3 warning alert(s)."
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn fires_every_interval() {
+        let mut monitor = monitor();
+        let alerts = CapturedAlerts::new(10);
+        let run = alerts.scope(monitor.run());
+        tokio::pin!(run);
+
+        for (secs, expected) in [(3599, 0), (2, 1), (3600, 1)] {
+            let _ = tokio::time::timeout(std::time::Duration::from_secs(secs), run.as_mut()).await;
+            let digests = alerts.take();
+            assert_eq!(digests.len(), expected, "after {secs}s");
+            for digest in digests {
+                assert_eq!(digest.level, AlertLevel::Info);
+                assert_eq!(digest.source, "digest monitor");
+            }
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn waits_longer_than_the_watchdog_without_restarts() {
+        let restarts = crate::monitors::tests::watchdog_restarts(
+            monitor(),
+            std::time::Duration::from_secs(7300),
+        )
+        .await;
+        assert_eq!(restarts, 0);
+    }
+}
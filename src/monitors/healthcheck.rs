@@ -1,5 +1,7 @@
 use crate::config::MonitorsConfig;
-use crate::monitors::Monitor;
+use crate::monitors::{heartbeat, sleep_or_triggered, Monitor};
+use crate::outbound::{client_builder, ensure_host_allowed, send_with_reset_retry};
+use anyhow::Context;
 use log::{debug, warn};
 
 /*
@@ -18,16 +20,26 @@ impl Monitor for HealthcheckMonitor {
         "healthcheck"
     }
 
+    fn description() -> &'static str {
+        "Sends a GET request to a healthcheck URL each interval"
+    }
+
+    fn required_config() -> &'static [&'static str] {
+        &["monitors.healthcheck"]
+    }
+
     fn from_config(config: &MonitorsConfig) -> anyhow::Result<Self> {
         let url = config
             .healthcheck
             .as_ref()
             .ok_or_else(|| anyhow::anyhow!("Missing healthcheck!"))?
             .clone();
+        ensure_host_allowed(&url)?;
 
-        // TODO: Add timeout to client via builder. See PushoverCommunicationProvider.
         Ok(HealthcheckMonitor {
-            client: reqwest::Client::new(),
+            client: client_builder()
+                .build()
+                .context("Failed to build healthcheck HTTP client!")?,
             interval: config.healthcheck_interval,
             url,
         })
@@ -38,8 +50,15 @@ impl Monitor for HealthcheckMonitor {
 
         debug!("Started with an interval of {} seconds!", self.interval);
         loop {
+            heartbeat();
             let mut current_interval = self.interval;
-            match self.client.get(&self.url).send().await {
+            let result = match ensure_host_allowed(&self.url) {
+                Ok(()) => send_with_reset_retry(self.client.get(&self.url))
+                    .await
+                    .map_err(Into::into),
+                Err(e) => Err(e),
+            };
+            match result {
                 Ok(response) => {
                     if response.status().is_success() {
                         debug!("Successfully sent update!");
@@ -55,7 +74,7 @@ impl Monitor for HealthcheckMonitor {
             }
 
             // Use a shorter interval when there's an error.
-            tokio::time::sleep(std::time::Duration::from_secs(current_interval)).await;
+            sleep_or_triggered(std::time::Duration::from_secs(current_interval)).await;
         }
     }
 }
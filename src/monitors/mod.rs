@@ -1,17 +1,28 @@
+mod clock;
+mod digest;
 mod healthcheck;
+mod modem;
 mod ping;
 mod power;
+mod self_test;
 mod systemctl;
 
-use crate::alerts::{send_alert, AlertInfo, AlertLevel};
+use crate::alerts::{display_name, send_alert, AlertInfo, AlertLevel};
+use crate::capabilities::Capability;
 use crate::config::MonitorsConfig;
-use log::{error, info, warn};
+use log::{debug, error, info, warn};
 
 #[async_trait::async_trait]
 pub(crate) trait Monitor: Send + Sync + 'static {
     /// Returns the static monitor name for logging.
     fn name() -> &'static str;
 
+    /// Short description of what the monitor checks.
+    fn description() -> &'static str;
+
+    /// Dotted config keys that must all be set for the monitor to be enabled.
+    fn required_config() -> &'static [&'static str];
+
     /// Creates a new monitor instance with given configuration.
     /// Implementations can override this for custom initialization.
     /// If None is returned, the monitor is not run.
@@ -23,29 +34,257 @@ pub(crate) trait Monitor: Send + Sync + 'static {
     /// The monitor is always restarted after any return value.
     async fn run(&mut self) -> anyhow::Result<()>;
 
+    /// Release any held resources when the monitor is stopped by a graceful shutdown.
+    async fn on_shutdown(&mut self) {}
+
+    /// Create an alert with the monitors name as the source.
+    fn create_alert(message: String, level: AlertLevel) -> anyhow::Result<AlertInfo> {
+        let name = Self::name();
+        let source = display_name(name)
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("{name} monitor"));
+        AlertInfo::new(source, message, level)
+    }
+
     /// Helper method to send alerts with the monitors name as the source.
     async fn send_alert(message: String, level: AlertLevel) -> anyhow::Result<()> {
-        let name = Self::name().to_string();
-        let alert = AlertInfo::new(format!("{name} monitor"), message, level)?;
+        send_alert(Self::create_alert(message, level)?).await
+    }
+
+    /// Helper method to send a problem alert that's followed by a recovery alert once it clears.
+    async fn send_problem_alert(message: String, level: AlertLevel) -> anyhow::Result<()> {
+        let mut alert = Self::create_alert(message, level)?;
+        alert.metadata.recoverable = true;
+        send_alert(alert).await
+    }
+
+    /// Helper method to send an alert that a previously alerted problem has cleared.
+    async fn send_recovery_alert(message: String, level: AlertLevel) -> anyhow::Result<()> {
+        let mut alert = Self::create_alert(message, level)?;
+        alert.metadata.recovery = true;
+        send_alert(alert).await
+    }
+
+    /// Helper method to send an alert about one of the monitor's targets, so its
+    /// recovery is only matched with that target's problem alerts.
+    async fn send_subject_alert(
+        subject: &str,
+        message: String,
+        level: AlertLevel,
+        recovery: bool,
+    ) -> anyhow::Result<()> {
+        let mut alert = Self::create_alert(message, level)?;
+        alert.metadata.subject = Some(subject.to_string());
+        alert.metadata.recovery = recovery;
+        alert.metadata.recoverable = !recovery;
         send_alert(alert).await
     }
 }
 
-async fn run_monitor<T: Monitor>(mut monitor: T) {
+tokio::task_local! {
+    static HEARTBEAT: std::sync::Arc<std::sync::atomic::AtomicU64>;
+    static TRIGGER: std::sync::Arc<tokio::sync::Notify>;
+}
+
+static TRIGGERS: std::sync::OnceLock<
+    std::collections::HashMap<&'static str, std::sync::Arc<tokio::sync::Notify>>,
+> = std::sync::OnceLock::new();
+
+/// Wake the named monitor to run its check immediately, returning false if it isn't running.
+/// A monitor that's already mid-check ignores the trigger, since it's checking anyway.
+pub(crate) fn trigger_check(name: &str) -> bool {
+    match TRIGGERS.get().and_then(|triggers| triggers.get(name)) {
+        Some(trigger) => {
+            trigger.notify_waiters();
+            true
+        }
+        None => false,
+    }
+}
+
+/// Names of the running monitors, sorted, which is empty until they're spawned.
+pub(crate) fn running_monitors() -> Vec<&'static str> {
+    let mut names: Vec<_> = TRIGGERS
+        .get()
+        .map(|triggers| triggers.keys().copied().collect())
+        .unwrap_or_default();
+    names.sort_unstable();
+    names
+}
+
+/// Resolve once a check is triggered for the current monitor, or never outside of one.
+pub(crate) async fn triggered() {
+    match TRIGGER.try_with(std::sync::Arc::clone) {
+        Ok(trigger) => {
+            trigger.notified().await;
+            debug!("Check triggered early!");
+        }
+        Err(_) => std::future::pending().await,
+    }
+}
+
+/// Sleep between checks while heartbeating, waking early if a check is triggered.
+pub(crate) async fn sleep_or_triggered(duration: std::time::Duration) {
+    wait_with_heartbeat(async {
+        tokio::select! {
+            _ = tokio::time::sleep(duration) => {},
+            _ = triggered() => {},
+        }
+    })
+    .await
+}
+
+/// Signal that the current monitor is still making progress, which must be called
+/// at least once per watchdog timeout for monitors that have a watchdog configured.
+pub(crate) fn heartbeat() {
+    let _ = HEARTBEAT.try_with(|heartbeat| {
+        heartbeat.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    });
+}
+
+// Short enough that even a one second watchdog sees a heartbeat within every timeout.
+const HEARTBEAT_SLEEP_SLICE: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Wait for the future while still heartbeating, for deliberate waits between checks
+/// that may outlast the watchdog timeout. Checks themselves must not be wrapped.
+pub(crate) async fn wait_with_heartbeat<F: std::future::Future>(future: F) -> F::Output {
+    tokio::pin!(future);
+    loop {
+        heartbeat();
+        if let Ok(output) = tokio::time::timeout(HEARTBEAT_SLEEP_SLICE, future.as_mut()).await {
+            return output;
+        }
+    }
+}
+
+/// Sleep while still heartbeating, for deliberate waits that may outlast the watchdog timeout.
+pub(crate) async fn sleep_with_heartbeat(duration: std::time::Duration) {
+    wait_with_heartbeat(tokio::time::sleep(duration)).await
+}
+
+/// Spawn a task that shares the current monitor's heartbeat and check trigger.
+pub(crate) fn spawn_with_heartbeat<F>(future: F) -> tokio::task::JoinHandle<F::Output>
+where
+    F: std::future::Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let heartbeat = HEARTBEAT.try_with(std::sync::Arc::clone);
+    let trigger = TRIGGER.try_with(std::sync::Arc::clone);
+    match (heartbeat, trigger) {
+        (Ok(heartbeat), Ok(trigger)) => {
+            tokio::spawn(HEARTBEAT.scope(heartbeat, TRIGGER.scope(trigger, future)))
+        }
+        _ => tokio::spawn(future),
+    }
+}
+
+/// Resolve once there have been no heartbeats for the timeout, or never without one.
+async fn watch_heartbeat(
+    heartbeat: &std::sync::atomic::AtomicU64,
+    timeout: Option<std::time::Duration>,
+) {
+    let Some(timeout) = timeout else {
+        return std::future::pending().await;
+    };
+
+    let mut last = heartbeat.load(std::sync::atomic::Ordering::Relaxed);
+    loop {
+        tokio::time::sleep(timeout).await;
+        let current = heartbeat.load(std::sync::atomic::Ordering::Relaxed);
+        if current == last {
+            return;
+        }
+        last = current;
+    }
+}
+
+const ANOMALY_BACKOFF_MIN: std::time::Duration = std::time::Duration::from_secs(5);
+const ANOMALY_BACKOFF_MAX: std::time::Duration = std::time::Duration::from_secs(300);
+
+async fn run_monitor<T: Monitor>(
+    mut monitor: T,
+    mut shutdown: tokio::sync::watch::Receiver<bool>,
+    watchdog: Option<std::time::Duration>,
+    expects_completion: bool,
+    trigger: std::sync::Arc<tokio::sync::Notify>,
+) {
     let name = T::name();
     info!("Starting '{name}' monitor!");
     loop {
+        let heartbeat = std::sync::Arc::new(std::sync::atomic::AtomicU64::new(0));
+        let supervised = TRIGGER.scope(
+            trigger.clone(),
+            supervise_monitor(&mut monitor, expects_completion),
+        );
+        let is_hung = tokio::select! {
+            _ = HEARTBEAT.scope(heartbeat.clone(), supervised) => false,
+            _ = watch_heartbeat(&heartbeat, watchdog) => true,
+            _ = shutdown.wait_for(|shutdown| *shutdown) => false,
+        };
+        if !is_hung {
+            break;
+        }
+
+        // The hung run was dropped, but any tasks it spawned must be stopped too.
+        warn!("Monitor '{name}' has no heartbeat, restarting!");
+        monitor.on_shutdown().await;
+        if let Err(e) = T::send_alert(
+            "Monitor stopped responding, restarting!".to_string(),
+            AlertLevel::Warning,
+        )
+        .await
+        {
+            error!("Failed to send '{name}' monitor watchdog alert: {e:#?}");
+        }
+    }
+
+    info!("Shutting down '{name}' monitor!");
+    monitor.on_shutdown().await;
+}
+
+/// Run the monitor forever. Monitors are assumed to loop forever, so unless completion is
+/// expected, an Ok return is treated as an anomaly and alerted with backoff.
+async fn supervise_monitor<T: Monitor>(monitor: &mut T, expects_completion: bool) {
+    let name = T::name();
+    let mut backoff = ANOMALY_BACKOFF_MIN;
+    loop {
+        let started = tokio::time::Instant::now();
         match monitor.run().await {
-            Ok(_) => info!("Restarting '{name}' monitor!"),
+            Ok(_) if expects_completion => info!("Restarting '{name}' monitor!"),
+            Ok(_) => {
+                // Only keep backing off if the monitor keeps returning quickly.
+                if started.elapsed() > ANOMALY_BACKOFF_MAX {
+                    backoff = ANOMALY_BACKOFF_MIN;
+                }
+
+                warn!(
+                    "Monitor '{name}' returned unexpectedly, restarting in {}s!",
+                    backoff.as_secs()
+                );
+                if let Err(e) = T::send_alert(
+                    format!(
+                        "Monitor returned unexpectedly, restarting in {}s!",
+                        backoff.as_secs()
+                    ),
+                    AlertLevel::Warning,
+                )
+                .await
+                {
+                    error!("Failed to send '{name}' monitor anomaly alert: {e:#?}");
+                }
+
+                tokio::time::sleep(backoff).await;
+                backoff = std::cmp::min(backoff * 2, ANOMALY_BACKOFF_MAX);
+            }
             Err(e) => error!("Error in '{name}' monitor: {e:#?}"),
         }
     }
 }
 
-fn try_from_config<T: Monitor>(
+fn create_monitor<T: Monitor>(
     config: &MonitorsConfig,
     disabled_monitors: Option<&std::collections::HashSet<String>>,
-) -> Option<tokio::task::JoinHandle<()>> {
+) -> Option<T> {
     let name = T::name();
     if let Some(disabled_monitors) = disabled_monitors {
         if disabled_monitors.contains(name) {
@@ -53,9 +292,15 @@ fn try_from_config<T: Monitor>(
             return None;
         }
     }
+    if let Some(enabled_monitors) = &config.enabled {
+        if !enabled_monitors.contains(name) {
+            debug!("Monitor '{name}' isn't in the enabled monitors, skipping.");
+            return None;
+        }
+    }
 
     match T::from_config(config) {
-        Ok(monitor) => Some(tokio::spawn(run_monitor(monitor))),
+        Ok(monitor) => Some(monitor),
         Err(e) => {
             warn!("Monitor '{name}' failed to initialize: {e:?}");
             None
@@ -63,14 +308,479 @@ fn try_from_config<T: Monitor>(
     }
 }
 
-pub(crate) async fn spawn_monitors(config: &MonitorsConfig) -> Vec<tokio::task::JoinHandle<()>> {
-    let disabled_monitors = config.disabled.as_ref();
+fn try_from_config<T: Monitor>(
+    config: &MonitorsConfig,
+    disabled_monitors: Option<&std::collections::HashSet<String>>,
+    shutdown: &tokio::sync::watch::Receiver<bool>,
+    triggers: &mut std::collections::HashMap<&'static str, std::sync::Arc<tokio::sync::Notify>>,
+) -> Option<(&'static str, tokio::task::JoinHandle<()>)> {
+    let monitor = create_monitor::<T>(config, disabled_monitors)?;
+    let name = T::name();
+    let watchdog = config
+        .watchdog_timeouts
+        .get(name)
+        .map(|timeout| std::time::Duration::from_secs(*timeout));
+
+    let expects_completion = config.completion_expected.contains(name);
+
+    let trigger = std::sync::Arc::new(tokio::sync::Notify::new());
+    triggers.insert(name, trigger.clone());
+    let handle = tokio::spawn(run_monitor(
+        monitor,
+        shutdown.clone(),
+        watchdog,
+        expects_completion,
+        trigger,
+    ));
+    Some((name, handle))
+}
+
+/// Whether the monitor passes the disabled and enabled lists, ignoring its own config.
+fn is_selected(config: &MonitorsConfig, name: &str) -> bool {
+    config
+        .disabled
+        .as_ref()
+        .is_none_or(|disabled| !disabled.contains(name))
+        && config
+            .enabled
+            .as_ref()
+            .is_none_or(|enabled| enabled.contains(name))
+}
+
+fn describe<T: Monitor>(config: &MonitorsConfig, serialized: &serde_json::Value) -> Capability {
+    let mut capability = Capability::new(
+        T::name(),
+        T::description(),
+        T::required_config(),
+        serialized,
+    );
+    capability.enabled &= is_selected(config, T::name());
+    capability
+}
+
+/// Describe every monitor, without creating any of them.
+pub(crate) fn describe_monitors(
+    config: &MonitorsConfig,
+    serialized: &serde_json::Value,
+) -> Vec<Capability> {
     vec![
-        try_from_config::<ping::PingMonitor>(config, disabled_monitors),
-        try_from_config::<healthcheck::HealthcheckMonitor>(config, disabled_monitors),
-        try_from_config::<systemctl::SystemctlMonitor>(config, disabled_monitors),
+        describe::<ping::PingMonitor>(config, serialized),
+        describe::<healthcheck::HealthcheckMonitor>(config, serialized),
+        describe::<systemctl::SystemctlMonitor>(config, serialized),
+        describe::<modem::ModemMonitor>(config, serialized),
+        describe::<clock::ClockMonitor>(config, serialized),
+        describe::<self_test::SelfTestMonitor>(config, serialized),
+        describe::<digest::DigestMonitor>(config, serialized),
+    ]
+}
+
+/// Spawn all enabled monitors by name, which run until the shutdown signal is set.
+pub(crate) async fn spawn_monitors(
+    config: &MonitorsConfig,
+    shutdown: tokio::sync::watch::Receiver<bool>,
+) -> Vec<(&'static str, tokio::task::JoinHandle<()>)> {
+    let disabled = config.disabled.as_ref();
+    let mut triggers = std::collections::HashMap::new();
+    let handles = vec![
+        try_from_config::<ping::PingMonitor>(config, disabled, &shutdown, &mut triggers),
+        try_from_config::<healthcheck::HealthcheckMonitor>(
+            config,
+            disabled,
+            &shutdown,
+            &mut triggers,
+        ),
+        try_from_config::<systemctl::SystemctlMonitor>(config, disabled, &shutdown, &mut triggers),
+        try_from_config::<modem::ModemMonitor>(config, disabled, &shutdown, &mut triggers),
+        try_from_config::<clock::ClockMonitor>(config, disabled, &shutdown, &mut triggers),
+        try_from_config::<self_test::SelfTestMonitor>(config, disabled, &shutdown, &mut triggers),
+        try_from_config::<digest::DigestMonitor>(config, disabled, &shutdown, &mut triggers),
     ]
     .into_iter()
     .flatten()
-    .collect()
+    .collect::<Vec<_>>();
+
+    let names: Vec<_> = handles.iter().map(|(name, _)| *name).collect();
+    info!("Running monitors: [{}]", names.join(", "));
+    if TRIGGERS.set(triggers).is_err() {
+        warn!("Monitor check triggers were already set!");
+    }
+    handles
+}
+
+/// Signal every monitor to shut down, waiting up to the timeout for them to release
+/// their resources. Returns false if any were still shutting down at the timeout.
+pub(crate) async fn stop_monitors(
+    shutdown: &tokio::sync::watch::Sender<bool>,
+    handles: Vec<(&'static str, tokio::task::JoinHandle<()>)>,
+    timeout: std::time::Duration,
+) -> bool {
+    let _ = shutdown.send(true);
+    tokio::time::timeout(
+        timeout,
+        futures::future::join_all(handles.into_iter().map(|(_, handle)| handle)),
+    )
+    .await
+    .is_ok()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Monitor whose run returns Ok shortly after starting.
+    struct ReturningMonitor {
+        runs: Arc<AtomicUsize>,
+    }
+
+    #[async_trait::async_trait]
+    impl Monitor for ReturningMonitor {
+        fn name() -> &'static str {
+            "returning"
+        }
+
+        fn description() -> &'static str {
+            "Returns from every run"
+        }
+
+        fn required_config() -> &'static [&'static str] {
+            &[]
+        }
+
+        fn from_config(_config: &MonitorsConfig) -> anyhow::Result<Self> {
+            anyhow::bail!("ReturningMonitor can't be configured!")
+        }
+
+        async fn run(&mut self) -> anyhow::Result<()> {
+            self.runs.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            Ok(())
+        }
+    }
+
+    async fn supervised_runs(expects_completion: bool, duration: std::time::Duration) -> usize {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let mut monitor = ReturningMonitor { runs: runs.clone() };
+        let _ = tokio::time::timeout(
+            duration,
+            supervise_monitor(&mut monitor, expects_completion),
+        )
+        .await;
+        runs.load(Ordering::SeqCst)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn ok_return_backs_off_as_an_anomaly() {
+        // First restart waits the minimum backoff, the second twice that.
+        assert_eq!(supervised_runs(false, ANOMALY_BACKOFF_MIN / 2).await, 1);
+        assert_eq!(
+            supervised_runs(false, ANOMALY_BACKOFF_MIN * 2).await,
+            2,
+            "Backoff should double after the first restart"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn expected_completion_restarts_immediately() {
+        assert!(supervised_runs(true, ANOMALY_BACKOFF_MIN / 2).await > 10);
+    }
+
+    struct AliasedMonitor;
+
+    #[async_trait::async_trait]
+    impl Monitor for AliasedMonitor {
+        fn name() -> &'static str {
+            "aliased"
+        }
+
+        fn description() -> &'static str {
+            "Has a display name"
+        }
+
+        fn required_config() -> &'static [&'static str] {
+            &[]
+        }
+
+        fn from_config(_config: &MonitorsConfig) -> anyhow::Result<Self> {
+            anyhow::bail!("AliasedMonitor can't be configured!")
+        }
+
+        async fn run(&mut self) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn alerts_use_the_display_name_as_their_source() {
+        crate::alerts::tests::set_display_names();
+        let alerts = crate::alerts::tests::CapturedAlerts::new(2);
+        alerts
+            .scope(async {
+                AliasedMonitor::send_alert("Sensor offline".to_string(), AlertLevel::Warning)
+                    .await
+                    .unwrap();
+                ReturningMonitor::send_alert("Sensor offline".to_string(), AlertLevel::Warning)
+                    .await
+                    .unwrap();
+            })
+            .await;
+
+        let alerts = alerts.take();
+        assert_eq!(alerts[0].to_string(), "Friendly Monitor - Sensor offline");
+        assert_eq!(alerts[1].to_string(), "returning monitor - Sensor offline");
+    }
+
+    /// Monitor whose run never returns, either heartbeating or hung.
+    #[derive(Default)]
+    struct WaitingMonitor {
+        runs: Arc<AtomicUsize>,
+        shutdowns: Arc<AtomicUsize>,
+        heartbeats: bool,
+        hangs_on_shutdown: bool,
+    }
+
+    #[async_trait::async_trait]
+    impl Monitor for WaitingMonitor {
+        fn name() -> &'static str {
+            "waiting"
+        }
+
+        fn description() -> &'static str {
+            "Waits forever"
+        }
+
+        fn required_config() -> &'static [&'static str] {
+            &[]
+        }
+
+        fn from_config(_config: &MonitorsConfig) -> anyhow::Result<Self> {
+            anyhow::bail!("WaitingMonitor can't be configured!")
+        }
+
+        async fn run(&mut self) -> anyhow::Result<()> {
+            self.runs.fetch_add(1, Ordering::SeqCst);
+            if self.heartbeats {
+                sleep_with_heartbeat(std::time::Duration::from_secs(3600)).await;
+            } else {
+                std::future::pending::<()>().await;
+            }
+            Ok(())
+        }
+
+        async fn on_shutdown(&mut self) {
+            self.shutdowns.fetch_add(1, Ordering::SeqCst);
+            if self.hangs_on_shutdown {
+                std::future::pending::<()>().await;
+            }
+        }
+    }
+
+    async fn watched_runs(heartbeats: bool) -> usize {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let monitor = WaitingMonitor {
+            runs: runs.clone(),
+            heartbeats,
+            ..Default::default()
+        };
+        let (_shutdown, receiver) = tokio::sync::watch::channel(false);
+        let _ = tokio::time::timeout(
+            std::time::Duration::from_secs(35),
+            run_monitor(
+                monitor,
+                receiver,
+                Some(std::time::Duration::from_secs(10)),
+                false,
+                std::sync::Arc::new(tokio::sync::Notify::new()),
+            ),
+        )
+        .await;
+        runs.load(Ordering::SeqCst)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn watchdog_restarts_a_hung_monitor() {
+        assert_eq!(watched_runs(false).await, 4);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn sleeping_with_heartbeat_outlasts_the_watchdog() {
+        assert_eq!(watched_runs(true).await, 1);
+    }
+
+    fn spawn_waiting(
+        monitor: WaitingMonitor,
+        shutdown: &tokio::sync::watch::Sender<bool>,
+    ) -> (&'static str, tokio::task::JoinHandle<()>) {
+        let handle = tokio::spawn(run_monitor(
+            monitor,
+            shutdown.subscribe(),
+            None,
+            false,
+            std::sync::Arc::new(tokio::sync::Notify::new()),
+        ));
+        (WaitingMonitor::name(), handle)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn shutdown_cancels_the_run_and_releases_resources() {
+        let monitor = WaitingMonitor::default();
+        let (runs, shutdowns) = (monitor.runs.clone(), monitor.shutdowns.clone());
+        let (shutdown, _) = tokio::sync::watch::channel(false);
+        let handle = spawn_waiting(monitor, &shutdown);
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+        let stopped = stop_monitors(&shutdown, vec![handle], std::time::Duration::from_secs(5));
+        assert!(stopped.await);
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        assert_eq!(shutdowns.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn shutdown_gives_up_on_monitors_after_the_timeout() {
+        let monitor = WaitingMonitor {
+            hangs_on_shutdown: true,
+            ..Default::default()
+        };
+        let shutdowns = monitor.shutdowns.clone();
+        let (shutdown, _) = tokio::sync::watch::channel(false);
+        let handles = vec![
+            spawn_waiting(monitor, &shutdown),
+            spawn_waiting(WaitingMonitor::default(), &shutdown),
+        ];
+
+        let started = tokio::time::Instant::now();
+        assert!(!stop_monitors(&shutdown, handles, std::time::Duration::from_secs(5)).await);
+        assert_eq!(started.elapsed(), std::time::Duration::from_secs(5));
+        assert_eq!(shutdowns.load(Ordering::SeqCst), 1);
+    }
+
+    /// Run the monitor under a 10s watchdog for the duration, returning how many times
+    /// the watchdog restarted it.
+    pub(crate) async fn watchdog_restarts<T: Monitor>(
+        monitor: T,
+        duration: std::time::Duration,
+    ) -> usize {
+        let alerts = crate::alerts::tests::CapturedAlerts::new(100);
+        let (_shutdown, receiver) = tokio::sync::watch::channel(false);
+        let _ = alerts
+            .scope(tokio::time::timeout(
+                duration,
+                run_monitor(
+                    monitor,
+                    receiver,
+                    Some(std::time::Duration::from_secs(10)),
+                    false,
+                    std::sync::Arc::new(tokio::sync::Notify::new()),
+                ),
+            ))
+            .await;
+        alerts
+            .take()
+            .iter()
+            .filter(|alert| alert.message == "Monitor stopped responding, restarting!")
+            .count()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn watchdog_restarts_are_alerted() {
+        assert_eq!(
+            watchdog_restarts(
+                WaitingMonitor::default(),
+                std::time::Duration::from_secs(35)
+            )
+            .await,
+            3
+        );
+    }
+
+    /// The trigger of a test monitor. Triggers can only be set once per process, so each
+    /// test that triggers checks has its own monitor name to not wake the others.
+    pub(crate) fn test_trigger(name: &str) -> std::sync::Arc<tokio::sync::Notify> {
+        TRIGGERS
+            .get_or_init(|| {
+                [
+                    ("triggered", Default::default()),
+                    ("checked", Default::default()),
+                ]
+                .into()
+            })
+            .get(name)
+            .unwrap()
+            .clone()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn triggered_checks_run_out_of_cycle() {
+        let trigger = test_trigger("triggered");
+        let checks = Arc::new(AtomicUsize::new(0));
+        let counted = checks.clone();
+        tokio::spawn(TRIGGER.scope(trigger, async move {
+            loop {
+                counted.fetch_add(1, Ordering::SeqCst);
+                sleep_or_triggered(std::time::Duration::from_secs(3600)).await;
+            }
+        }));
+
+        tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+        assert_eq!(checks.load(Ordering::SeqCst), 1);
+
+        assert!(trigger_check("triggered"));
+        assert!(!trigger_check("unknown"));
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        assert_eq!(checks.load(Ordering::SeqCst), 2);
+
+        // The interval restarts from the triggered check.
+        tokio::time::sleep(std::time::Duration::from_secs(3598)).await;
+        assert_eq!(checks.load(Ordering::SeqCst), 2);
+        tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+        assert_eq!(checks.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn sleep_with_heartbeat_sleeps_the_full_duration() {
+        let started = tokio::time::Instant::now();
+        sleep_with_heartbeat(std::time::Duration::from_millis(1200)).await;
+        assert_eq!(started.elapsed(), std::time::Duration::from_millis(1200));
+    }
+
+    #[test]
+    fn completion_expected_is_read_from_config() {
+        let config: MonitorsConfig =
+            toml::from_str("completion_expected = [\"returning\"]").unwrap();
+        assert!(config
+            .completion_expected
+            .contains(ReturningMonitor::name()));
+        assert!(MonitorsConfig::default().completion_expected.is_empty());
+    }
+
+    fn selected(config: &str) -> Vec<&'static str> {
+        let config: MonitorsConfig = toml::from_str(config).unwrap();
+        ["ping", "clock", "digest"]
+            .into_iter()
+            .filter(|name| is_selected(&config, name))
+            .collect()
+    }
+
+    #[test]
+    fn disabled_monitors_are_not_selected() {
+        assert_eq!(selected("disabled = [\"clock\"]"), ["ping", "digest"]);
+        assert_eq!(selected(""), ["ping", "clock", "digest"]);
+    }
+
+    #[test]
+    fn only_enabled_monitors_are_selected() {
+        assert_eq!(
+            selected("enabled = [\"ping\", \"digest\"]"),
+            ["ping", "digest"]
+        );
+    }
+
+    #[test]
+    fn disabled_takes_precedence_over_enabled() {
+        assert_eq!(
+            selected("enabled = [\"ping\", \"clock\"]\ndisabled = [\"clock\", \"digest\"]"),
+            ["ping"]
+        );
+    }
 }
@@ -0,0 +1,313 @@
+use crate::alerts::AlertLevel;
+use crate::config::{ModemMonitorConfig, MonitorsConfig};
+use crate::monitors::{heartbeat, sleep_or_triggered, Monitor};
+use log::{debug, warn};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_serial::SerialPortBuilderExt;
+
+/*
+   Query the modem's cellular signal strength with AT+CSQ per interval.
+   Send a warning when the signal drops below the threshold, and a critical
+   alert if the modem stops responding entirely. Optionally also check the
+   modem is attached for packet data with AT+CGATT, since it can have signal
+   but still be unable to send anything.
+*/
+
+#[derive(Debug, Clone, PartialEq)]
+enum ModemState {
+    Healthy,
+    Weak(u8),
+    Detached,
+    Unresponsive,
+}
+
+/// Sends AT commands to the modem, replaced in tests to script its responses.
+#[async_trait::async_trait]
+trait ModemPort: Send + Sync {
+    async fn query(&self, command: &[u8]) -> anyhow::Result<String>;
+}
+
+struct SerialModem {
+    device: String,
+    baud_rate: u32,
+}
+
+#[async_trait::async_trait]
+impl ModemPort for SerialModem {
+    async fn query(&self, command: &[u8]) -> anyhow::Result<String> {
+        let mut port = tokio_serial::new(&self.device, self.baud_rate).open_native_async()?;
+        port.write_all(command).await?;
+
+        // Read until the modem finishes its response.
+        let mut response = String::new();
+        let mut buffer = [0u8; 64];
+        while !response.contains("OK") && !response.contains("ERROR") {
+            let read = port.read(&mut buffer).await?;
+            if read == 0 {
+                anyhow::bail!("Modem closed the connection!");
+            }
+            response.push_str(&String::from_utf8_lossy(&buffer[..read]));
+        }
+        Ok(response)
+    }
+}
+
+pub(crate) struct ModemMonitor {
+    port: Box<dyn ModemPort>,
+    config: ModemMonitorConfig,
+}
+impl ModemMonitor {
+    /// Parse the RSSI from a `+CSQ: <rssi>,<ber>` response.
+    fn parse_csq(response: &str) -> Option<u8> {
+        let values = response
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("+CSQ:"))?;
+
+        // 99 means the signal is not known or not detectable.
+        match values.split(',').next()?.trim().parse::<u8>().ok()? {
+            99 => Some(0),
+            rssi => Some(rssi),
+        }
+    }
+
+    /// Parse the attach state from a `+CGATT: <state>` response.
+    fn parse_cgatt(response: &str) -> Option<bool> {
+        let state = response
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("+CGATT:"))?;
+
+        match state.trim() {
+            "1" => Some(true),
+            "0" => Some(false),
+            _ => None,
+        }
+    }
+
+    async fn query_signal(&self) -> anyhow::Result<u8> {
+        let response = self.port.query(b"AT+CSQ\r").await?;
+        Self::parse_csq(&response)
+            .ok_or_else(|| anyhow::anyhow!("Invalid AT+CSQ response: {response:?}"))
+    }
+
+    async fn query_attached(&self) -> anyhow::Result<bool> {
+        let response = self.port.query(b"AT+CGATT?\r").await?;
+        Self::parse_cgatt(&response)
+            .ok_or_else(|| anyhow::anyhow!("Invalid AT+CGATT response: {response:?}"))
+    }
+
+    /// Query the signal and, if enabled, the data attachment, which is the more severe.
+    async fn query_state(&self) -> anyhow::Result<ModemState> {
+        let rssi = self.query_signal().await?;
+        debug!("Modem signal RSSI is {rssi}.");
+        if self.config.check_data_attach && !self.query_attached().await? {
+            return Ok(ModemState::Detached);
+        }
+
+        if rssi < self.config.signal_threshold {
+            Ok(ModemState::Weak(rssi))
+        } else {
+            Ok(ModemState::Healthy)
+        }
+    }
+
+    async fn check_state(&self) -> ModemState {
+        let timeout = std::time::Duration::from_secs(self.config.timeout);
+        match tokio::time::timeout(timeout, self.query_state()).await {
+            Ok(Ok(state)) => state,
+            Ok(Err(e)) => {
+                warn!("Failed to query modem signal: {e:#?}");
+                ModemState::Unresponsive
+            }
+            Err(_) => {
+                warn!("Modem signal query timed out ({timeout:?})!");
+                ModemState::Unresponsive
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Monitor for ModemMonitor {
+    fn name() -> &'static str {
+        "modem"
+    }
+
+    fn description() -> &'static str {
+        "Checks the cellular modem's signal and registration over AT commands"
+    }
+
+    fn required_config() -> &'static [&'static str] {
+        &["monitors.modem.device"]
+    }
+
+    fn from_config(config: &MonitorsConfig) -> anyhow::Result<Self> {
+        let config = config
+            .modem
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Missing modem!"))?
+            .clone();
+
+        Ok(Self {
+            port: Box::new(SerialModem {
+                device: config.device.clone(),
+                baud_rate: config.baud_rate,
+            }),
+            config,
+        })
+    }
+
+    async fn run(&mut self) -> anyhow::Result<()> {
+        let mut state = ModemState::Healthy;
+        let interval = std::time::Duration::from_secs(self.config.interval);
+
+        debug!(
+            "Started with an interval of {} seconds!",
+            self.config.interval
+        );
+        loop {
+            let current = self.check_state().await;
+            heartbeat();
+
+            // Only alert on entering or leaving a degraded state.
+            let alert = match (&state, &current) {
+                (ModemState::Healthy, ModemState::Healthy)
+                | (ModemState::Weak(_), ModemState::Weak(_))
+                | (ModemState::Detached, ModemState::Detached)
+                | (ModemState::Unresponsive, ModemState::Unresponsive) => None,
+                (_, ModemState::Weak(rssi)) => Some((
+                    format!(
+                        "Signal is weak (RSSI {rssi} below {})!",
+                        self.config.signal_threshold
+                    ),
+                    AlertLevel::Warning,
+                )),
+                (_, ModemState::Detached) => Some((
+                    "Modem has no packet data attachment!".to_string(),
+                    AlertLevel::Critical,
+                )),
+                (_, ModemState::Unresponsive) => {
+                    Some(("Modem is not responding!".to_string(), AlertLevel::Critical))
+                }
+                (ModemState::Weak(_), ModemState::Healthy) => {
+                    Some(("Signal has recovered!".to_string(), AlertLevel::Warning))
+                }
+                (ModemState::Detached, ModemState::Healthy) => Some((
+                    "Modem packet data is attached again!".to_string(),
+                    AlertLevel::Critical,
+                )),
+                (ModemState::Unresponsive, ModemState::Healthy) => Some((
+                    "Modem is responding again!".to_string(),
+                    AlertLevel::Critical,
+                )),
+            };
+
+            let recovered = current == ModemState::Healthy;
+            state = current;
+            if let Some((message, level)) = alert {
+                debug!("{message}");
+                if recovered {
+                    Self::send_recovery_alert(message, level).await?;
+                } else {
+                    Self::send_problem_alert(message, level).await?;
+                }
+            }
+
+            sleep_or_triggered(interval).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_csq_responses() {
+        assert_eq!(
+            ModemMonitor::parse_csq("\r\n+CSQ: 18,99\r\n\r\nOK\r\n"),
+            Some(18)
+        );
+        assert_eq!(
+            ModemMonitor::parse_csq("AT+CSQ\r\r\n+CSQ: 5,0\r\nOK"),
+            Some(5)
+        );
+        assert_eq!(ModemMonitor::parse_csq("+CSQ: 31,99\r\nOK"), Some(31));
+    }
+
+    #[test]
+    fn treats_unknown_signal_as_none() {
+        assert_eq!(ModemMonitor::parse_csq("+CSQ: 99,99\r\nOK"), Some(0));
+    }
+
+    #[test]
+    fn parses_cgatt_responses() {
+        assert_eq!(
+            ModemMonitor::parse_cgatt("\r\n+CGATT: 1\r\n\r\nOK\r\n"),
+            Some(true)
+        );
+        assert_eq!(ModemMonitor::parse_cgatt("+CGATT: 0\r\nOK"), Some(false));
+        assert_eq!(ModemMonitor::parse_cgatt("ERROR"), None);
+    }
+
+    #[test]
+    fn rejects_invalid_csq_responses() {
+        assert_eq!(ModemMonitor::parse_csq("ERROR"), None);
+        assert_eq!(ModemMonitor::parse_csq("+CSQ: ,99\r\nOK"), None);
+        assert_eq!(ModemMonitor::parse_csq("+CSQ: abc\r\nOK"), None);
+    }
+
+    /// A modem with good signal, answering AT+CGATT as given or not at all.
+    struct ScriptedModem {
+        cgatt: Option<&'static str>,
+    }
+
+    #[async_trait::async_trait]
+    impl ModemPort for ScriptedModem {
+        async fn query(&self, command: &[u8]) -> anyhow::Result<String> {
+            match command {
+                b"AT+CSQ\r" => Ok("+CSQ: 20,99\r\nOK\r\n".to_string()),
+                b"AT+CGATT?\r" => self
+                    .cgatt
+                    .map(str::to_string)
+                    .ok_or_else(|| anyhow::anyhow!("No response")),
+                _ => anyhow::bail!("Unexpected command {command:?}"),
+            }
+        }
+    }
+
+    async fn state(cgatt: Option<&'static str>) -> ModemState {
+        let monitor = ModemMonitor {
+            port: Box::new(ScriptedModem { cgatt }),
+            config: toml::from_str(
+                r#"
+                device = "/dev/ttyUSB2"
+                check_data_attach = true
+                "#,
+            )
+            .unwrap(),
+        };
+        monitor.check_state().await
+    }
+
+    #[tokio::test]
+    async fn attached_modems_are_healthy() {
+        assert_eq!(
+            state(Some("+CGATT: 1\r\nOK\r\n")).await,
+            ModemState::Healthy
+        );
+    }
+
+    #[tokio::test]
+    async fn modems_with_signal_but_no_data_are_detached() {
+        assert_eq!(
+            state(Some("+CGATT: 0\r\nOK\r\n")).await,
+            ModemState::Detached
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_data_checks_are_unresponsive() {
+        assert_eq!(state(None).await, ModemState::Unresponsive);
+        assert_eq!(state(Some("ERROR\r\n")).await, ModemState::Unresponsive);
+    }
+}
@@ -1,12 +1,19 @@
 use crate::alerts::AlertLevel;
 use crate::config::{MonitoredPingTarget, MonitorsConfig};
-use crate::monitors::Monitor;
+use crate::monitors::{heartbeat, sleep_or_triggered, spawn_with_heartbeat, Monitor};
+use crate::outbound::{client_builder, ensure_host_allowed};
+use anyhow::Context;
 use log::{debug, warn};
 
 /*
-   Attempt TCP connections to an addr per interval with a timeout.
+   Attempt TCP connections to an addr per interval with a timeout. Targets with
+   an HTTP path are also sent a GET request, since a port can still accept
+   connections while the service behind it is wedged.
 */
 
+// Each port is checked by its own task, so a range can't spawn an unbounded number.
+const MAX_PORT_RANGE: u16 = 64;
+
 #[derive(Clone)]
 struct PingTarget {
     name: String,
@@ -14,48 +21,194 @@ struct PingTarget {
     level: AlertLevel,
     timeout: std::time::Duration,
     interval: std::time::Duration,
+    sustained: std::time::Duration,
+    http: Option<(reqwest::Client, String)>,
 }
-impl TryFrom<&MonitoredPingTarget> for PingTarget {
-    type Error = anyhow::Error;
-
-    fn try_from(value: &MonitoredPingTarget) -> Result<Self, Self::Error> {
-        AlertLevel::try_from(value.level).map(|level| PingTarget {
-            name: value.name.clone(),
-            addr: value.addr.clone(),
-            level,
-            timeout: std::time::Duration::from_secs(value.timeout.unwrap_or(5)),
-            interval: std::time::Duration::from_secs(value.interval.unwrap_or(60)),
-        })
+impl PingTarget {
+    /// Create a target per port, since the address can give a range of ports.
+    fn from_config(value: &MonitoredPingTarget, default_port: u16) -> anyhow::Result<Vec<Self>> {
+        let addrs = Self::normalize_addrs(&value.addr, default_port)
+            .with_context(|| format!("Invalid address for ping target '{}'", value.name))?;
+
+        let level = AlertLevel::try_from(value.level)?;
+        let timeout = std::time::Duration::from_secs(value.timeout.unwrap_or(5));
+        let client = match &value.http_path {
+            Some(_) => Some(client_builder().timeout(timeout).build()?),
+            None => None,
+        };
+
+        let is_range = addrs.len() > 1;
+        addrs
+            .into_iter()
+            .map(|addr| {
+                let http = match (&client, &value.http_path) {
+                    (Some(client), Some(path)) => {
+                        let url = format!("http://{addr}/{}", path.trim_start_matches('/'));
+                        ensure_host_allowed(&url)?;
+                        Some((client.clone(), url))
+                    }
+                    _ => None,
+                };
+
+                // Name each port of a range so their alerts and recoveries are told apart.
+                let name = match addr.rsplit_once(':') {
+                    Some((_, port)) if is_range => format!("{}:{port}", value.name),
+                    _ => value.name.clone(),
+                };
+                Ok(PingTarget {
+                    name,
+                    addr,
+                    level: level.clone(),
+                    timeout,
+                    interval: std::time::Duration::from_secs(value.interval.unwrap_or(60)),
+                    sustained: std::time::Duration::from_secs(value.sustained.unwrap_or(0)),
+                    http,
+                })
+            })
+            .collect()
+    }
+
+    async fn is_online(&self) -> bool {
+        match tokio::time::timeout(self.timeout, tokio::net::TcpStream::connect(&self.addr)).await {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => {
+                warn!("[{}] Ping error to {}: {}", self.name, self.addr, e);
+                return false;
+            }
+            Err(_) => {
+                warn!(
+                    "[{}] Ping timeout ({:?}) to {}!",
+                    self.name, self.timeout, self.addr
+                );
+                return false;
+            }
+        }
+
+        let Some((client, url)) = &self.http else {
+            return true;
+        };
+        match client.get(url).send().await {
+            Ok(response) if response.status().is_success() => true,
+            Ok(response) => {
+                warn!(
+                    "[{}] HTTP check to {url} returned {}!",
+                    self.name,
+                    response.status()
+                );
+                false
+            }
+            Err(e) => {
+                warn!("[{}] HTTP check error to {url}: {e}", self.name);
+                false
+            }
+        }
+    }
+
+    /// Normalize an address into `host:port` form, once per port. Accepts bracketed IPv6
+    /// literals (with or without a port), bare IPv6 literals, and hosts with or without a
+    /// port, where the port may be an inclusive `start-end` range.
+    fn normalize_addrs(addr: &str, default_port: u16) -> anyhow::Result<Vec<String>> {
+        let addr = addr.trim();
+        if let Some(rest) = addr.strip_prefix('[') {
+            let (host, rest) = rest
+                .split_once(']')
+                .ok_or_else(|| anyhow::anyhow!("Missing closing bracket in '{addr}'"))?;
+            let ip = host
+                .parse::<std::net::Ipv6Addr>()
+                .with_context(|| format!("Invalid IPv6 address '{host}'"))?;
+            let ports = match rest {
+                "" => default_port..=default_port,
+                rest => Self::parse_ports(
+                    rest.strip_prefix(':')
+                        .ok_or_else(|| anyhow::anyhow!("Expected ':' after ']' in '{addr}'"))?,
+                )?,
+            };
+            return Ok(ports
+                .map(|port| std::net::SocketAddr::from((ip, port)).to_string())
+                .collect());
+        }
+
+        if let Ok(ip) = addr.parse::<std::net::Ipv6Addr>() {
+            return Ok(vec![
+                std::net::SocketAddr::from((ip, default_port)).to_string()
+            ]);
+        }
+
+        let (host, ports) = match addr.rsplit_once(':') {
+            Some((host, ports)) => (host, Self::parse_ports(ports)?),
+            None => (addr, default_port..=default_port),
+        };
+        if host.is_empty() || host.contains(':') {
+            anyhow::bail!("Invalid host in '{addr}'");
+        }
+        Ok(ports.map(|port| format!("{host}:{port}")).collect())
+    }
+
+    /// Parse a port, or an inclusive `start-end` range of ports.
+    fn parse_ports(ports: &str) -> anyhow::Result<std::ops::RangeInclusive<u16>> {
+        let parse_port = |port: &str| {
+            port.parse::<u16>()
+                .with_context(|| format!("Invalid port '{port}'"))
+        };
+
+        let Some((start, end)) = ports.split_once('-') else {
+            let port = parse_port(ports)?;
+            return Ok(port..=port);
+        };
+        let (start, end) = (parse_port(start)?, parse_port(end)?);
+        if start > end {
+            anyhow::bail!("Port range '{ports}' ends before it starts");
+        }
+        if end - start >= MAX_PORT_RANGE {
+            anyhow::bail!("Port range '{ports}' spans more than {MAX_PORT_RANGE} ports");
+        }
+        Ok(start..=end)
+    }
+}
+
+/// Whether a target's outage has lasted long enough to alert, and so whether its
+/// recovery should be alerted too.
+struct Outage {
+    sustained: std::time::Duration,
+    down_since: Option<tokio::time::Instant>,
+    alerted: bool,
+}
+impl Outage {
+    fn new(sustained: std::time::Duration) -> Self {
+        Self {
+            sustained,
+            down_since: None,
+            alerted: false,
+        }
+    }
+
+    /// Record a check, returning whether to send an offline (false) or recovery (true) alert.
+    fn update(&mut self, online: bool, now: tokio::time::Instant) -> Option<bool> {
+        if online {
+            self.down_since = None;
+            return std::mem::take(&mut self.alerted).then_some(true);
+        }
+
+        let since = *self.down_since.get_or_insert(now);
+        if !self.alerted && now.duration_since(since) >= self.sustained {
+            self.alerted = true;
+            return Some(false);
+        }
+        None
     }
 }
 
 pub(crate) struct PingMonitor {
     targets: Vec<PingTarget>,
+    handles: Vec<tokio::task::AbortHandle>,
 }
 impl PingMonitor {
     async fn run_target(target: PingTarget) -> anyhow::Result<()> {
-        let mut is_online = true;
+        let mut outage = Outage::new(target.sustained);
         let seconds = target.interval.as_secs();
         loop {
-            let currently_online = match tokio::time::timeout(
-                target.timeout,
-                tokio::net::TcpStream::connect(&target.addr),
-            )
-            .await
-            {
-                Ok(Ok(_)) => true,
-                Ok(Err(e)) => {
-                    warn!("[{}] Ping error to {}: {}", target.name, target.addr, e);
-                    false
-                }
-                Err(_) => {
-                    warn!(
-                        "[{}] Ping timeout ({:?}) to {}!",
-                        target.name, target.timeout, target.addr
-                    );
-                    false
-                }
-            };
+            let currently_online = target.is_online().await;
+            heartbeat();
 
             debug!(
                 "[{}, {seconds}s] Ping to {}: {}",
@@ -68,19 +221,20 @@ impl PingMonitor {
                 }
             );
 
-            if currently_online != is_online {
-                is_online = currently_online;
-                let message = if currently_online {
+            // Only alert once the target has been offline for the sustained duration,
+            // and only send a recovery alert if the outage was alerted.
+            if let Some(recovery) = outage.update(currently_online, tokio::time::Instant::now()) {
+                let message = if recovery {
                     format!("[{}] Now online!", target.name)
                 } else {
                     format!("[{}] Now offline!", target.name)
                 };
-
                 debug!("{message}");
-                Self::send_alert(message, target.level.clone()).await?;
+                Self::send_subject_alert(&target.name, message, target.level.clone(), recovery)
+                    .await?;
             }
 
-            tokio::time::sleep(target.interval).await;
+            sleep_or_triggered(target.interval).await;
         }
     }
 }
@@ -91,20 +245,34 @@ impl Monitor for PingMonitor {
         "ping"
     }
 
+    fn description() -> &'static str {
+        "Connects to TCP targets, optionally also requiring an HTTP 2xx"
+    }
+
+    fn required_config() -> &'static [&'static str] {
+        &["monitors.pings"]
+    }
+
     fn from_config(config: &MonitorsConfig) -> anyhow::Result<Self> {
         let targets: Vec<_> = config
             .pings
             .as_ref()
             .ok_or_else(|| anyhow::anyhow!("Missing pings_monitored!"))?
             .iter()
-            .map(PingTarget::try_from)
-            .collect::<Result<_, _>>()?;
+            .map(|target| PingTarget::from_config(target, config.ping_default_port))
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .flatten()
+            .collect();
 
         if targets.is_empty() {
             anyhow::bail!("No ping targets configured!");
         }
 
-        Ok(Self { targets })
+        Ok(Self {
+            targets,
+            handles: Vec::new(),
+        })
     }
 
     async fn run(&mut self) -> anyhow::Result<()> {
@@ -112,8 +280,9 @@ impl Monitor for PingMonitor {
             .targets
             .iter()
             .cloned()
-            .map(|target| tokio::spawn(async move { Self::run_target(target).await }))
+            .map(|target| spawn_with_heartbeat(async move { Self::run_target(target).await }))
             .collect();
+        self.handles = handles.iter().map(|handle| handle.abort_handle()).collect();
 
         // Wait for any task to complete (they shouldn't unless there's an error)
         for result in futures::future::join_all(handles).await {
@@ -126,4 +295,179 @@ impl Monitor for PingMonitor {
 
         Ok(())
     }
+
+    async fn on_shutdown(&mut self) {
+        // Target tasks are detached from run, so they must be stopped explicitly.
+        for handle in self.handles.drain(..) {
+            handle.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalize(addr: &str) -> Vec<String> {
+        PingTarget::normalize_addrs(addr, 80).unwrap()
+    }
+
+    #[test]
+    fn normalizes_ipv4() {
+        assert_eq!(normalize("192.168.1.1:8080"), ["192.168.1.1:8080"]);
+        assert_eq!(normalize(" 192.168.1.1 "), ["192.168.1.1:80"]);
+    }
+
+    #[test]
+    fn normalizes_ipv6() {
+        assert_eq!(normalize("[fd00::1]:443"), ["[fd00::1]:443"]);
+        assert_eq!(normalize("[::1]"), ["[::1]:80"]);
+        assert_eq!(normalize("fd00::1"), ["[fd00::1]:80"]);
+    }
+
+    #[test]
+    fn normalizes_bare_hosts() {
+        assert_eq!(normalize("router.local"), ["router.local:80"]);
+        assert_eq!(normalize("router.local:22"), ["router.local:22"]);
+        assert_eq!(
+            PingTarget::normalize_addrs("router.local", 443).unwrap(),
+            ["router.local:443"]
+        );
+    }
+
+    #[test]
+    fn expands_port_ranges() {
+        assert_eq!(
+            normalize("nas:8000-8002"),
+            ["nas:8000", "nas:8001", "nas:8002"]
+        );
+        assert_eq!(normalize("[::1]:22-23"), ["[::1]:22", "[::1]:23"]);
+        assert_eq!(normalize("nas:22-22"), ["nas:22"]);
+    }
+
+    #[test]
+    fn rejects_invalid_addrs() {
+        for addr in [
+            "",
+            ":80",
+            "[::1",
+            "[::1]80",
+            "[nas]:80",
+            "nas:99999",
+            "nas:http",
+            "nas:90-80",
+            "nas:1-1000",
+            "fd00::1:80-90",
+        ] {
+            assert!(
+                PingTarget::normalize_addrs(addr, 80).is_err(),
+                "'{addr}' should be invalid"
+            );
+        }
+    }
+
+    #[test]
+    fn names_each_port_of_a_range() {
+        let config = MonitoredPingTarget {
+            name: "nas".to_string(),
+            addr: "nas:8000-8001".to_string(),
+            level: 2,
+            timeout: None,
+            interval: None,
+            sustained: None,
+            http_path: None,
+        };
+        let targets = PingTarget::from_config(&config, 80).unwrap();
+        let names: Vec<_> = targets.iter().map(|target| target.name.as_str()).collect();
+        assert_eq!(names, ["nas:8000", "nas:8001"]);
+
+        let config = MonitoredPingTarget {
+            addr: "nas:8000".to_string(),
+            ..config
+        };
+        let targets = PingTarget::from_config(&config, 80).unwrap();
+        assert_eq!(targets.len(), 1);
+        assert_eq!(targets[0].name, "nas");
+    }
+
+    fn http_target(addr: std::net::SocketAddr, http_path: Option<&str>) -> PingTarget {
+        let config = MonitoredPingTarget {
+            name: "web".to_string(),
+            addr: addr.to_string(),
+            level: 2,
+            timeout: Some(2),
+            interval: None,
+            sustained: None,
+            http_path: http_path.map(str::to_string),
+        };
+        PingTarget::from_config(&config, 80).unwrap().remove(0)
+    }
+
+    /// Serve `/healthy` with a 200 and `/wedged` with a 500, both on the same port.
+    fn serve() -> std::net::SocketAddr {
+        use warp::Filter;
+        let routes = warp::path("healthy")
+            .map(warp::reply)
+            .or(warp::path("wedged").map(|| {
+                warp::reply::with_status("", warp::http::StatusCode::INTERNAL_SERVER_ERROR)
+            }));
+        let (address, server) = warp::serve(routes).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        address
+    }
+
+    #[tokio::test]
+    async fn http_mode_reports_tcp_up_http_down_targets_as_down() {
+        let address = serve();
+
+        // The port accepts connections either way, only the HTTP check tells them apart.
+        assert!(http_target(address, None).is_online().await);
+        assert!(http_target(address, Some("/healthy")).is_online().await);
+        assert!(!http_target(address, Some("/wedged")).is_online().await);
+    }
+
+    /// Feed the outage a check result per minute, returning the alerts it sends.
+    fn outage_alerts(sustained: u64, checks: &[bool]) -> Vec<(u64, bool)> {
+        let mut outage = Outage::new(std::time::Duration::from_secs(sustained));
+        let started = tokio::time::Instant::now();
+        checks
+            .iter()
+            .enumerate()
+            .filter_map(|(minute, online)| {
+                let now = started + std::time::Duration::from_secs(minute as u64 * 60);
+                outage
+                    .update(*online, now)
+                    .map(|recovery| (minute as u64, recovery))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn short_blips_are_silent() {
+        assert_eq!(
+            outage_alerts(300, &[true, false, false, false, true, true]),
+            []
+        );
+    }
+
+    #[test]
+    fn sustained_outages_alert_once_then_recover() {
+        let checks = [true, false, false, false, false, false, false, false, true];
+        // Down from minute 1, so alerted at minute 6 and recovered at minute 8.
+        assert_eq!(outage_alerts(300, &checks), [(6, false), (8, true)]);
+    }
+
+    #[test]
+    fn outages_alert_immediately_without_a_sustained_duration() {
+        assert_eq!(
+            outage_alerts(0, &[false, false, true, false]),
+            [(0, false), (2, true), (3, false)]
+        );
+    }
+
+    #[test]
+    fn blips_restart_the_sustained_duration() {
+        let checks = [false, false, false, false, true, false, false, false, false];
+        assert_eq!(outage_alerts(300, &checks), []);
+    }
 }
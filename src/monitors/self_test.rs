@@ -0,0 +1,162 @@
+use crate::alerts::{send_alert, AlertLevel};
+use crate::config::MonitorsConfig;
+use crate::monitors::{heartbeat, sleep_or_triggered, Monitor};
+use log::{debug, info};
+
+/*
+   Fire a test alarm through every real provider on a schedule, so a broken
+   delivery path (SMS credit, a dead siren relay) is found by a drill rather
+   than a break-in. The AlertManager alerts if the test alarm isn't delivered.
+*/
+
+const TEST_ALARM_MESSAGE: &str = "TEST ALARM, this is a scheduled drill and needs no action.";
+
+pub(crate) struct SelfTestMonitor {
+    weekday: Option<time::Weekday>,
+    time: time::Time,
+}
+impl SelfTestMonitor {
+    /// Parse an "HH:MM" time of day.
+    fn parse_time(value: &str) -> Option<time::Time> {
+        let (hour, minute) = value.trim().split_once(':')?;
+        time::Time::from_hms(hour.parse().ok()?, minute.parse().ok()?, 0).ok()
+    }
+
+    /// Parse a weekday name, ignoring case.
+    fn parse_weekday(value: &str) -> Option<time::Weekday> {
+        let value = value.trim().to_lowercase();
+        let mut chars = value.chars();
+        let first = chars.next()?.to_uppercase();
+        first.chain(chars).collect::<String>().parse().ok()
+    }
+
+    fn next_run(&self, now: time::OffsetDateTime) -> time::OffsetDateTime {
+        let mut next = now.replace_time(self.time);
+        while next <= now
+            || self
+                .weekday
+                .is_some_and(|weekday| next.weekday() != weekday)
+        {
+            next += time::Duration::days(1);
+        }
+        next
+    }
+}
+
+#[async_trait::async_trait]
+impl Monitor for SelfTestMonitor {
+    fn name() -> &'static str {
+        "self_test"
+    }
+
+    fn description() -> &'static str {
+        "Sends a scheduled test alarm through every provider"
+    }
+
+    fn required_config() -> &'static [&'static str] {
+        &["monitors.self_test.time"]
+    }
+
+    fn from_config(config: &MonitorsConfig) -> anyhow::Result<Self> {
+        let config = config
+            .self_test
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Missing self_test!"))?;
+
+        let time = Self::parse_time(&config.time)
+            .ok_or_else(|| anyhow::anyhow!("Invalid self_test time {:?}!", config.time))?;
+        let weekday = match &config.weekday {
+            Some(weekday) => Some(
+                Self::parse_weekday(weekday)
+                    .ok_or_else(|| anyhow::anyhow!("Invalid self_test weekday {weekday:?}!"))?,
+            ),
+            None => None,
+        };
+
+        Ok(Self { weekday, time })
+    }
+
+    async fn run(&mut self) -> anyhow::Result<()> {
+        loop {
+            let now = time::OffsetDateTime::now_utc();
+            let next = self.next_run(now);
+            debug!("Next test alarm at {next}");
+
+            sleep_or_triggered((next - now).unsigned_abs()).await;
+            heartbeat();
+
+            // Unsuppressible so it neither waits for confirmation nor starts a cooldown.
+            info!("Sending scheduled test alarm!");
+            let mut alert = Self::create_alert(TEST_ALARM_MESSAGE.to_string(), AlertLevel::Alarm)?;
+            alert.metadata.unsuppressible = true;
+            alert.metadata.test = true;
+            send_alert(alert).await?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alerts::tests::CapturedAlerts;
+    use time::macros::{datetime, time};
+
+    fn monitor(time: time::Time, weekday: Option<time::Weekday>) -> SelfTestMonitor {
+        SelfTestMonitor { weekday, time }
+    }
+
+    #[test]
+    fn parses_the_schedule() {
+        assert_eq!(SelfTestMonitor::parse_time("09:30"), Some(time!(09:30)));
+        assert_eq!(SelfTestMonitor::parse_time("24:00"), None);
+        assert_eq!(
+            SelfTestMonitor::parse_weekday("SUNDAY"),
+            Some(time::Weekday::Sunday)
+        );
+        assert_eq!(SelfTestMonitor::parse_weekday("someday"), None);
+    }
+
+    #[test]
+    fn runs_at_the_next_scheduled_time() {
+        // Wednesday.
+        let now = datetime!(2026-10-14 12:00 UTC);
+        assert_eq!(
+            monitor(time!(13:00), None).next_run(now),
+            datetime!(2026-10-14 13:00 UTC)
+        );
+        assert_eq!(
+            monitor(time!(12:00), None).next_run(now),
+            datetime!(2026-10-15 12:00 UTC)
+        );
+        assert_eq!(
+            monitor(time!(09:00), Some(time::Weekday::Sunday)).next_run(now),
+            datetime!(2026-10-18 09:00 UTC)
+        );
+    }
+
+    /// Run a monitor scheduled two minutes from now until the offset from its scheduled
+    /// time, returning its alerts.
+    async fn alarms_by(offset: time::Duration) -> Vec<crate::alerts::AlertInfo> {
+        let now = time::OffsetDateTime::now_utc();
+        let mut monitor = monitor((now + time::Duration::minutes(2)).time(), None);
+        let until = (monitor.next_run(now) - now + offset).unsigned_abs();
+
+        let alerts = CapturedAlerts::new(10);
+        let _ = alerts
+            .scope(tokio::time::timeout(until, monitor.run()))
+            .await;
+        alerts.take()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn fires_a_test_alarm_at_the_configured_time() {
+        assert!(alarms_by(-time::Duration::seconds(5)).await.is_empty());
+
+        let alarms = alarms_by(time::Duration::seconds(5)).await;
+        assert_eq!(alarms.len(), 1);
+        assert_eq!(alarms[0].message, TEST_ALARM_MESSAGE);
+        assert_eq!(alarms[0].level, AlertLevel::Alarm);
+        assert!(alarms[0].metadata.test);
+        assert!(alarms[0].metadata.unsuppressible);
+    }
+}
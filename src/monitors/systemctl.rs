@@ -1,6 +1,6 @@
 use crate::alerts::AlertLevel;
 use crate::config::MonitorsConfig;
-use crate::monitors::Monitor;
+use crate::monitors::{heartbeat, sleep_with_heartbeat, triggered, wait_with_heartbeat, Monitor};
 use log::{debug, error, info};
 
 /*
@@ -12,18 +12,27 @@ use log::{debug, error, info};
 struct MonitoredSystemctlState {
     name: String,
     level: AlertLevel,
+    recovery_level: AlertLevel,
     is_offline: bool,
     retry_count: u8,
+    online_since: Option<tokio::time::Instant>,
+
+    // Whether our last restart succeeded, to tell it apart from the service recovering itself.
+    restarted: bool,
 }
 
-pub(crate) struct SystemctlMonitor {
-    services: Vec<MonitoredSystemctlState>,
-    interval: u64,
-    retry_attempts: u8,
-    retry_delay: std::time::Duration,
+/// Controls services through systemctl, replaced in tests to script how services behave.
+#[async_trait::async_trait]
+trait ServiceControl: Send + Sync {
+    async fn is_active(&self, name: &str) -> anyhow::Result<bool>;
+    async fn restart(&self, name: &str) -> anyhow::Result<bool>;
 }
-impl SystemctlMonitor {
-    async fn is_service_active(name: &str) -> anyhow::Result<bool> {
+
+struct Systemctl;
+
+#[async_trait::async_trait]
+impl ServiceControl for Systemctl {
+    async fn is_active(&self, name: &str) -> anyhow::Result<bool> {
         let output = tokio::process::Command::new("systemctl")
             .arg("is-active")
             .arg(name)
@@ -33,7 +42,7 @@ impl SystemctlMonitor {
         Ok(output.status.success())
     }
 
-    async fn attempt_service_restart(name: &str) -> anyhow::Result<bool> {
+    async fn restart(&self, name: &str) -> anyhow::Result<bool> {
         let output = tokio::process::Command::new("systemctl")
             .arg("restart")
             .arg(name)
@@ -42,9 +51,33 @@ impl SystemctlMonitor {
 
         Ok(output.status.success())
     }
+}
+
+pub(crate) struct SystemctlMonitor {
+    control: Box<dyn ServiceControl>,
+    services: Vec<MonitoredSystemctlState>,
+    interval: u64,
+    retry_attempts: u8,
+    retry_delay: std::time::Duration,
+    retry_delay_max: std::time::Duration,
+    stable_duration: std::time::Duration,
+    suppress_self_recovery: bool,
+}
+impl SystemctlMonitor {
+    /// Get the delay before a restart attempt, doubling each attempt up to the maximum.
+    fn get_retry_delay(&self, retry_count: u8) -> std::time::Duration {
+        let multiplier = 2u32.saturating_pow(u32::from(retry_count.saturating_sub(1)));
+        std::cmp::min(
+            self.retry_delay.saturating_mul(multiplier),
+            self.retry_delay_max,
+        )
+    }
 
     async fn handle_offline_service(&mut self, index: usize) -> anyhow::Result<()> {
-        self.services[index].retry_count += 1;
+        let service = &mut self.services[index];
+        service.retry_count = service.retry_count.saturating_add(1);
+        service.online_since = None;
+        service.restarted = false;
 
         let service = &self.services[index];
         let service_name = service.name.clone();
@@ -55,12 +88,17 @@ impl SystemctlMonitor {
         );
 
         if service.retry_count <= self.retry_attempts {
-            tokio::time::sleep(self.retry_delay).await;
+            // The delay can reach retry_delay_max, which may be longer than the watchdog.
+            sleep_with_heartbeat(self.get_retry_delay(service.retry_count)).await;
 
+            // The retry count is only reset once the service has been stable for a while,
+            // so a service that keeps flapping still runs out of restart attempts.
             info!("Attempting to restart service {}!", &service_name);
-            if Self::attempt_service_restart(&service_name).await? {
+            if self.control.restart(&service_name).await? {
                 info!("Service {} was successfully restarted!", &service_name);
-                self.services[index].retry_count = 0;
+                let service = &mut self.services[index];
+                service.online_since = Some(tokio::time::Instant::now());
+                service.restarted = true;
                 return Ok(());
             }
         }
@@ -68,12 +106,14 @@ impl SystemctlMonitor {
         let service = &mut self.services[index];
         if !service.is_offline {
             service.is_offline = true;
-            Self::send_alert(
+            Self::send_subject_alert(
+                &service_name,
                 format!(
                     "{} is OFFLINE after {} attempts to restart!",
                     service_name, service.retry_count
                 ),
                 service.level.clone(),
+                false,
             )
             .await?;
         }
@@ -86,18 +126,34 @@ impl SystemctlMonitor {
         let service_name = self.services[index].name.clone();
         debug!("Checking service {} state...", &service_name);
 
-        match Self::is_service_active(&service_name).await {
+        match self.control.is_active(&service_name).await {
             Ok(true) => {
                 // The service is now online.
                 debug!("Service {} is online!", &service_name);
                 let service = &mut self.services[index];
+                let online_since = *service
+                    .online_since
+                    .get_or_insert_with(tokio::time::Instant::now);
+                if service.retry_count > 0 && online_since.elapsed() >= self.stable_duration {
+                    debug!(
+                        "Service {} is stable, resetting restart attempts.",
+                        &service_name
+                    );
+                    service.retry_count = 0;
+                }
+
                 if service.is_offline {
                     service.is_offline = false;
-                    service.retry_count = 0;
+                    if self.suppress_self_recovery && !service.restarted {
+                        info!("Service {service_name} recovered by itself, not alerting.");
+                        return Ok(());
+                    }
 
-                    Self::send_alert(
+                    Self::send_subject_alert(
+                        &service_name,
                         format!("{service_name} is now ONLINE!"),
-                        service.level.clone(),
+                        service.recovery_level.clone(),
+                        true,
                     )
                     .await?;
                 }
@@ -116,6 +172,14 @@ impl Monitor for SystemctlMonitor {
         "system_ctl"
     }
 
+    fn description() -> &'static str {
+        "Restarts offline systemd services, alerting if they stay offline"
+    }
+
+    fn required_config() -> &'static [&'static str] {
+        &["monitors.systemctl"]
+    }
+
     fn from_config(config: &MonitorsConfig) -> anyhow::Result<Self> {
         let services = config
             .systemctl
@@ -125,9 +189,18 @@ impl Monitor for SystemctlMonitor {
             .map(|service| {
                 Ok(MonitoredSystemctlState {
                     name: service.name.to_string(),
-                    level: AlertLevel::try_from(service.level)?,
+                    level: if service.alarm_on_offline {
+                        AlertLevel::Alarm
+                    } else {
+                        AlertLevel::try_from(service.level)?
+                    },
+                    recovery_level: AlertLevel::try_from(
+                        service.recovery_level.unwrap_or(service.level),
+                    )?,
                     is_offline: false,
                     retry_count: 0,
+                    online_since: None,
+                    restarted: false,
                 })
             })
             .collect::<Result<Vec<_>, anyhow::Error>>()?;
@@ -138,10 +211,14 @@ impl Monitor for SystemctlMonitor {
         }
 
         Ok(Self {
+            control: Box::new(Systemctl),
             services,
             interval: config.systemctl_poll_interval,
             retry_attempts: config.systemctl_retry_attempts,
             retry_delay: std::time::Duration::from_secs(config.systemctl_retry_delay),
+            retry_delay_max: std::time::Duration::from_secs(config.systemctl_retry_delay_max),
+            stable_duration: std::time::Duration::from_secs(config.systemctl_stable_duration),
+            suppress_self_recovery: config.systemctl_suppress_self_recovery,
         })
     }
 
@@ -152,8 +229,263 @@ impl Monitor for SystemctlMonitor {
         loop {
             for i in 0..self.services.len() {
                 self.check_service(i).await?;
+                heartbeat();
             }
-            interval.tick().await;
+            wait_with_heartbeat(async {
+                tokio::select! {
+                    _ = interval.tick() => {},
+                    _ = triggered() => {},
+                }
+            })
+            .await;
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alerts::tests::CapturedAlerts;
+    use crate::alerts::AlertInfo;
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    /// Reports each scripted state in turn, staying active once they run out.
+    #[derive(Default)]
+    struct ScriptedControl {
+        active: Mutex<VecDeque<bool>>,
+        restarts: Mutex<VecDeque<bool>>,
+    }
+
+    #[async_trait::async_trait]
+    impl ServiceControl for ScriptedControl {
+        async fn is_active(&self, _: &str) -> anyhow::Result<bool> {
+            Ok(self.active.lock().unwrap().pop_front().unwrap_or(true))
+        }
+
+        async fn restart(&self, _: &str) -> anyhow::Result<bool> {
+            Ok(self.restarts.lock().unwrap().pop_front().unwrap_or(false))
+        }
+    }
+
+    fn monitor(config: &str, active: &[bool], restarts: &[bool]) -> SystemctlMonitor {
+        let config: MonitorsConfig = toml::from_str(config).unwrap();
+        SystemctlMonitor {
+            control: Box::new(ScriptedControl {
+                active: Mutex::new(active.iter().copied().collect()),
+                restarts: Mutex::new(restarts.iter().copied().collect()),
+            }),
+            ..SystemctlMonitor::from_config(&config).unwrap()
+        }
+    }
+
+    /// Check the first service the given number of times, returning the alerts sent.
+    async fn check(monitor: &mut SystemctlMonitor, checks: usize) -> Vec<AlertInfo> {
+        let alerts = CapturedAlerts::new(16);
+        alerts
+            .scope(async {
+                for _ in 0..checks {
+                    monitor.check_service(0).await.unwrap();
+                }
+            })
+            .await;
+        alerts.take()
+    }
+
+    const SERVICE: &str = r#"
+        systemctl_retry_attempts = 1
+        [[systemctl]]
+        name = "security_alarm_modem"
+        level = 3
+        recovery_level = 2
+    "#;
+
+    #[tokio::test(start_paused = true)]
+    async fn offline_and_recovery_alerts_use_their_own_levels() {
+        // Offline with a failed restart, then a successful restart it comes back from.
+        let mut monitor = monitor(SERVICE, &[false, false, true], &[false, true]);
+        let alerts = check(&mut monitor, 3).await;
+
+        assert_eq!(alerts.len(), 2);
+        assert!(alerts[0].message.contains("OFFLINE"));
+        assert_eq!(alerts[0].level, AlertLevel::Critical);
+        assert!(!alerts[0].metadata.recovery);
+        assert!(alerts[1].message.contains("ONLINE"));
+        assert_eq!(alerts[1].level, AlertLevel::Warning);
+        assert!(alerts[1].metadata.recovery);
+        assert_eq!(
+            alerts[1].metadata.subject.as_deref(),
+            Some("security_alarm_modem")
+        );
+    }
+
+    #[test]
+    fn restart_delays_double_up_to_the_cap() {
+        let monitor = monitor(
+            &format!("systemctl_retry_delay = 5\nsystemctl_retry_delay_max = 30\n{SERVICE}"),
+            &[],
+            &[],
+        );
+        let delays: Vec<_> = (1..=6)
+            .map(|retry_count| monitor.get_retry_delay(retry_count).as_secs())
+            .collect();
+        assert_eq!(delays, [5, 10, 20, 30, 30, 30]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn restart_attempts_only_reset_once_stable() {
+        let mut monitor = monitor(
+            &format!("systemctl_stable_duration = 60\n{SERVICE}"),
+            &[false],
+            &[true],
+        );
+        check(&mut monitor, 1).await;
+        assert_eq!(monitor.services[0].retry_count, 1);
+
+        // A flapping service that's only briefly online keeps its attempts used.
+        tokio::time::sleep(std::time::Duration::from_secs(30)).await;
+        check(&mut monitor, 1).await;
+        assert_eq!(monitor.services[0].retry_count, 1);
+
+        tokio::time::sleep(std::time::Duration::from_secs(31)).await;
+        check(&mut monitor, 1).await;
+        assert_eq!(monitor.services[0].retry_count, 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn restart_attempts_run_out_while_flapping() {
+        // Restarts succeed but the service keeps going down before it's stable.
+        let mut monitor = monitor(
+            &format!("systemctl_stable_duration = 60\n{SERVICE}"),
+            &[false, true, false],
+            &[true, true],
+        );
+        let alerts = check(&mut monitor, 3).await;
+
+        assert_eq!(monitor.services[0].retry_count, 2);
+        assert_eq!(alerts.len(), 1);
+        assert!(alerts[0].message.contains("OFFLINE after 2 attempts"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn alarm_marked_services_go_offline_as_alarms() {
+        let mut monitor = monitor(
+            r#"
+            systemctl_retry_attempts = 0
+            [[systemctl]]
+            name = "security_alarm_modem"
+            level = 3
+            alarm_on_offline = true
+
+            [[systemctl]]
+            name = "nginx"
+            level = 3
+            "#,
+            &[false, false],
+            &[],
+        );
+        let alerts = CapturedAlerts::new(16);
+        alerts
+            .scope(async {
+                monitor.check_service(0).await.unwrap();
+                monitor.check_service(1).await.unwrap();
+            })
+            .await;
+        let alerts = alerts.take();
+
+        assert_eq!(alerts.len(), 2);
+        assert_eq!(alerts[0].level, AlertLevel::Alarm);
+        assert_eq!(alerts[1].level, AlertLevel::Critical);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn alarm_marked_services_recover_at_their_level() {
+        let mut monitor = monitor(
+            r#"
+            systemctl_retry_attempts = 0
+            [[systemctl]]
+            name = "security_alarm_modem"
+            level = 3
+            alarm_on_offline = true
+            "#,
+            &[false, true],
+            &[],
+        );
+        let alerts = check(&mut monitor, 2).await;
+
+        assert_eq!(alerts[0].level, AlertLevel::Alarm);
+        assert_eq!(alerts[1].level, AlertLevel::Critical);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn recovery_level_defaults_to_the_offline_level() {
+        let mut monitor = monitor(
+            "[[systemctl]]\nname = \"nginx\"\nlevel = 3",
+            &[false, true],
+            &[false],
+        );
+        let alerts = check(&mut monitor, 2).await;
+
+        assert_eq!(alerts.len(), 2);
+        assert_eq!(alerts[1].level, AlertLevel::Critical);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn polls_slower_than_the_watchdog_without_restarts() {
+        let monitor = monitor(
+            &format!("systemctl_poll_interval = 60\n{SERVICE}"),
+            &[],
+            &[],
+        );
+        let restarts =
+            crate::monitors::tests::watchdog_restarts(monitor, std::time::Duration::from_secs(300))
+                .await;
+        assert_eq!(restarts, 0);
+    }
+
+    const SELF_HEALING: &str = r#"
+        systemctl_retry_attempts = 2
+        systemctl_suppress_self_recovery = true
+        [[systemctl]]
+        name = "security_alarm_modem"
+        level = 3
+    "#;
+
+    fn messages(alerts: &[AlertInfo]) -> Vec<&str> {
+        alerts.iter().map(|alert| alert.message.as_str()).collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn self_recoveries_are_not_alerted_when_suppressed() {
+        // Offline after a failed restart, then back before the next restart attempt.
+        let mut monitor = monitor(SELF_HEALING, &[false, true], &[false]);
+        let alerts = check(&mut monitor, 2).await;
+        assert_eq!(
+            messages(&alerts),
+            ["security_alarm_modem is OFFLINE after 1 attempts to restart!"]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn restart_recoveries_are_alerted_when_suppressing_self_recoveries() {
+        // Offline after a failed restart, then back after the second restart works.
+        let mut monitor = monitor(SELF_HEALING, &[false, false, true], &[false, true]);
+        let alerts = check(&mut monitor, 3).await;
+        assert_eq!(
+            messages(&alerts),
+            [
+                "security_alarm_modem is OFFLINE after 1 attempts to restart!",
+                "security_alarm_modem is now ONLINE!"
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn self_recoveries_are_alerted_by_default() {
+        let config = SELF_HEALING.replace("systemctl_suppress_self_recovery = true", "");
+        let mut monitor = monitor(&config, &[false, true], &[false]);
+        let alerts = check(&mut monitor, 2).await;
+        assert_eq!(alerts.len(), 2);
+        assert_eq!(alerts[1].message, "security_alarm_modem is now ONLINE!");
+    }
+}
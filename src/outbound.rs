@@ -0,0 +1,320 @@
+use crate::config::OutboundConfig;
+use log::{debug, info, warn};
+
+/*
+   Shared setup and checks for outbound requests. When a host allowlist is
+   configured, requests can only be made to those hosts, so a tampered config
+   can't be used to make requests to arbitrary URLs.
+*/
+
+const MAX_REDIRECTS: usize = 10;
+const RESET_RETRY_ATTEMPTS: u32 = 3;
+const RESET_RETRY_BACKOFF: std::time::Duration = std::time::Duration::from_millis(100);
+
+static OUTBOUND_CONFIG: std::sync::OnceLock<OutboundConfig> = std::sync::OnceLock::new();
+
+pub fn initialize(config: &OutboundConfig) {
+    if let Some(allowlist) = &config.host_allowlist {
+        info!(
+            "Restricting outbound requests to {} host(s).",
+            allowlist.len()
+        );
+    }
+    if OUTBOUND_CONFIG.set(config.clone()).is_err() {
+        warn!("Outbound config was already initialized!");
+    }
+}
+
+/// Get a client builder tuned for fast repeated requests to a few known hosts,
+/// keeping warm connections around so alarm bursts skip the TLS handshake.
+pub fn client_builder() -> reqwest::ClientBuilder {
+    builder_for(OUTBOUND_CONFIG.get_or_init(OutboundConfig::default))
+}
+
+fn builder_for(config: &OutboundConfig) -> reqwest::ClientBuilder {
+    let builder = reqwest::Client::builder()
+        .redirect(redirect_policy(config.host_allowlist.clone()))
+        .pool_max_idle_per_host(config.pool_max_idle_per_host)
+        .pool_idle_timeout(std::time::Duration::from_secs(config.pool_idle_timeout))
+        .tcp_nodelay(config.tcp_nodelay);
+
+    if config.http2_prior_knowledge {
+        builder.http2_prior_knowledge()
+    } else {
+        builder
+    }
+}
+
+/// Check every redirect hop against the allowlist, since an allowed host could
+/// otherwise redirect requests anywhere.
+fn redirect_policy(
+    allowlist: Option<std::collections::HashSet<String>>,
+) -> reqwest::redirect::Policy {
+    let Some(allowlist) = allowlist else {
+        return reqwest::redirect::Policy::limited(MAX_REDIRECTS);
+    };
+    reqwest::redirect::Policy::custom(move |attempt| {
+        if attempt.previous().len() >= MAX_REDIRECTS {
+            return attempt.error(anyhow::anyhow!("Too many redirects!"));
+        }
+        match check_host(&allowlist, attempt.url()) {
+            Ok(()) => attempt.follow(),
+            Err(e) => attempt.error(e),
+        }
+    })
+}
+
+/// Check if a request failed at the connection level, e.g. a reset on a flaky cellular
+/// link, rather than with an HTTP error or timeout.
+fn is_connection_error(error: &reqwest::Error) -> bool {
+    if error.is_connect() {
+        return true;
+    }
+
+    let mut source = std::error::Error::source(error);
+    while let Some(e) = source {
+        if let Some(e) = e.downcast_ref::<std::io::Error>() {
+            return matches!(
+                e.kind(),
+                std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::ConnectionAborted
+                    | std::io::ErrorKind::BrokenPipe
+                    | std::io::ErrorKind::UnexpectedEof
+            );
+        }
+        source = e.source();
+    }
+    false
+}
+
+/// Send a request, immediately retrying a few times on connection-level errors so a
+/// single reset doesn't have to wait for the much slower broadcast retry.
+pub async fn send_with_reset_retry(
+    request: reqwest::RequestBuilder,
+) -> reqwest::Result<reqwest::Response> {
+    let mut attempt = 1;
+    loop {
+        // Streaming bodies can't be cloned, so those are only ever sent once.
+        let Some(retry) = request
+            .try_clone()
+            .filter(|_| attempt < RESET_RETRY_ATTEMPTS)
+        else {
+            return request.send().await;
+        };
+        match retry.send().await {
+            Err(e) if is_connection_error(&e) => {
+                debug!("Connection error on attempt {attempt}, retrying: {e}");
+                tokio::time::sleep(RESET_RETRY_BACKOFF * attempt).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+pub fn ensure_host_allowed(url: &str) -> anyhow::Result<()> {
+    let Some(allowlist) = OUTBOUND_CONFIG
+        .get()
+        .and_then(|config| config.host_allowlist.as_ref())
+    else {
+        return Ok(());
+    };
+
+    check_host(allowlist, &reqwest::Url::parse(url)?)
+}
+
+fn check_host(
+    allowlist: &std::collections::HashSet<String>,
+    url: &reqwest::Url,
+) -> anyhow::Result<()> {
+    let host = url
+        .host_str()
+        .ok_or_else(|| anyhow::anyhow!("Outbound URL has no host!"))?;
+
+    if allowlist.contains(host) {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!(
+            "Outbound host '{host}' is not in the allowlist!"
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use warp::Filter;
+
+    fn allowlist(hosts: &[&str]) -> std::collections::HashSet<String> {
+        hosts.iter().map(|host| host.to_string()).collect()
+    }
+
+    fn config(hosts: &[&str]) -> OutboundConfig {
+        OutboundConfig {
+            host_allowlist: Some(allowlist(hosts)),
+            ..Default::default()
+        }
+    }
+
+    /// Serve /target, and /redirect to /target on the given host.
+    fn serve(redirect_host: &'static str) -> std::net::SocketAddr {
+        let (address, server) = warp::serve(warp::path("target").map(|| "target").or(
+            warp::path("redirect").and(warp::host::optional()).map(
+                move |authority: Option<warp::host::Authority>| {
+                    let port = authority.and_then(|a| a.port_u16()).unwrap_or(80);
+                    warp::redirect::found(
+                        format!("http://{redirect_host}:{port}/target")
+                            .parse::<warp::http::Uri>()
+                            .unwrap(),
+                    )
+                },
+            ),
+        ))
+        .bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        address
+    }
+
+    #[tokio::test]
+    async fn reuses_connections_for_repeated_requests() {
+        let (address, server) = warp::serve(warp::addr::remote().map(
+            |remote: Option<std::net::SocketAddr>| {
+                remote
+                    .map(|remote| remote.port())
+                    .unwrap_or_default()
+                    .to_string()
+            },
+        ))
+        .bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let client = builder_for(&OutboundConfig::default()).build().unwrap();
+        let mut ports = Vec::new();
+        for _ in 0..3 {
+            let response = client
+                .get(format!("http://{address}/"))
+                .send()
+                .await
+                .unwrap();
+            ports.push(response.text().await.unwrap());
+        }
+
+        // Each request came from the same client port, so over the same connection.
+        assert_eq!(ports[0], ports[1]);
+        assert_eq!(ports[1], ports[2]);
+    }
+
+    #[test]
+    fn allows_only_listed_hosts() {
+        let allowlist = allowlist(&["api.pushover.net"]);
+        let url = |url: &str| reqwest::Url::parse(url).unwrap();
+        assert!(check_host(&allowlist, &url("https://api.pushover.net/1/messages.json")).is_ok());
+        assert!(check_host(&allowlist, &url("https://pushover.net.evil.example/")).is_err());
+        assert!(check_host(&allowlist, &url("http://169.254.169.254/latest/meta-data")).is_err());
+    }
+
+    #[tokio::test]
+    async fn follows_redirects_to_allowed_hosts() {
+        let address = serve("127.0.0.1");
+        let client = builder_for(&config(&["127.0.0.1"])).build().unwrap();
+
+        let response = client
+            .get(format!("http://{address}/redirect"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.text().await.unwrap(), "target");
+    }
+
+    #[tokio::test]
+    async fn blocks_redirects_to_other_hosts() {
+        let address = serve("localhost");
+        let client = builder_for(&config(&["127.0.0.1"])).build().unwrap();
+
+        let error = client
+            .get(format!("http://{address}/redirect"))
+            .send()
+            .await
+            .unwrap_err();
+        assert!(error.is_redirect(), "{error:?}");
+        assert!(
+            format!("{error:?}").contains("Outbound host 'localhost' is not in the allowlist!"),
+            "{error:?}"
+        );
+    }
+
+    /// Serve each connection's response in turn, where `None` resets the connection
+    /// after reading the request. Returns the address and the number of connections.
+    async fn serve_raw(
+        responses: Vec<Option<&'static str>>,
+    ) -> (
+        std::net::SocketAddr,
+        std::sync::Arc<std::sync::atomic::AtomicUsize>,
+    ) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let connections = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let accepted = connections.clone();
+        tokio::spawn(async move {
+            for response in responses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                accepted.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                let _ = stream.read(&mut [0; 1024]).await;
+                match response {
+                    Some(response) => stream.write_all(response.as_bytes()).await.unwrap(),
+                    // A zero linger resets the connection on drop, rather than blocking.
+                    #[allow(deprecated)]
+                    None => stream.set_linger(Some(std::time::Duration::ZERO)).unwrap(),
+                }
+            }
+        });
+        (address, connections)
+    }
+
+    const OK: &str = "HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\nok";
+    const SERVER_ERROR: &str =
+        "HTTP/1.1 500 Internal Server Error\r\ncontent-length: 0\r\nconnection: close\r\n\r\n";
+
+    #[tokio::test]
+    async fn retries_a_reset_connection_immediately() {
+        let (address, connections) = serve_raw(vec![None, Some(OK)]).await;
+        let client = builder_for(&OutboundConfig::default()).build().unwrap();
+
+        let started = std::time::Instant::now();
+        let response = send_with_reset_retry(client.post(format!("http://{address}/")).body("{}"))
+            .await
+            .unwrap();
+        assert_eq!(response.text().await.unwrap(), "ok");
+        assert_eq!(connections.load(std::sync::atomic::Ordering::SeqCst), 2);
+        assert!(started.elapsed() < std::time::Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_repeated_resets() {
+        let (address, connections) = serve_raw(vec![None; 4]).await;
+        let client = builder_for(&OutboundConfig::default()).build().unwrap();
+
+        let error = send_with_reset_retry(client.get(format!("http://{address}/")))
+            .await
+            .unwrap_err();
+        assert!(is_connection_error(&error), "{error:?}");
+        assert_eq!(
+            connections.load(std::sync::atomic::Ordering::SeqCst),
+            RESET_RETRY_ATTEMPTS as usize
+        );
+    }
+
+    #[tokio::test]
+    async fn http_errors_are_not_retried() {
+        let (address, connections) = serve_raw(vec![Some(SERVER_ERROR), Some(OK)]).await;
+        let client = builder_for(&OutboundConfig::default()).build().unwrap();
+
+        let response = send_with_reset_retry(client.get(format!("http://{address}/")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 500);
+        assert_eq!(connections.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+}
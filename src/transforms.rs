@@ -0,0 +1,115 @@
+use crate::alerts::AlertInfo;
+use crate::config::AlertsConfig;
+
+/*
+   Alert transforms, applied in order by the AlertManager before an alert is broadcast.
+*/
+
+pub(crate) trait AlertTransform: Send + Sync + 'static {
+    /// Rewrite the alert in place.
+    fn apply(&self, alert: &mut AlertInfo);
+}
+
+/// Render the message through a template using the {source}, {message} and {level} placeholders.
+struct TemplateTransform {
+    template: String,
+}
+impl AlertTransform for TemplateTransform {
+    fn apply(&self, alert: &mut AlertInfo) {
+        alert.message = self
+            .template
+            .replace("{source}", &alert.source)
+            .replace("{level}", &format!("{:?}", alert.level))
+            .replace("{message}", &alert.message);
+    }
+}
+
+/// Tag the alert source with the site name, for deployments across multiple buildings.
+struct SiteTagTransform {
+    site: String,
+}
+impl AlertTransform for SiteTagTransform {
+    fn apply(&self, alert: &mut AlertInfo) {
+        alert.source = format!("{} {}", self.site, alert.source);
+    }
+}
+
+pub(crate) struct TransformChain {
+    transforms: Vec<Box<dyn AlertTransform>>,
+}
+impl TransformChain {
+    pub fn from_config(config: &AlertsConfig) -> Self {
+        let mut transforms: Vec<Box<dyn AlertTransform>> = Vec::new();
+        if let Some(template) = &config.message_template {
+            transforms.push(Box::new(TemplateTransform {
+                template: template.clone(),
+            }));
+        }
+        if let Some(site) = &config.site {
+            transforms.push(Box::new(SiteTagTransform { site: site.clone() }));
+        }
+
+        Self { transforms }
+    }
+
+    pub fn apply(&self, alert: &mut AlertInfo) {
+        for transform in &self.transforms {
+            transform.apply(alert);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alerts::AlertLevel;
+
+    fn alert() -> AlertInfo {
+        AlertInfo::new(
+            "door monitor".to_string(),
+            "Door open".to_string(),
+            AlertLevel::Warning,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn transforms_run_in_order() {
+        let chain = TransformChain {
+            transforms: vec![
+                Box::new(TemplateTransform {
+                    template: "[{message}]".to_string(),
+                }),
+                Box::new(TemplateTransform {
+                    template: "{message}!".to_string(),
+                }),
+            ],
+        };
+        let mut alert = alert();
+        chain.apply(&mut alert);
+        assert_eq!(alert.message, "[Door open]!");
+    }
+
+    #[test]
+    fn configured_chain_templates_before_tagging_the_site() {
+        let config: AlertsConfig = toml::from_str(
+            r#"
+            message_template = "{level}: {message} ({source})"
+            site = "Warehouse"
+            "#,
+        )
+        .unwrap();
+        let mut alert = alert();
+        TransformChain::from_config(&config).apply(&mut alert);
+
+        assert_eq!(alert.message, "Warning: Door open (door monitor)");
+        assert_eq!(alert.source, "Warehouse door monitor");
+    }
+
+    #[test]
+    fn empty_chain_leaves_the_alert_unchanged() {
+        let mut alert = alert();
+        TransformChain::from_config(&toml::from_str("").unwrap()).apply(&mut alert);
+        assert_eq!(alert.to_string(), "door monitor - Door open");
+    }
+}
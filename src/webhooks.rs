@@ -1,40 +1,402 @@
-use crate::alerts::{send_alert, AlertInfo, AlertLevel};
-use log::{error, info};
+use crate::alerts::{alert_channel_fill_level, send_alert, AlertInfo, AlertLevel, AlertMetadata};
+use crate::capabilities;
+use crate::config::{AppConfig, HttpConfig, HttpListenerConfig, HttpTlsConfig};
+use crate::metrics;
+use crate::monitors::trigger_check;
+use anyhow::Context;
+use futures::StreamExt;
+use log::{debug, error, info, warn};
 use warp::Filter;
 
+const TLS_HANDSHAKE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+const TLS_HANDSHAKE_CONCURRENCY: usize = 32;
+
 #[derive(Debug)]
 struct AuthError;
 impl warp::reject::Reject for AuthError {}
 
+#[derive(Debug)]
+struct AuthUnconfigured;
+impl warp::reject::Reject for AuthUnconfigured {}
+
+/// Accepted Authorization tokens. A rotated out token keeps working until its
+/// overlap ends, so webhook senders can be moved over without rejected requests.
+struct AuthTokens {
+    current: Option<String>,
+    previous: Option<(String, std::time::SystemTime)>,
+}
+impl AuthTokens {
+    /// The overlap is counted from when the token was rotated, so restarting during
+    /// it doesn't extend it. Without a rotation time it's counted from startup.
+    fn from_config(config: &HttpConfig) -> Self {
+        let overlap = std::time::Duration::from_secs(config.previous_auth_token_overlap);
+        let rotated_at = config
+            .previous_auth_token_rotated_at
+            .map_or_else(std::time::SystemTime::now, |rotated_at| {
+                std::time::UNIX_EPOCH + std::time::Duration::from_secs(rotated_at)
+            });
+        Self {
+            current: config.auth_token.clone(),
+            previous: config
+                .previous_auth_token
+                .clone()
+                .map(|previous| (previous, rotated_at + overlap)),
+        }
+    }
+
+    /// Accepts the token either bare or with a Bearer scheme, for standard clients.
+    fn is_valid(&self, header: &str) -> bool {
+        let token = match header.split_once(' ') {
+            Some((scheme, token)) if scheme.eq_ignore_ascii_case("bearer") => token.trim(),
+            _ => header,
+        };
+
+        if self
+            .current
+            .as_deref()
+            .is_some_and(|current| constant_time_eq(current, token))
+        {
+            return true;
+        }
+        self.previous.as_ref().is_some_and(|(previous, until)| {
+            constant_time_eq(previous, token) && std::time::SystemTime::now() < *until
+        })
+    }
+}
+
+/// Compare without returning early on the first difference, so response timing doesn't leak the token.
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
 #[derive(Debug, serde::Deserialize)]
 #[serde(rename_all = "PascalCase")]
 struct AlarmEvent {
     input1: Option<String>,
     extra_text: String,
+
+    #[serde(flatten)]
+    other: serde_json::Map<String, serde_json::Value>,
+}
+impl AlarmEvent {
+    /// Get a payload field by its PascalCase name, as sent by the CCTV system.
+    fn get_field(&self, field: &str) -> Option<String> {
+        match field {
+            "ExtraText" => Some(self.extra_text.clone()),
+            "Input1" => self.input1.clone(),
+            _ => self.other.get(field).map(|value| match value {
+                serde_json::Value::String(value) => value.clone(),
+                value => value.to_string(),
+            }),
+        }
+    }
+
+    /// Test inputs are alarms, and an EventType sets the level. Unknown types are still
+    /// sent as Info rather than dropped, and events without one stay Critical.
+    fn level(&self) -> AlertLevel {
+        if self.input1.as_deref() == Some("test") {
+            return AlertLevel::Alarm;
+        }
+        match self.get_field("EventType").as_deref() {
+            Some("MotionDetect") => AlertLevel::Warning,
+            Some("VideoLoss" | "VideoBlind") => AlertLevel::Critical,
+            Some(_) => AlertLevel::Info,
+            None => AlertLevel::Critical,
+        }
+    }
+
+    /// Build the alert message from only the fields allowed to be forwarded.
+    fn forwarded_message(&self, config: &HttpConfig) -> String {
+        config
+            .cctv_forwarded_fields
+            .iter()
+            .filter(|field| !config.cctv_dropped_fields.contains(*field))
+            .filter_map(|field| {
+                self.get_field(field).map(|value| match field.as_str() {
+                    "ExtraText" => value,
+                    _ => format!("{field}: {value}"),
+                })
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Events are only coalesced with earlier events of the same source, type and level,
+/// so a VideoLoss is never merged into an earlier motion event.
+type DebounceKey = (String, Option<String>, AlertLevel);
+type LastEvents =
+    std::sync::Arc<std::sync::Mutex<std::collections::BTreeMap<DebounceKey, tokio::time::Instant>>>;
+
+/// Check if an event falls within the debounce window of the last queued event like it.
+/// Each debounced event refreshes the window, so a burst is coalesced into its first event.
+fn is_debounced(last_events: &LastEvents, key: &DebounceKey, window: std::time::Duration) -> bool {
+    if window.is_zero() {
+        return false;
+    }
+
+    let now = tokio::time::Instant::now();
+    let Ok(mut last_events) = last_events.lock() else {
+        return false;
+    };
+    match last_events.get_mut(key) {
+        Some(last) if now.duration_since(*last) < window => {
+            *last = now;
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Start the debounce window of a queued event, forgetting any windows that have ended.
+/// Only called once queued, so an event that got a 503 isn't debounced when retried.
+fn record_event(last_events: &LastEvents, key: DebounceKey, window: std::time::Duration) {
+    if window.is_zero() {
+        return;
+    }
+
+    let now = tokio::time::Instant::now();
+    if let Ok(mut last_events) = last_events.lock() {
+        last_events.retain(|_, last| now.duration_since(*last) < window);
+        last_events.insert(key, now);
+    }
 }
 
 async fn handle_cctv_webhook(
     _: (),
-    payload: AlarmEvent,
-) -> Result<impl warp::Reply, warp::Rejection> {
-    info!("Received CCTV webhook: {payload:?}");
+    body: warp::hyper::body::Bytes,
+    config: std::sync::Arc<HttpConfig>,
+    last_events: LastEvents,
+) -> Result<warp::reply::WithStatus<warp::reply::Json>, warp::Rejection> {
+    // Parse here rather than with warp::body::json, so that a truncated body from a
+    // flaky connection gets a specific error the CCTV system can retry on.
+    let payload = match serde_json::from_slice::<AlarmEvent>(&body) {
+        Ok(payload) => payload,
+        Err(e) => {
+            warn!(
+                "Failed to parse CCTV webhook body ({} bytes): {e}",
+                body.len()
+            );
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&serde_json::json!({
+                    "success": false,
+                    "error_message": format!("Invalid JSON body: {e}")
+                })),
+                warp::http::StatusCode::UNPROCESSABLE_ENTITY,
+            ));
+        }
+    };
+
+    // Only log the forwarded fields, since dropped fields may be private.
+    let message = payload.forwarded_message(&config);
+    info!("Received CCTV webhook: {message:?}");
 
     let alert = AlertInfo {
         source: "cctv-webhook".to_string(),
-        message: payload.extra_text,
-        level: if payload.input1 == Some("test".to_string()) {
-            AlertLevel::Alarm
-        } else {
-            AlertLevel::Critical
-        },
+        message,
+        level: payload.level(),
+        timestamp: None,
+        metadata: AlertMetadata::default(),
+    };
+
+    let debounce = std::time::Duration::from_millis(config.cctv_debounce);
+    let key = (
+        alert.source.clone(),
+        payload.get_field("EventType"),
+        alert.level.clone(),
+    );
+    if is_debounced(&last_events, &key, debounce) {
+        debug!("Coalescing CCTV webhook into the previous event.");
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({
+                "status": "success",
+                "message": "CCTV webhook debounced"
+            })),
+            warp::http::StatusCode::ACCEPTED,
+        ));
+    }
+
+    // Shed non-alarm alerts when the pipeline is backing up, so a webhook flood can't
+    // starve alarms or exhaust memory. Alarms always try to enqueue.
+    if !alert.is_alarm() {
+        if let Some(fill_level) = alert_channel_fill_level() {
+            if fill_level >= config.shed_watermark {
+                warn!(
+                    "Shedding CCTV alert, alert channel is {:.0}% full!",
+                    fill_level * 100.0
+                );
+                return Ok(unavailable_reply());
+            }
+        }
+    }
+
+    if !try_enqueue_alert(alert, &config, "CCTV webhook").await {
+        return Ok(unavailable_reply());
+    }
+    record_event(&last_events, key, debounce);
+    Ok(queued_reply("CCTV webhook"))
+}
+
+async fn handle_panic(
+    _: (),
+    config: std::sync::Arc<HttpConfig>,
+) -> Result<warp::reply::WithStatus<warp::reply::Json>, warp::Rejection> {
+    warn!("Panic button pressed!");
+    Ok(enqueue_alert(panic_alert(), &config, "Panic").await)
+}
+
+/// A human pressed this deliberately, so it must never be suppressed.
+pub(crate) fn panic_alert() -> AlertInfo {
+    AlertInfo {
+        source: "panic-button".to_string(),
+        message: "Panic button pressed!".to_string(),
+        level: AlertLevel::Alarm,
         timestamp: None,
+        metadata: AlertMetadata {
+            unsuppressible: true,
+            ..Default::default()
+        },
+    }
+}
+
+/// Only wait for the alert to be queued, never for it to be broadcast. If the
+/// pipeline is too busy to accept it in time, the caller should retry.
+async fn enqueue_alert(
+    alert: AlertInfo,
+    config: &HttpConfig,
+    label: &str,
+) -> warp::reply::WithStatus<warp::reply::Json> {
+    if try_enqueue_alert(alert, config, label).await {
+        queued_reply(label)
+    } else {
+        unavailable_reply()
+    }
+}
+
+/// Try to queue the alert within the enqueue timeout, returning whether it was queued.
+async fn try_enqueue_alert(alert: AlertInfo, config: &HttpConfig, label: &str) -> bool {
+    let timeout = std::time::Duration::from_millis(config.enqueue_timeout);
+    match tokio::time::timeout(timeout, send_alert(alert)).await {
+        Ok(Ok(())) => true,
+        Ok(Err(e)) => {
+            error!("Failed to queue {label} alert: {e:#?}");
+            false
+        }
+        Err(_) => {
+            warn!("Timed out queueing {label} alert after {timeout:?}!");
+            false
+        }
+    }
+}
+
+fn queued_reply(label: &str) -> warp::reply::WithStatus<warp::reply::Json> {
+    warp::reply::with_status(
+        warp::reply::json(&serde_json::json!({
+            "status": "success",
+            "message": format!("{label} queued")
+        })),
+        warp::http::StatusCode::ACCEPTED,
+    )
+}
+
+fn unavailable_reply() -> warp::reply::WithStatus<warp::reply::Json> {
+    warp::reply::with_status(
+        warp::reply::json(&serde_json::json!({
+            "success": false,
+            "error_message": "Alert could not be queued"
+        })),
+        warp::http::StatusCode::SERVICE_UNAVAILABLE,
+    )
+}
+
+async fn handle_monitor_check(
+    name: String,
+    _: (),
+) -> Result<warp::reply::WithStatus<warp::reply::Json>, warp::Rejection> {
+    if !trigger_check(&name) {
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({
+                "success": false,
+                "error_message": format!("Monitor '{name}' is not running")
+            })),
+            warp::http::StatusCode::NOT_FOUND,
+        ));
+    }
+
+    info!("Triggered an immediate '{name}' monitor check!");
+    Ok(warp::reply::with_status(
+        warp::reply::json(&serde_json::json!({
+            "status": "success",
+            "message": format!("Monitor '{name}' check triggered")
+        })),
+        warp::http::StatusCode::ACCEPTED,
+    ))
+}
+
+/// Reply with JSON that was rendered once at startup.
+async fn handle_json(
+    _: (),
+    value: std::sync::Arc<serde_json::Value>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    Ok(warp::reply::json(value.as_ref()))
+}
+
+async fn handle_metrics(_: ()) -> Result<impl warp::Reply, warp::Rejection> {
+    Ok(warp::reply::with_header(
+        metrics::render(),
+        "Content-Type",
+        "text/plain; version=0.0.4",
+    ))
+}
+
+/// Fails closed, so a missing auth_token rejects everything unless auth is explicitly disabled.
+/// A listener's own auth_token is always required, otherwise the shared http auth applies.
+fn auth_filter(
+    config: &HttpConfig,
+    listener: &HttpListenerConfig,
+) -> warp::filters::BoxedFilter<((),)> {
+    let auth_tokens = match &listener.auth_token {
+        Some(token) => AuthTokens {
+            current: Some(token.clone()),
+            previous: None,
+        },
+        None if config.auth_disabled => {
+            warn!(
+                "HTTP auth is disabled on {}, accepting all requests!",
+                listener.bind_address
+            );
+            return warp::any().map(|| ()).boxed();
+        }
+        None if config.auth_token.is_none() => {
+            error!(
+                "HTTP auth_token is unset, rejecting all requests on {} until it's configured!",
+                listener.bind_address
+            );
+            return warp::any()
+                .and_then(|| async { Err::<(), _>(warp::reject::custom(AuthUnconfigured)) })
+                .boxed();
+        }
+        None => AuthTokens::from_config(config),
     };
-    let _ = send_alert(alert).await;
 
-    Ok(warp::reply::json(&serde_json::json!({
-        "status": "success",
-        "message": "CCTV webhook processed"
-    })))
+    let auth_tokens = std::sync::Arc::new(auth_tokens);
+    warp::header::<String>("Authorization")
+        .and_then(move |v: String| {
+            let auth_tokens = auth_tokens.clone();
+            async move {
+                if auth_tokens.is_valid(&v) {
+                    Ok(())
+                } else {
+                    metrics::record_auth_failure();
+                    Err(warp::reject::custom(AuthError))
+                }
+            }
+        })
+        .boxed()
 }
 
 async fn handle_rejection(
@@ -47,6 +409,11 @@ async fn handle_rejection(
             warp::http::StatusCode::UNAUTHORIZED,
             "Invalid Authorization header",
         )
+    } else if err.find::<AuthUnconfigured>().is_some() {
+        (
+            warp::http::StatusCode::SERVICE_UNAVAILABLE,
+            "Authorization is not configured",
+        )
     } else if err.find::<warp::reject::MissingHeader>().is_some() {
         (
             warp::http::StatusCode::BAD_REQUEST,
@@ -94,20 +461,822 @@ async fn handle_rejection(
     Ok(warp::reply::with_status(json_reply, code))
 }
 
+/// A connection holding a permit if limited, so its slot is released once it's closed.
+pub(crate) struct LimitedConnection {
+    stream: tokio::net::TcpStream,
+    _permit: Option<tokio::sync::OwnedSemaphorePermit>,
+}
+impl tokio::io::AsyncRead for LimitedConnection {
+    fn poll_read(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        std::pin::Pin::new(&mut self.get_mut().stream).poll_read(cx, buf)
+    }
+}
+impl tokio::io::AsyncWrite for LimitedConnection {
+    fn poll_write(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &[u8],
+    ) -> std::task::Poll<std::io::Result<usize>> {
+        std::pin::Pin::new(&mut self.get_mut().stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        std::pin::Pin::new(&mut self.get_mut().stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        std::pin::Pin::new(&mut self.get_mut().stream).poll_shutdown(cx)
+    }
+}
+
+/// Only accept connections while under the limit. Connections beyond it wait in the
+/// listen backlog, so a flood can't exhaust file descriptors needed by monitors.
+fn limited_incoming(
+    listener: tokio::net::TcpListener,
+    limit: Option<usize>,
+) -> impl futures::Stream<Item = std::io::Result<LimitedConnection>> {
+    let semaphore = limit.map(|limit| std::sync::Arc::new(tokio::sync::Semaphore::new(limit)));
+    futures::stream::unfold((listener, semaphore), |(listener, semaphore)| async move {
+        let permit = match &semaphore {
+            Some(semaphore) => Some(semaphore.clone().acquire_owned().await.ok()?),
+            None => None,
+        };
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    let connection = LimitedConnection {
+                        stream,
+                        _permit: permit,
+                    };
+                    return Some((Ok(connection), (listener, semaphore)));
+                }
+                Err(e) => {
+                    // Returning the error would stop the server, so back off instead.
+                    warn!("Failed to accept HTTP connection: {e}");
+                    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                }
+            }
+        }
+    })
+}
+
+fn tls_acceptor(config: &HttpTlsConfig) -> anyhow::Result<tokio_rustls::TlsAcceptor> {
+    use tokio_rustls::rustls;
+    use tokio_rustls::rustls::pki_types::pem::PemObject;
+
+    let certs = rustls::pki_types::CertificateDer::pem_file_iter(&config.cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .with_context(|| format!("Failed to read TLS certificate {:?}", config.cert_path))?;
+    let key = rustls::pki_types::PrivateKeyDer::from_pem_file(&config.key_path)
+        .with_context(|| format!("Failed to read TLS key {:?}", config.key_path))?;
+
+    let server_config = rustls::ServerConfig::builder_with_provider(std::sync::Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()?
+    .with_no_client_auth()
+    .with_single_cert(certs, key)?;
+    Ok(tokio_rustls::TlsAcceptor::from(std::sync::Arc::new(
+        server_config,
+    )))
+}
+
+/// A listener that's bound with its TLS loaded, so serving it can't fail.
+pub(crate) struct BoundListener {
+    address: std::net::SocketAddr,
+    tcp: tokio::net::TcpListener,
+    tls: Option<tokio_rustls::TlsAcceptor>,
+}
+impl BoundListener {
+    /// Load the TLS config and bind, naming the listener in any error.
+    pub async fn bind(listener: &HttpListenerConfig) -> anyhow::Result<Self> {
+        let address = listener.bind_address;
+        let tls = match &listener.tls {
+            Some(tls) => Some(
+                tls_acceptor(tls)
+                    .with_context(|| format!("Invalid TLS config for HTTP listener {address}"))?,
+            ),
+            None => None,
+        };
+        let tcp = tokio::net::TcpListener::bind(address)
+            .await
+            .with_context(|| format!("Failed to bind HTTP listener {address}"))?;
+
+        Ok(Self {
+            address: tcp.local_addr().unwrap_or(address),
+            tcp,
+            tls,
+        })
+    }
+}
+
+/// Bind every listener before any are served, so one bad listener fails startup
+/// rather than leaving the others running without it.
+pub(crate) async fn bind_listeners<T>(
+    listeners: Vec<(HttpListenerConfig, T)>,
+) -> anyhow::Result<Vec<(BoundListener, T)>> {
+    let mut bound = Vec::with_capacity(listeners.len());
+    for (listener, routes) in listeners {
+        bound.push((BoundListener::bind(&listener).await?, routes));
+    }
+    Ok(bound)
+}
+
+/// Serve the routes on a bound listener until shutdown, terminating TLS if configured.
+pub(crate) async fn serve(
+    listener: BoundListener,
+    routes: warp::filters::BoxedFilter<(warp::reply::Response,)>,
+    max_connections: Option<usize>,
+    mut shutdown: tokio::sync::watch::Receiver<bool>,
+) {
+    let shutdown = async move {
+        let _ = shutdown.wait_for(|shutdown| *shutdown).await;
+    };
+    let incoming = limited_incoming(listener.tcp, max_connections);
+    let limit = max_connections
+        .map(|limit| format!(" (max {limit} connections)"))
+        .unwrap_or_default();
+
+    let Some(acceptor) = listener.tls else {
+        info!("HTTP server listening on {}{limit}", listener.address);
+        warp::serve(routes)
+            .serve_incoming_with_graceful_shutdown(incoming, shutdown)
+            .await;
+        return;
+    };
+
+    let incoming = incoming
+        .map(move |connection| {
+            let acceptor = acceptor.clone();
+            async move {
+                // Bounded, so a client that never finishes its handshake can't hold a slot.
+                let handshake = acceptor.accept(connection?);
+                tokio::time::timeout(TLS_HANDSHAKE_TIMEOUT, handshake)
+                    .await
+                    .map_err(|_| std::io::Error::from(std::io::ErrorKind::TimedOut))?
+            }
+        })
+        .buffer_unordered(TLS_HANDSHAKE_CONCURRENCY)
+        .filter_map(|connection| async move {
+            // Returning the error would stop the server, so only log failed handshakes.
+            connection
+                .inspect_err(|e| debug!("Failed HTTPS handshake: {e}"))
+                .ok()
+                .map(Ok::<_, std::io::Error>)
+        });
+
+    info!("HTTPS server listening on {}{limit}", listener.address);
+    warp::serve(routes)
+        .serve_incoming_with_graceful_shutdown(incoming, shutdown)
+        .await;
+}
+
+/// Build the routes for each listener. They share all state, only differing in auth.
 pub(crate) fn get_routes(
-) -> impl Filter<Extract = (impl warp::Reply,), Error = std::convert::Infallible> + Clone {
-    let auth_header = warp::header::<String>("Authorization").and_then(|v: String| async move {
-        if v == "hello" {
-            Ok(())
-        } else {
-            Err(warp::reject::custom(AuthError))
-        }
-    });
-
-    warp::post()
-        .and(warp::path("cctv"))
-        .and(auth_header)
-        .and(warp::body::json())
-        .and_then(handle_cctv_webhook)
-        .recover(handle_rejection)
+    config: &AppConfig,
+) -> Vec<(
+    HttpListenerConfig,
+    warp::filters::BoxedFilter<(warp::reply::Response,)>,
+)> {
+    let http_config = std::sync::Arc::new(config.http.clone());
+    let with_config = warp::any().map(move || http_config.clone());
+
+    // Rendered once since the config can't change while running, with secrets redacted.
+    let effective_config = std::sync::Arc::new(
+        serde_json::to_value(config).expect("Failed to serialize effective config!"),
+    );
+    let with_effective_config = warp::any().map(move || effective_config.clone());
+
+    let capabilities = std::sync::Arc::new(
+        serde_json::to_value(
+            capabilities::describe(config).expect("Failed to describe capabilities!"),
+        )
+        .expect("Failed to serialize capabilities!"),
+    );
+    let with_capabilities = warp::any().map(move || capabilities.clone());
+
+    let last_events = LastEvents::default();
+    let with_last_events = warp::any().map(move || last_events.clone());
+
+    config
+        .http
+        .get_listeners()
+        .into_iter()
+        .map(|listener| {
+            let auth_header = auth_filter(&config.http, &listener);
+
+            let cctv = warp::post()
+                .and(warp::path("cctv"))
+                .and(auth_header.clone())
+                .and(warp::body::bytes())
+                .and(with_config.clone())
+                .and(with_last_events.clone())
+                .and_then(handle_cctv_webhook);
+
+            let panic = warp::post()
+                .and(warp::path("panic"))
+                .and(warp::path::end())
+                .and(auth_header.clone())
+                .and(with_config.clone())
+                .and_then(handle_panic);
+
+            let monitor_check = warp::post()
+                .and(warp::path!("monitors" / String / "check"))
+                .and(auth_header.clone())
+                .and_then(handle_monitor_check);
+
+            let effective_config = warp::get()
+                .and(warp::path("config"))
+                .and(warp::path::end())
+                .and(auth_header.clone())
+                .and(with_effective_config.clone())
+                .and_then(handle_json);
+
+            let capabilities = warp::get()
+                .and(warp::path("capabilities"))
+                .and(warp::path::end())
+                .and(auth_header.clone())
+                .and(with_capabilities.clone())
+                .and_then(handle_json);
+
+            let metrics_route = warp::get()
+                .and(warp::path("metrics"))
+                .and(warp::path::end())
+                .and(auth_header.clone())
+                .and_then(handle_metrics);
+
+            let routes = cctv
+                .or(panic)
+                .or(monitor_check)
+                .or(effective_config)
+                .or(capabilities)
+                .or(metrics_route)
+                .recover(handle_rejection)
+                .with(warp::log::custom(metrics::record_request))
+                .map(warp::Reply::into_response)
+                .boxed();
+            (listener, routes)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alerts::tests::CapturedAlerts;
+
+    const CERT_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/localhost.crt");
+    const KEY_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/localhost.key");
+
+    fn config(listeners: &str) -> AppConfig {
+        toml::from_str(&format!("[http]\nauth_token = \"shared\"\n{listeners}")).unwrap()
+    }
+
+    async fn bind_error(config: &AppConfig) -> String {
+        match bind_listeners(
+            config
+                .http
+                .get_listeners()
+                .into_iter()
+                .map(|l| (l, ()))
+                .collect(),
+        )
+        .await
+        {
+            Ok(_) => panic!("Listeners should have failed to bind!"),
+            Err(e) => format!("{e:#}"),
+        }
+    }
+
+    fn event(json: &str) -> AlarmEvent {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn dropped_fields_are_never_forwarded() {
+        let event = event(
+            r#"{"ExtraText": "Motion on Front", "DateTime": "2024-01-01 10:00:00", "Address": "10 High Street", "Channel": 2}"#,
+        );
+        let config: HttpConfig = toml::from_str(
+            r#"
+            cctv_forwarded_fields = ["ExtraText", "DateTime", "Address", "Channel"]
+            cctv_dropped_fields = ["Address"]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            event.forwarded_message(&config),
+            "Motion on Front\nDateTime: 2024-01-01 10:00:00\nChannel: 2"
+        );
+    }
+
+    #[test]
+    fn only_default_fields_are_forwarded() {
+        let event = event(
+            r#"{"ExtraText": "Motion on Front", "DateTime": "2024-01-01 10:00:00", "Address": "10 High Street"}"#,
+        );
+        assert_eq!(
+            event.forwarded_message(&HttpConfig::default()),
+            "Motion on Front\nDateTime: 2024-01-01 10:00:00"
+        );
+    }
+
+    #[test]
+    fn accepts_bearer_and_raw_tokens() {
+        let tokens = AuthTokens::from_config(&config("").http);
+        for header in [
+            "Bearer shared",
+            "bearer shared",
+            "shared",
+            "Bearer  shared ",
+        ] {
+            assert!(tokens.is_valid(header), "{header:?} should be accepted");
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_and_wrong_tokens() {
+        let tokens = AuthTokens::from_config(&config("").http);
+        for header in ["Bearer", "Basic shared", "Bearer wrong", ""] {
+            assert!(!tokens.is_valid(header), "{header:?} should be rejected");
+        }
+    }
+
+    /// Routes of the config's first listener.
+    fn routes(config: &AppConfig) -> warp::filters::BoxedFilter<(warp::reply::Response,)> {
+        get_routes(config).remove(0).1
+    }
+
+    async fn get_json(
+        routes: &warp::filters::BoxedFilter<(warp::reply::Response,)>,
+        path: &str,
+    ) -> (u16, serde_json::Value) {
+        let response = warp::test::request()
+            .path(path)
+            .header("Authorization", "Bearer shared")
+            .reply(routes)
+            .await;
+        (
+            response.status().as_u16(),
+            serde_json::from_slice(response.body()).unwrap(),
+        )
+    }
+
+    async fn post(
+        routes: &warp::filters::BoxedFilter<(warp::reply::Response,)>,
+        path: &str,
+        body: &str,
+    ) -> (u16, serde_json::Value) {
+        let response = warp::test::request()
+            .method("POST")
+            .path(path)
+            .header("Authorization", "Bearer shared")
+            .body(body)
+            .reply(routes)
+            .await;
+        (
+            response.status().as_u16(),
+            serde_json::from_slice(response.body()).unwrap_or_default(),
+        )
+    }
+
+    const MOTION: &str = r#"{"ExtraText": "Motion on Front", "EventType": "MotionDetect"}"#;
+    const TEST_ALARM: &str = r#"{"ExtraText": "Test", "Input1": "test"}"#;
+
+    #[tokio::test(start_paused = true)]
+    async fn cctv_webhook_queues_without_waiting_for_broadcast() {
+        let routes = routes(&config(""));
+        let alerts = CapturedAlerts::new(10);
+        let (status, body) = alerts.scope(post(&routes, "/cctv", MOTION)).await;
+
+        assert_eq!(status, 202);
+        assert_eq!(body["message"], "CCTV webhook queued");
+        let alerts = alerts.take();
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].source, "cctv-webhook");
+        assert_eq!(alerts[0].message, "Motion on Front");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cctv_webhook_rejects_truncated_bodies_with_422() {
+        let routes = routes(&config(""));
+        let alerts = CapturedAlerts::new(10);
+
+        let (status, body) = alerts
+            .scope(post(&routes, "/cctv", &MOTION[..MOTION.len() - 10]))
+            .await;
+        assert_eq!(status, 422);
+        assert_eq!(body["success"], false);
+        let error = body["error_message"].as_str().unwrap();
+        assert!(error.starts_with("Invalid JSON body: EOF"), "{error}");
+
+        let (status, body) = alerts.scope(post(&routes, "/cctv", "")).await;
+        assert_eq!(status, 422);
+        let error = body["error_message"].as_str().unwrap();
+        assert!(error.starts_with("Invalid JSON body: EOF"), "{error}");
+        assert!(alerts.take().is_empty());
+
+        // The retried body is then accepted as usual.
+        let (status, _) = alerts.scope(post(&routes, "/cctv", MOTION)).await;
+        assert_eq!(status, 202);
+        assert_eq!(alerts.take().len(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn bursts_of_events_are_coalesced_into_one() {
+        let routes = routes(&config("cctv_debounce = 2000"));
+        let alerts = CapturedAlerts::new(10);
+
+        let mut messages = Vec::new();
+        for _ in 0..5 {
+            let (status, body) = alerts.scope(post(&routes, "/cctv", MOTION)).await;
+            assert_eq!(status, 202);
+            messages.push(body["message"].as_str().unwrap().to_string());
+            tokio::time::sleep(std::time::Duration::from_millis(1500)).await;
+        }
+        // Each event refreshed the window, so the whole burst is one event.
+        assert_eq!(messages[0], "CCTV webhook queued");
+        assert!(messages[1..]
+            .iter()
+            .all(|message| message == "CCTV webhook debounced"));
+        assert_eq!(alerts.take().len(), 1);
+
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+        let (_, body) = alerts.scope(post(&routes, "/cctv", MOTION)).await;
+        assert_eq!(body["message"], "CCTV webhook queued");
+        assert_eq!(alerts.take().len(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn distinct_events_are_not_coalesced() {
+        let routes = routes(&config("cctv_debounce = 2000"));
+        let alerts = CapturedAlerts::new(10);
+        let video_loss = r#"{"ExtraText": "Front lost", "EventType": "VideoLoss"}"#;
+        let tamper = r#"{"ExtraText": "Front tampered", "EventType": "Tamper"}"#;
+
+        for body in [MOTION, video_loss, tamper, TEST_ALARM, MOTION] {
+            alerts.scope(post(&routes, "/cctv", body)).await;
+        }
+        let levels: Vec<_> = alerts.take().into_iter().map(|alert| alert.level).collect();
+        assert_eq!(
+            levels,
+            [
+                AlertLevel::Warning,
+                AlertLevel::Critical,
+                AlertLevel::Info,
+                AlertLevel::Alarm
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn events_that_failed_to_queue_are_not_debounced() {
+        let routes = routes(&config("cctv_debounce = 2000\nenqueue_timeout = 200"));
+        let alerts = CapturedAlerts::new(1);
+        alerts.scope(send_alert(panic_alert())).await.unwrap();
+
+        let (status, _) = alerts.scope(post(&routes, "/cctv", TEST_ALARM)).await;
+        assert_eq!(status, 503);
+        alerts.take();
+
+        // The sender's retry is queued, rather than being treated as part of the failed event.
+        let (status, body) = alerts.scope(post(&routes, "/cctv", TEST_ALARM)).await;
+        assert_eq!(status, 202);
+        assert_eq!(body["message"], "CCTV webhook queued");
+        assert_eq!(alerts.take().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn monitor_checks_wake_the_running_monitor() {
+        let trigger = crate::monitors::tests::test_trigger("checked");
+        let routes = routes(&config(""));
+        let notified = trigger.notified();
+        tokio::pin!(notified);
+        notified.as_mut().enable();
+
+        let (status, body) = post(&routes, "/monitors/checked/check", "").await;
+        assert_eq!(status, 202);
+        assert_eq!(body["message"], "Monitor 'checked' check triggered");
+        assert!(
+            tokio::time::timeout(std::time::Duration::from_secs(1), notified)
+                .await
+                .is_ok()
+        );
+
+        let (status, body) = post(&routes, "/monitors/unknown/check", "").await;
+        assert_eq!(status, 404);
+        assert_eq!(body["error_message"], "Monitor 'unknown' is not running");
+    }
+
+    #[tokio::test]
+    async fn monitor_checks_require_auth() {
+        let response = warp::test::request()
+            .method("POST")
+            .path("/monitors/checked/check")
+            .header("Authorization", "wrong")
+            .reply(&routes(&config("")))
+            .await;
+        assert_eq!(response.status(), 401);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn busy_pipeline_times_out_with_503() {
+        let routes = routes(&config("enqueue_timeout = 200"));
+        let alerts = CapturedAlerts::new(1);
+        alerts.scope(send_alert(panic_alert())).await.unwrap();
+
+        let started = tokio::time::Instant::now();
+        for (path, body) in [("/cctv", TEST_ALARM), ("/panic", "")] {
+            let (status, body) = alerts.scope(post(&routes, path, body)).await;
+            assert_eq!(status, 503, "{path}");
+            assert_eq!(body["error_message"], "Alert could not be queued");
+        }
+        // Each request only waited for the enqueue timeout, rather than the full pipeline.
+        assert_eq!(started.elapsed(), std::time::Duration::from_millis(400));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn near_full_channel_sheds_cctv_but_not_alarms() {
+        let routes = routes(&config("shed_watermark = 0.5"));
+        let alerts = CapturedAlerts::new(4);
+        for _ in 0..2 {
+            alerts.scope(send_alert(panic_alert())).await.unwrap();
+        }
+
+        let (status, _) = alerts.scope(post(&routes, "/cctv", MOTION)).await;
+        assert_eq!(status, 503);
+        let (status, _) = alerts.scope(post(&routes, "/cctv", TEST_ALARM)).await;
+        assert_eq!(status, 202);
+        let (status, _) = alerts.scope(post(&routes, "/panic", "")).await;
+        assert_eq!(status, 202);
+
+        let alerts = alerts.take();
+        assert_eq!(alerts.len(), 4);
+        assert!(alerts.iter().all(AlertInfo::is_alarm));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cctv_is_accepted_below_the_watermark() {
+        let routes = routes(&config("shed_watermark = 0.5"));
+        let alerts = CapturedAlerts::new(4);
+        alerts.scope(send_alert(panic_alert())).await.unwrap();
+
+        let (status, _) = alerts.scope(post(&routes, "/cctv", MOTION)).await;
+        assert_eq!(status, 202);
+    }
+
+    #[tokio::test]
+    async fn effective_config_redacts_secrets() {
+        let config = config(
+            r#"
+            previous_auth_token = "old"
+
+            [communications.pushover]
+            token = "pushover-secret"
+            recipients = [{ target = "user-key", level = 3 }]
+
+            [communications.pagerduty]
+            routing_key = "pagerduty-secret"
+            level = 4
+            "#,
+        );
+        let (status, body) = get_json(&routes(&config), "/config").await;
+        assert_eq!(status, 200);
+
+        assert_eq!(body["http"]["auth_token"], "***");
+        assert_eq!(body["http"]["previous_auth_token"], "***");
+        assert_eq!(body["communications"]["pushover"]["token"], "***");
+        assert_eq!(
+            body["communications"]["pushover"]["recipients"][0]["target"],
+            "***"
+        );
+        assert_eq!(body["communications"]["pagerduty"]["routing_key"], "***");
+        assert!(!body.to_string().contains("secret"));
+
+        // Everything else is shown as configured.
+        assert_eq!(
+            body["communications"]["pushover"]["recipients"][0]["level"],
+            3
+        );
+        assert_eq!(body["communications"]["pagerduty"]["level"], 4);
+        assert_eq!(body["http"]["bind_address"], "127.0.0.1:8080");
+    }
+
+    /// Routes accepting a previous "old" token, rotated out the given seconds ago.
+    fn rotated_routes(
+        rotated_secs_ago: u64,
+    ) -> warp::filters::BoxedFilter<(warp::reply::Response,)> {
+        let rotated_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+            - rotated_secs_ago;
+        routes(&config(&format!(
+            "previous_auth_token = \"old\"\nprevious_auth_token_overlap = 3600\nprevious_auth_token_rotated_at = {rotated_at}"
+        )))
+    }
+
+    async fn status_with_token(
+        routes: &warp::filters::BoxedFilter<(warp::reply::Response,)>,
+        token: &str,
+    ) -> u16 {
+        warp::test::request()
+            .path("/config")
+            .header("Authorization", token)
+            .reply(routes)
+            .await
+            .status()
+            .as_u16()
+    }
+
+    #[tokio::test]
+    async fn both_tokens_are_accepted_during_the_rotation_overlap() {
+        let routes = rotated_routes(60);
+        assert_eq!(status_with_token(&routes, "shared").await, 200);
+        assert_eq!(status_with_token(&routes, "Bearer old").await, 200);
+        assert_eq!(status_with_token(&routes, "wrong").await, 401);
+    }
+
+    #[tokio::test]
+    async fn only_the_current_token_is_accepted_after_the_rotation_overlap() {
+        let routes = rotated_routes(3660);
+        assert_eq!(status_with_token(&routes, "shared").await, 200);
+        assert_eq!(status_with_token(&routes, "Bearer old").await, 401);
+    }
+
+    #[tokio::test]
+    async fn unconfigured_auth_rejects_everything_with_503() {
+        let routes = routes(&toml::from_str("[http]").unwrap());
+        assert_eq!(status_with_token(&routes, "shared").await, 503);
+        let response = warp::test::request().path("/config").reply(&routes).await;
+        assert_eq!(response.status(), 503);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["error_message"], "Authorization is not configured");
+    }
+
+    #[tokio::test]
+    async fn configured_auth_checks_the_token() {
+        let routes = routes(&config(""));
+        assert_eq!(status_with_token(&routes, "shared").await, 200);
+        assert_eq!(status_with_token(&routes, "wrong").await, 401);
+    }
+
+    #[tokio::test]
+    async fn disabled_auth_accepts_everything() {
+        let routes = routes(&toml::from_str("[http]\nauth_disabled = true").unwrap());
+        let response = warp::test::request().path("/config").reply(&routes).await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(status_with_token(&routes, "wrong").await, 200);
+    }
+
+    #[tokio::test]
+    async fn effective_config_requires_auth() {
+        let response = warp::test::request()
+            .path("/config")
+            .header("Authorization", "wrong")
+            .reply(&routes(&config("")))
+            .await;
+        assert_eq!(response.status(), 401);
+    }
+
+    #[tokio::test]
+    async fn connections_beyond_the_limit_wait_for_a_slot() {
+        use futures::StreamExt;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let incoming = limited_incoming(listener, Some(2));
+        tokio::pin!(incoming);
+
+        let mut clients = Vec::new();
+        for _ in 0..3 {
+            clients.push(tokio::net::TcpStream::connect(address).await.unwrap());
+        }
+        let next = std::time::Duration::from_millis(200);
+        let first = incoming.next().await.unwrap().unwrap();
+        let _second = incoming.next().await.unwrap().unwrap();
+
+        // The third stays in the backlog without a file descriptor, until a slot frees up.
+        assert!(tokio::time::timeout(next, incoming.next()).await.is_err());
+        drop(first);
+        assert!(tokio::time::timeout(next, incoming.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn connections_are_unlimited_by_default() {
+        use futures::StreamExt;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let incoming = limited_incoming(listener, None);
+        tokio::pin!(incoming);
+
+        let mut connections = Vec::new();
+        for _ in 0..10 {
+            let _client = tokio::net::TcpStream::connect(address).await.unwrap();
+            connections.push(incoming.next().await.unwrap().unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn serves_every_listener_with_its_own_auth() {
+        let config = config(&format!(
+            r#"
+            [[http.listeners]]
+            bind_address = "127.0.0.1:0"
+
+            [[http.listeners]]
+            bind_address = "127.0.0.1:0"
+            auth_token = "vpn"
+            tls = {{ cert_path = "{CERT_PATH}", key_path = "{KEY_PATH}" }}
+            "#
+        ));
+        let listeners = bind_listeners(get_routes(&config)).await.unwrap();
+        let plain = format!("http://{}/capabilities", listeners[0].0.address);
+        let https = format!("https://{}/capabilities", listeners[1].0.address);
+
+        let (shutdown, receiver) = tokio::sync::watch::channel(false);
+        let servers =
+            tokio::spawn(futures::future::join_all(listeners.into_iter().map(
+                |(listener, routes)| serve(listener, routes, None, receiver.clone()),
+            )));
+
+        let client = reqwest::Client::builder()
+            .danger_accept_invalid_certs(true)
+            .build()
+            .unwrap();
+        let status = |url: &str, token: &str| {
+            let request = client.get(url).header("Authorization", token);
+            async move { request.send().await.unwrap().status().as_u16() }
+        };
+        assert_eq!(status(&plain, "shared").await, 200);
+        assert_eq!(status(&plain, "vpn").await, 401);
+        assert_eq!(status(&https, "vpn").await, 200);
+        assert_eq!(status(&https, "shared").await, 401);
+        assert!(
+            client
+                .get(https.replace("https", "http"))
+                .send()
+                .await
+                .is_err(),
+            "The TLS listener shouldn't serve plain HTTP"
+        );
+
+        shutdown.send(true).unwrap();
+        servers.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn reports_the_listener_with_invalid_tls() {
+        let config = config(
+            r#"
+            [[http.listeners]]
+            bind_address = "127.0.0.1:0"
+
+            [[http.listeners]]
+            bind_address = "127.0.0.2:0"
+            tls = { cert_path = "/nonexistent/cert.pem", key_path = "/nonexistent/key.pem" }
+            "#,
+        );
+        let error = bind_error(&config).await;
+        assert!(
+            error.contains("Invalid TLS config for HTTP listener 127.0.0.2:0"),
+            "{error}"
+        );
+    }
+
+    #[tokio::test]
+    async fn reports_the_listener_that_failed_to_bind() {
+        let taken = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = taken.local_addr().unwrap();
+        let config = config(&format!(
+            r#"
+            [[http.listeners]]
+            bind_address = "127.0.0.1:0"
+
+            [[http.listeners]]
+            bind_address = "{address}"
+            "#
+        ));
+        let error = bind_error(&config).await;
+        assert!(
+            error.contains(&format!("Failed to bind HTTP listener {address}")),
+            "{error}"
+        );
+    }
 }
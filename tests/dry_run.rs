@@ -0,0 +1,100 @@
+/*
+   Run the binary with --dry-run against a temporary config.toml, checking it
+   reports what the config enables without starting or reporting anything.
+*/
+
+fn dry_run(name: &str, config: &str) -> (bool, String) {
+    let dir = std::env::temp_dir().join(format!("sentinel-dry-run-{}-{name}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("config.toml"), config).unwrap();
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_sentinel"))
+        .arg("--dry-run")
+        .current_dir(&dir)
+        .env("RUST_LOG", "info")
+        .output()
+        .unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    (
+        output.status.success(),
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+    )
+}
+
+#[test]
+fn reports_configured_components() {
+    let (success, stderr) = dry_run(
+        "configured",
+        r#"
+            [http]
+            bind_address = "127.0.0.1:1"
+
+            [sentry]
+            dsn = "https://key@127.0.0.1:1/1"
+
+            [monitors]
+            digest_interval = 3600
+
+            [[monitors.pings]]
+            name = "router"
+            addr = "127.0.0.1:1"
+            level = 2
+
+            [monitors.clock]
+
+            [communications.stdout]
+            level = 1
+
+            [communications.gpio]
+            pin = 17
+        "#,
+    );
+
+    assert!(success, "{stderr}");
+    assert!(
+        stderr.contains("HTTP server would listen on 127.0.0.1:1"),
+        "{stderr}"
+    );
+    assert!(
+        stderr.contains("Enabled monitors: ping, clock, digest"),
+        "{stderr}"
+    );
+    // GPIO is only built on Linux, and would claim the pin if it were created.
+    let providers = if cfg!(target_os = "linux") {
+        "Enabled providers: stdout, gpio"
+    } else {
+        "Enabled providers: stdout"
+    };
+    assert!(stderr.contains(providers), "{stderr}");
+    assert!(!stderr.contains("Initializing Sentry"), "{stderr}");
+}
+
+#[test]
+fn fails_without_providers() {
+    let (success, stderr) = dry_run("no-providers", "");
+    assert!(!success, "{stderr}");
+    assert!(
+        stderr.contains("No CommunicationProviders would be enabled!"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn fails_without_required_monitors() {
+    let (success, stderr) = dry_run(
+        "no-monitors",
+        r#"
+            [monitors]
+            require_any = true
+
+            [communications.stdout]
+            level = 1
+        "#,
+    );
+    assert!(!success, "{stderr}");
+    assert!(
+        stderr.contains("No monitors would be enabled, but they are required!"),
+        "{stderr}"
+    );
+}
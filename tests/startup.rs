@@ -0,0 +1,66 @@
+/*
+   Start the binary against a temporary config.toml without any monitors, checking
+   require_any decides between failing startup and only serving webhooks.
+*/
+
+const CONFIG: &str = r#"
+    [http]
+    bind_address = "127.0.0.1:0"
+    auth_token = "token"
+
+    [communications.stdout]
+    level = 0
+"#;
+
+fn start(name: &str, require_any: bool) -> (std::path::PathBuf, std::process::Child) {
+    let dir = std::env::temp_dir().join(format!("sentinel-startup-{}-{name}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("config.toml"),
+        format!("{CONFIG}\n[monitors]\nrequire_any = {require_any}\n"),
+    )
+    .unwrap();
+
+    let child = std::process::Command::new(env!("CARGO_BIN_EXE_sentinel"))
+        .current_dir(&dir)
+        .env("RUST_LOG", "info")
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    (dir, child)
+}
+
+#[test]
+fn fails_when_required_monitors_are_missing() {
+    let (dir, child) = start("required", true);
+    let output = child.wait_with_output().unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success(), "{stderr}");
+    assert!(
+        stderr.contains("Startup failed, no monitors are enabled, but they are required!"),
+        "{stderr}"
+    );
+    // The shutdown is still alerted before exiting.
+    assert!(
+        stdout.contains(r#""message":"Shutting down: no monitors are enabled, but they are required","level":"Critical""#),
+        "{stdout}"
+    );
+}
+
+#[test]
+fn serves_webhooks_without_monitors_by_default() {
+    let (dir, mut child) = start("optional", false);
+    std::thread::sleep(std::time::Duration::from_secs(2));
+    let status = child.try_wait().unwrap();
+    child.kill().unwrap();
+    let output = child.wait_with_output().unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(status.is_none(), "Exited with {status:?}: {stderr}");
+    assert!(stderr.contains("HTTP server listening on"), "{stderr}");
+}